@@ -0,0 +1,60 @@
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use http::StatusCode;
+
+use super::Scope;
+use crate::supabase::SupabaseError;
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct ErrorBody {
+    pub error: String,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ApiError {
+    #[error("Missing or invalid access token")]
+    Unauthorized,
+    #[error("The access token lacks the `{0}` scope")]
+    Forbidden(Scope),
+    #[error("Two-factor authentication is enabled, create a token in the user settings instead")]
+    MfaRequired,
+    #[error("Task not found")]
+    NotFound,
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("{1}")]
+    Supabase(StatusCode, String),
+}
+
+impl ApiError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) | ApiError::MfaRequired => StatusCode::FORBIDDEN,
+            ApiError::NotFound => StatusCode::NOT_FOUND,
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Supabase(code, _) => *code,
+        }
+    }
+}
+
+impl From<supabase_rust::errors::Error> for ApiError {
+    fn from(e: supabase_rust::errors::Error) -> Self {
+        // Reuses the server fn mapping so internal errors are filtered the same way
+        let (code, err) = SupabaseError(e).into();
+        let message = match err {
+            leptos::ServerFnError::ServerError(message) => message,
+            err => err.to_string(),
+        };
+        ApiError::Supabase(code, message)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = ErrorBody {
+            error: self.to_string(),
+        };
+        (self.status_code(), Json(body)).into_response()
+    }
+}
@@ -0,0 +1,77 @@
+//! Versioned REST API, authenticated with personal access tokens instead of the session cookie
+
+mod error;
+mod tasks;
+pub mod tokens;
+
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+use crate::components::todo::quick_add::Priority;
+use crate::components::todo::{TaskReminder, TaskSchema};
+use crate::middlewares::rate_limit_sign_in;
+use crate::AppState;
+
+pub use error::ApiError;
+pub use tokens::{ApiUser, Scope};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Todo API", version = "1"),
+    paths(
+        tasks::list_tasks,
+        tasks::get_task,
+        tasks::create_task,
+        tasks::update_task,
+        tasks::delete_task,
+        tokens::create_token
+    ),
+    components(schemas(
+        TaskSchema,
+        TaskReminder,
+        Priority,
+        error::ErrorBody,
+        tokens::Scope,
+        tokens::TokenRequest,
+        tokens::TokenResponse
+    )),
+    modifiers(&PersonalAccessToken),
+    tags(
+        (name = "tasks", description = "Manage the token owner's tasks"),
+        (name = "tokens", description = "Obtain personal access tokens")
+    )
+)]
+pub struct ApiDoc;
+
+struct PersonalAccessToken;
+
+impl Modify for PersonalAccessToken {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "personal_access_token",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+            );
+        }
+    }
+}
+
+/// Routes to be nested under `/api/v1`
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/tasks", get(tasks::list_tasks).post(tasks::create_task))
+        .route(
+            "/tasks/:id",
+            get(tasks::get_task)
+                .patch(tasks::update_task)
+                .delete(tasks::delete_task),
+        )
+        // Checks a password like signing in does, and is limited the same way
+        .route(
+            "/tokens",
+            post(tokens::create_token).layer(axum::middleware::from_fn(rate_limit_sign_in)),
+        )
+        .route("/openapi.json", get(|| async { Json(ApiDoc::openapi()) }))
+}
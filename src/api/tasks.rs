@@ -0,0 +1,298 @@
+use axum::extract::{Path, Query, State};
+use axum::Json;
+use http::StatusCode;
+use leptos::serde_json;
+
+use super::error::ErrorBody;
+use super::{ApiError, ApiUser, Scope};
+use crate::components::todo::{TaskSchema, TITLE_MAX_LEN};
+use crate::supabase::Supabase;
+use crate::webhooks::TaskEvent;
+
+const TASK_COLUMNS: &str = "id,title,description,completed,due_at,tags,priority";
+const DESCRIPTION_MAX_LEN: usize = 300;
+
+#[derive(serde::Deserialize, utoipa::IntoParams, Debug)]
+pub struct TaskFilter {
+    /// Only return completed (`true`) or pending (`false`) tasks
+    completed: Option<bool>,
+}
+
+fn validate(task: &TaskSchema) -> Result<(), ApiError> {
+    if task
+        .title
+        .as_ref()
+        .is_some_and(|t| t.trim().is_empty() || t.chars().count() > TITLE_MAX_LEN)
+    {
+        return Err(ApiError::BadRequest(format!(
+            "title must be 1 to {TITLE_MAX_LEN} characters"
+        )));
+    }
+    if task
+        .description
+        .as_ref()
+        .is_some_and(|d| d.chars().count() > DESCRIPTION_MAX_LEN)
+    {
+        return Err(ApiError::BadRequest(format!(
+            "description must be at most {DESCRIPTION_MAX_LEN} characters"
+        )));
+    }
+    Ok(())
+}
+
+/// The fields of `task` a client may write. `TaskSchema` always serializes `description`,
+/// which would clear it on a partial update.
+fn writable_fields(task: TaskSchema) -> serde_json::Map<String, serde_json::Value> {
+    let mut fields = serde_json::Map::new();
+    if let Some(title) = task.title {
+        fields.insert("title".to_string(), title.into());
+    }
+    if let Some(description) = task.description {
+        fields.insert("description".to_string(), description.into());
+    }
+    if let Some(completed) = task.completed {
+        fields.insert("completed".to_string(), completed.into());
+    }
+    if let Some(due_at) = task.due_at {
+        fields.insert("due_at".to_string(), due_at.into());
+    }
+    if let Some(tags) = task.tags {
+        fields.insert("tags".to_string(), tags.into());
+    }
+    if let Some(priority) = task.priority {
+        fields.insert("priority".to_string(), priority.as_str().into());
+    }
+    fields
+}
+
+fn emit(supabase: &Supabase, user: &ApiUser, event: TaskEvent, task: &TaskSchema) {
+    tokio::spawn(crate::webhooks::emit(
+        supabase.clone(),
+        user.user_id.clone(),
+        supabase.admin_token().to_string(),
+        event,
+        task.clone(),
+    ));
+}
+
+/// List the token owner's tasks
+#[utoipa::path(
+    get,
+    path = "/api/v1/tasks",
+    tag = "tasks",
+    params(TaskFilter),
+    responses(
+        (status = 200, description = "The owner's tasks", body = [TaskSchema]),
+        (status = 401, body = ErrorBody),
+        (status = 403, description = "Missing the `read` scope", body = ErrorBody)
+    ),
+    security(("personal_access_token" = []))
+)]
+pub async fn list_tasks(
+    State(supabase): State<Supabase>,
+    user: ApiUser,
+    Query(filter): Query<TaskFilter>,
+) -> Result<Json<Vec<TaskSchema>>, ApiError> {
+    user.require(Scope::Read)?;
+
+    let mut query = supabase
+        .client
+        .query()
+        .from("tasks")
+        .select(TASK_COLUMNS)
+        .eq("author_id", user.user_id.clone())
+        .order("id.asc");
+    if let Some(completed) = filter.completed {
+        query = query.eq("completed", completed.to_string());
+    }
+    let query_response = query.auth(supabase.admin_token()).execute().await;
+
+    let tasks = supabase_rust::parse_response::<TaskSchema>(query_response).await?;
+    Ok(Json(tasks))
+}
+
+/// Get one of the token owner's tasks
+#[utoipa::path(
+    get,
+    path = "/api/v1/tasks/{id}",
+    tag = "tasks",
+    params(("id" = u32, Path, description = "Task id")),
+    responses(
+        (status = 200, body = TaskSchema),
+        (status = 401, body = ErrorBody),
+        (status = 403, description = "Missing the `read` scope", body = ErrorBody),
+        (status = 404, body = ErrorBody)
+    ),
+    security(("personal_access_token" = []))
+)]
+pub async fn get_task(
+    State(supabase): State<Supabase>,
+    user: ApiUser,
+    Path(id): Path<u32>,
+) -> Result<Json<TaskSchema>, ApiError> {
+    user.require(Scope::Read)?;
+
+    let query_response = supabase
+        .client
+        .query()
+        .from("tasks")
+        .select(TASK_COLUMNS)
+        .eq("id", id.to_string())
+        .eq("author_id", user.user_id.clone())
+        .auth(supabase.admin_token())
+        .execute()
+        .await;
+
+    supabase_rust::parse_response::<TaskSchema>(query_response)
+        .await?
+        .into_iter()
+        .next()
+        .map(Json)
+        .ok_or(ApiError::NotFound)
+}
+
+/// Create a task
+#[utoipa::path(
+    post,
+    path = "/api/v1/tasks",
+    tag = "tasks",
+    request_body = TaskSchema,
+    responses(
+        (status = 201, body = TaskSchema),
+        (status = 400, body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 403, description = "Missing the `write` scope", body = ErrorBody)
+    ),
+    security(("personal_access_token" = []))
+)]
+pub async fn create_task(
+    State(supabase): State<Supabase>,
+    user: ApiUser,
+    Json(task): Json<TaskSchema>,
+) -> Result<(StatusCode, Json<TaskSchema>), ApiError> {
+    user.require(Scope::Write)?;
+    if task.title.is_none() {
+        return Err(ApiError::BadRequest("title is required".to_string()));
+    }
+    validate(&task)?;
+
+    let mut fields = writable_fields(task);
+    fields.insert("author_id".to_string(), user.user_id.clone().into());
+
+    let query_response = supabase
+        .client
+        .query()
+        .from("tasks")
+        .insert(serde_json::Value::Object(fields).to_string())
+        .auth(supabase.admin_token())
+        .execute()
+        .await;
+
+    let task = supabase_rust::parse_response::<TaskSchema>(query_response)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or(ApiError::NotFound)?;
+
+    emit(&supabase, &user, TaskEvent::Created, &task);
+    Ok((StatusCode::CREATED, Json(task)))
+}
+
+/// Update some fields of a task
+#[utoipa::path(
+    patch,
+    path = "/api/v1/tasks/{id}",
+    tag = "tasks",
+    params(("id" = u32, Path, description = "Task id")),
+    request_body = TaskSchema,
+    responses(
+        (status = 200, body = TaskSchema),
+        (status = 400, body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 403, description = "Missing the `write` scope", body = ErrorBody),
+        (status = 404, body = ErrorBody)
+    ),
+    security(("personal_access_token" = []))
+)]
+pub async fn update_task(
+    State(supabase): State<Supabase>,
+    user: ApiUser,
+    Path(id): Path<u32>,
+    Json(task): Json<TaskSchema>,
+) -> Result<Json<TaskSchema>, ApiError> {
+    user.require(Scope::Write)?;
+    validate(&task)?;
+
+    let completed = task.completed;
+    let fields = writable_fields(task);
+    if fields.is_empty() {
+        return Err(ApiError::BadRequest("Nothing to update".to_string()));
+    }
+
+    let query_response = supabase
+        .client
+        .query()
+        .from("tasks")
+        .update(serde_json::Value::Object(fields).to_string())
+        .eq("id", id.to_string())
+        .eq("author_id", user.user_id.clone())
+        .auth(supabase.admin_token())
+        .execute()
+        .await;
+
+    let task = supabase_rust::parse_response::<TaskSchema>(query_response)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or(ApiError::NotFound)?;
+
+    let event = if completed == Some(true) {
+        TaskEvent::Completed
+    } else {
+        TaskEvent::Updated
+    };
+    emit(&supabase, &user, event, &task);
+    Ok(Json(task))
+}
+
+/// Delete a task
+#[utoipa::path(
+    delete,
+    path = "/api/v1/tasks/{id}",
+    tag = "tasks",
+    params(("id" = u32, Path, description = "Task id")),
+    responses(
+        (status = 204, description = "Task deleted"),
+        (status = 401, body = ErrorBody),
+        (status = 403, description = "Missing the `write` scope", body = ErrorBody),
+        (status = 404, body = ErrorBody)
+    ),
+    security(("personal_access_token" = []))
+)]
+pub async fn delete_task(
+    State(supabase): State<Supabase>,
+    user: ApiUser,
+    Path(id): Path<u32>,
+) -> Result<StatusCode, ApiError> {
+    user.require(Scope::Write)?;
+
+    let query_response = supabase
+        .client
+        .query()
+        .from("tasks")
+        .delete()
+        .eq("id", id.to_string())
+        .eq("author_id", user.user_id.clone())
+        .auth(supabase.admin_token())
+        .execute()
+        .await;
+
+    let task = supabase_rust::parse_response::<TaskSchema>(query_response)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or(ApiError::NotFound)?;
+
+    emit(&supabase, &user, TaskEvent::Deleted, &task);
+    Ok(StatusCode::NO_CONTENT)
+}
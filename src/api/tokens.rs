@@ -0,0 +1,271 @@
+use axum::async_trait;
+use axum::extract::{FromRef, FromRequestParts, State};
+use axum::{Extension, Json};
+use base64::prelude::{Engine as _, BASE64_URL_SAFE_NO_PAD};
+use http::request::Parts;
+use http::StatusCode;
+use leptos::serde_json;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use time::format_description::well_known::Iso8601;
+use time::OffsetDateTime;
+
+use super::error::ErrorBody;
+use super::ApiError;
+use crate::middlewares::RateLimitGuard;
+use crate::supabase::Supabase;
+
+/// Makes leaked tokens easy to spot by secret scanners
+pub const TOKEN_PREFIX: &str = "todo_pat_";
+
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Read,
+    Write,
+}
+
+impl std::fmt::Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Scope::Read => write!(f, "read"),
+            Scope::Write => write!(f, "write"),
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default)]
+pub struct TokenSchema {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<Scope>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<String>,
+}
+
+/// A new random token, only ever shown to the user once
+pub fn generate() -> String {
+    let mut buf = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut buf);
+    format!("{TOKEN_PREFIX}{}", BASE64_URL_SAFE_NO_PAD.encode(buf))
+}
+
+/// Tokens are stored as their SHA-256, they carry enough entropy not to need a slow hash
+pub fn hash(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// The owner of the bearer token of an API request
+#[derive(Debug, Clone)]
+pub struct ApiUser {
+    pub user_id: String,
+    pub scopes: Vec<Scope>,
+}
+
+impl ApiUser {
+    pub fn require(&self, scope: Scope) -> Result<(), ApiError> {
+        if self.scopes.contains(&scope) {
+            Ok(())
+        } else {
+            Err(ApiError::Forbidden(scope))
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ApiUser
+where
+    Supabase: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let token = parts
+            .headers
+            .get(http::header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .filter(|token| token.starts_with(TOKEN_PREFIX))
+            .ok_or(ApiError::Unauthorized)?;
+
+        let supabase = Supabase::from_ref(state);
+        let token_hash = hash(token);
+
+        let query = supabase
+            .client
+            .query()
+            .from("personal_access_tokens")
+            .select("id,owner_id,scopes")
+            .eq("token_hash", token_hash)
+            .auth(supabase.admin_token())
+            .execute()
+            .await;
+
+        let row = supabase_rust::parse_response::<TokenSchema>(query)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or(ApiError::Unauthorized)?;
+
+        if let Some(id) = row.id {
+            let supabase = supabase.clone();
+            tokio::spawn(async move {
+                let now = OffsetDateTime::now_utc().format(&Iso8601::DEFAULT).unwrap();
+                let _ = supabase
+                    .client
+                    .query()
+                    .from("personal_access_tokens")
+                    .update(serde_json::json!({ "last_used_at": now }).to_string())
+                    .eq("id", id.to_string())
+                    .auth(supabase.admin_token())
+                    .execute()
+                    .await;
+            });
+        }
+
+        Ok(ApiUser {
+            user_id: row.owner_id.ok_or(ApiError::Unauthorized)?,
+            scopes: row.scopes.unwrap_or_default(),
+        })
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema, Debug)]
+pub struct TokenRequest {
+    pub email: String,
+    pub password: String,
+    /// Shown in the user's settings, defaults to "API token"
+    pub name: Option<String>,
+    /// Defaults to `["read"]`
+    pub scopes: Option<Vec<Scope>>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema, Debug)]
+pub struct TokenResponse {
+    pub token: String,
+}
+
+/// Exchange an email and password for a personal access token.
+/// Accounts with two-factor authentication, authenticators or passkeys, have to create their
+/// tokens in the user settings.
+#[utoipa::path(
+    post,
+    path = "/api/v1/tokens",
+    tag = "tokens",
+    request_body = TokenRequest,
+    responses(
+        (status = 201, body = TokenResponse),
+        (status = 400, body = ErrorBody),
+        (status = 401, description = "Wrong email or password", body = ErrorBody),
+        (status = 403, description = "The account has an authenticator or a passkey", body = ErrorBody),
+        (status = 429, description = "Too many failed attempts, see `Retry-After`")
+    )
+)]
+pub async fn create_token(
+    State(supabase): State<Supabase>,
+    Extension(rate_limit): Extension<RateLimitGuard>,
+    Json(request): Json<TokenRequest>,
+) -> Result<(StatusCode, Json<TokenResponse>), ApiError> {
+    use supabase_rust::schema::MFAFactorStatus;
+
+    let name = request
+        .name
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .unwrap_or("API token".to_string());
+    if name.chars().count() > 60 {
+        return Err(ApiError::BadRequest(
+            "name must be at most 60 characters".to_string(),
+        ));
+    }
+    let scopes = request
+        .scopes
+        .filter(|s| !s.is_empty())
+        .unwrap_or(vec![Scope::Read]);
+
+    let access_token = match supabase
+        .client
+        .sign_in_password(&request.email, &request.password)
+        .await
+    {
+        Ok(access_token) => access_token,
+        Err(e) => {
+            // Supabase being down isn't a failed attempt
+            if e.http_status > 499 {
+                rate_limit.release().await;
+            }
+            return Err(ApiError::Unauthorized);
+        }
+    };
+    rate_limit.record_success().await;
+    let user_id = access_token.user.id.clone();
+
+    let factors = supabase
+        .client
+        .mfa_list_factors(user_id.clone(), supabase.admin_token())
+        .await;
+
+    // Passkeys are a second factor too, as when signing in. Failing to tell refuses the token.
+    let has_mfa = match factors {
+        Ok(factors)
+            if factors
+                .iter()
+                .any(|f| f.status != MFAFactorStatus::Unverified) =>
+        {
+            Ok(true)
+        }
+        Ok(_) => supabase
+            .list_passkeys(&user_id)
+            .await
+            .map(|passkeys| !passkeys.is_empty()),
+        Err(e) => Err(e),
+    };
+
+    let result = match has_mfa {
+        Ok(true) => Err(ApiError::MfaRequired),
+        Ok(false) => {
+            let token = generate();
+            let row = serde_json::to_string(&TokenSchema {
+                owner_id: Some(user_id),
+                name: Some(name),
+                token_hash: Some(hash(&token)),
+                scopes: Some(scopes),
+                ..Default::default()
+            })
+            .unwrap();
+
+            let query = supabase
+                .client
+                .query()
+                .from("personal_access_tokens")
+                .insert(row)
+                .auth(&access_token.access_token)
+                .execute()
+                .await;
+
+            supabase_rust::parse_response::<TokenSchema>(query)
+                .await
+                .map(|_| token)
+                .map_err(ApiError::from)
+        }
+        Err(e) => Err(e.into()),
+    };
+
+    // The Supabase session was only needed to create the token
+    let _ = supabase.logout_session(&access_token.access_token).await;
+
+    result.map(|token| (StatusCode::CREATED, Json(TokenResponse { token })))
+}
@@ -4,7 +4,8 @@ use leptos_meta::*;
 use leptos_router::*;
 
 use super::pages::{
-    AddNewAuthenticator, HomePage, SignInPage, SignUpPage, UserSettings, VerifyMultiFactorAuth,
+    AddNewAuthenticator, AdminAuditLog, AdminPage, AdminUserDetail, AdminUsers, HomePage,
+    ResetPasswordPage, SignInPage, SignUpPage, UserSettings, VerifyMultiFactorAuth,
 };
 
 #[component]
@@ -43,6 +44,12 @@ pub fn App() -> impl IntoView {
             <Route path="/user" view=UserSettings/>
             <Route path="/user/add_2fa" view=AddNewAuthenticator/>
             <Route path="/user/authenticate" view=VerifyMultiFactorAuth/>
+            <Route path="/user/password" view=ResetPasswordPage/>
+            <Route path="/admin" view=AdminPage>
+              <Route path="" view=AdminUsers/>
+              <Route path="users/:id" view=AdminUserDetail/>
+              <Route path="audit" view=AdminAuditLog/>
+            </Route>
           </Routes>
         </Router>
       </main>
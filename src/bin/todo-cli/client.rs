@@ -0,0 +1,173 @@
+use std::path::PathBuf;
+
+use leptos::serde_json;
+use todo_leptos_supabase::TaskSchema;
+
+#[derive(thiserror::Error, Debug)]
+pub enum CliError {
+    #[error("Not logged in, run `todo-cli login` or pass --token")]
+    NoToken,
+    #[error("{0}")]
+    Usage(&'static str),
+    #[error("{status}: {message}")]
+    Api { status: u16, message: String },
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(serde::Deserialize)]
+struct ErrorBody {
+    error: String,
+}
+
+#[derive(serde::Deserialize)]
+struct TokenResponse {
+    token: String,
+}
+
+pub struct ApiClient {
+    http: reqwest::Client,
+    base: String,
+    token: Option<String>,
+}
+
+impl ApiClient {
+    pub fn new(url: &str, token: Option<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base: format!("{}/api/v1", url.trim_end_matches('/')),
+            token,
+        }
+    }
+
+    fn authorized(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::RequestBuilder, CliError> {
+        let token = self.token.as_ref().ok_or(CliError::NoToken)?;
+        Ok(request.bearer_auth(token))
+    }
+
+    /// Turns non-2xx responses into [`CliError::Api`] with the server's message
+    async fn check(response: reqwest::Response) -> Result<reqwest::Response, CliError> {
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let message = response
+            .json::<ErrorBody>()
+            .await
+            .map(|body| body.error)
+            .unwrap_or_else(|_| status.canonical_reason().unwrap_or_default().to_string());
+        Err(CliError::Api {
+            status: status.as_u16(),
+            message,
+        })
+    }
+
+    pub async fn login(
+        &self,
+        email: &str,
+        password: &str,
+        write: bool,
+    ) -> Result<String, CliError> {
+        let hostname = std::env::var("HOSTNAME").unwrap_or("terminal".to_string());
+        let scopes = if write {
+            vec!["read", "write"]
+        } else {
+            vec!["read"]
+        };
+        let response = self
+            .http
+            .post(format!("{}/tokens", self.base))
+            .json(&serde_json::json!({
+                "email": email,
+                "password": password,
+                "name": format!("todo-cli ({hostname})"),
+                "scopes": scopes,
+            }))
+            .send()
+            .await?;
+        Ok(Self::check(response)
+            .await?
+            .json::<TokenResponse>()
+            .await?
+            .token)
+    }
+
+    pub async fn list(&self, completed: Option<bool>) -> Result<Vec<TaskSchema>, CliError> {
+        let mut request = self.http.get(format!("{}/tasks", self.base));
+        if let Some(completed) = completed {
+            request = request.query(&[("completed", completed)]);
+        }
+        let response = self.authorized(request)?.send().await?;
+        Ok(Self::check(response).await?.json().await?)
+    }
+
+    pub async fn create(&self, task: &TaskSchema) -> Result<TaskSchema, CliError> {
+        let request = self.http.post(format!("{}/tasks", self.base)).json(task);
+        let response = self.authorized(request)?.send().await?;
+        Ok(Self::check(response).await?.json().await?)
+    }
+
+    pub async fn update(&self, id: u32, task: &TaskSchema) -> Result<TaskSchema, CliError> {
+        let request = self
+            .http
+            .patch(format!("{}/tasks/{id}", self.base))
+            .json(task);
+        let response = self.authorized(request)?.send().await?;
+        Ok(Self::check(response).await?.json().await?)
+    }
+
+    pub async fn delete(&self, id: u32) -> Result<(), CliError> {
+        let request = self.http.delete(format!("{}/tasks/{id}", self.base));
+        let response = self.authorized(request)?.send().await?;
+        Self::check(response).await?;
+        Ok(())
+    }
+}
+
+fn token_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_dir.join("todo-cli").join("token"))
+}
+
+pub fn saved_token() -> Option<String> {
+    std::fs::read_to_string(token_path()?)
+        .ok()
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+}
+
+pub fn save_token(token: &str) -> Result<PathBuf, CliError> {
+    let path = token_path().ok_or(CliError::Usage("Couldn't find a config directory"))?;
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    // Created owner-only, the token never sits in a world-readable file
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&path)?;
+    // The mode only applies to new files, one from an older version is tightened too
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    }
+    std::io::Write::write_all(&mut file, token.as_bytes())?;
+    Ok(path)
+}
+
+pub fn forget_token() -> Result<(), CliError> {
+    match token_path().map(std::fs::remove_file) {
+        Some(Err(e)) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,214 @@
+//! Terminal client for the `/api/v1` REST API.
+//!
+//! Authenticates with a personal access token (`--token` or `TODO_TOKEN`) or the token saved by
+//! `todo-cli login`.
+
+mod client;
+mod output;
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use todo_leptos_supabase::TaskSchema;
+
+use client::{ApiClient, CliError};
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "todo-cli",
+    version,
+    about = "Manage your tasks from the terminal"
+)]
+struct Cli {
+    /// Base URL of the app
+    #[arg(
+        long,
+        env = "TODO_URL",
+        default_value = "http://127.0.0.1:3000",
+        global = true
+    )]
+    url: String,
+    /// Personal access token, overrides the one saved by `login`
+    #[arg(long, env = "TODO_TOKEN", hide_env_values = true, global = true)]
+    token: Option<String>,
+    #[arg(short, long, value_enum, default_value_t = Format::Table, global = true)]
+    output: Format,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Table,
+    Json,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Exchange your email and password for a personal access token and save it
+    Login {
+        #[arg(long)]
+        email: String,
+        /// Also allow creating, editing and deleting tasks
+        #[arg(long)]
+        write: bool,
+    },
+    /// Forget the saved token
+    Logout,
+    /// Create a task
+    Add {
+        title: String,
+        #[arg(short, long)]
+        description: Option<String>,
+    },
+    /// List tasks
+    Ls {
+        /// Only completed tasks
+        #[arg(long, conflicts_with = "pending")]
+        done: bool,
+        /// Only pending tasks
+        #[arg(long)]
+        pending: bool,
+        /// Only tasks whose title or description contains this text
+        #[arg(short, long)]
+        grep: Option<String>,
+    },
+    /// Mark a task as completed
+    Done {
+        id: u32,
+        /// Mark it as pending again
+        #[arg(long)]
+        undo: bool,
+    },
+    /// Change a task's title or description
+    Edit {
+        id: u32,
+        #[arg(short, long)]
+        title: Option<String>,
+        #[arg(short, long)]
+        description: Option<String>,
+    },
+    /// Delete a task
+    Rm { id: u32 },
+    /// Write every task as JSON
+    Export {
+        /// Defaults to stdout
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+    },
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), CliError> {
+    let token = cli.token.or_else(client::saved_token);
+    let api = ApiClient::new(&cli.url, token);
+
+    match cli.command {
+        Command::Login { email, write } => {
+            let password = rpassword::prompt_password("Password: ")?;
+            let token = api.login(&email, &password, write).await?;
+            let path = client::save_token(&token)?;
+            println!("Token saved to {}", path.display());
+        }
+        Command::Logout => {
+            client::forget_token()?;
+            println!("Token removed, revoke it in the user settings if it leaked");
+        }
+        Command::Add { title, description } => {
+            let task = api
+                .create(&TaskSchema {
+                    title: Some(title),
+                    description,
+                    ..Default::default()
+                })
+                .await?;
+            output::print_tasks(&[task], cli.output);
+        }
+        Command::Ls {
+            done,
+            pending,
+            grep,
+        } => {
+            let completed = match (done, pending) {
+                (true, _) => Some(true),
+                (_, true) => Some(false),
+                _ => None,
+            };
+            let mut tasks = api.list(completed).await?;
+            if let Some(needle) = grep.map(|g| g.to_lowercase()) {
+                tasks.retain(|t| {
+                    [&t.title, &t.description].iter().any(|field| {
+                        field
+                            .as_ref()
+                            .is_some_and(|f| f.to_lowercase().contains(&needle))
+                    })
+                });
+            }
+            output::print_tasks(&tasks, cli.output);
+        }
+        Command::Done { id, undo } => {
+            let task = api
+                .update(
+                    id,
+                    &TaskSchema {
+                        completed: Some(!undo),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+            output::print_tasks(&[task], cli.output);
+        }
+        Command::Edit {
+            id,
+            title,
+            description,
+        } => {
+            if title.is_none() && description.is_none() {
+                return Err(CliError::Usage("pass --title and/or --description"));
+            }
+            let task = api
+                .update(
+                    id,
+                    &TaskSchema {
+                        title,
+                        description,
+                        ..Default::default()
+                    },
+                )
+                .await?;
+            output::print_tasks(&[task], cli.output);
+        }
+        Command::Rm { id } => {
+            api.delete(id).await?;
+            if cli.output == Format::Json {
+                println!("{{\"deleted\":{id}}}");
+            } else {
+                println!("Deleted task {id}");
+            }
+        }
+        Command::Export { file } => {
+            let tasks = api.list(None).await?;
+            let json = output::to_json(&tasks);
+            match file {
+                Some(path) => {
+                    std::fs::write(&path, json)?;
+                    eprintln!("Exported {} tasks to {}", tasks.len(), path.display());
+                }
+                None => println!("{json}"),
+            }
+        }
+    }
+    Ok(())
+}
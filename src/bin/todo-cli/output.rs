@@ -0,0 +1,69 @@
+use leptos::serde_json;
+use todo_leptos_supabase::TaskSchema;
+
+use super::Format;
+
+pub fn to_json(tasks: &[TaskSchema]) -> String {
+    serde_json::to_string_pretty(tasks).unwrap()
+}
+
+pub fn print_tasks(tasks: &[TaskSchema], format: Format) {
+    match format {
+        Format::Json => println!("{}", to_json(tasks)),
+        Format::Table => println!("{}", table(tasks)),
+    }
+}
+
+fn table(tasks: &[TaskSchema]) -> String {
+    if tasks.is_empty() {
+        return "No tasks".to_string();
+    }
+
+    let rows: Vec<[String; 5]> = tasks
+        .iter()
+        .map(|t| {
+            [
+                t.id.map(|id| id.to_string()).unwrap_or_default(),
+                if t.completed.unwrap_or_default() {
+                    "x"
+                } else {
+                    " "
+                }
+                .to_string(),
+                t.title.clone().unwrap_or_default(),
+                t.due_at
+                    .as_deref()
+                    .map(|due| {
+                        due.chars()
+                            .take(16)
+                            .collect::<String>()
+                            .replacen('T', " ", 1)
+                    })
+                    .unwrap_or_default(),
+                t.description.clone().unwrap_or_default(),
+            ]
+        })
+        .collect();
+    let header = ["ID", "DONE", "TITLE", "DUE", "DESCRIPTION"].map(String::from);
+
+    let mut widths = header.clone().map(|h| h.chars().count());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    std::iter::once(&header)
+        .chain(&rows)
+        .map(|row| {
+            row.iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{cell:<width$}"))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -1,9 +1,25 @@
 mod form_fields;
+pub mod oauth;
+pub mod passkey;
+#[cfg(feature = "ssr")]
+pub mod pkce;
 pub mod provider;
+pub mod recovery;
+mod recovery_codes;
+#[cfg(feature = "ssr")]
+pub mod redirect;
 pub mod signin;
 pub mod signup;
+mod step_up;
+#[cfg(feature = "ssr")]
+pub mod trusted_device;
 
+pub use oauth::OAuthProvider;
+pub use passkey::{PasskeyInfo, PasskeySignin, PasskeyVerify};
 pub use provider::AuthProvider;
+pub use recovery::{EmailLinkForm, SetNewPasswordForm};
+pub use recovery_codes::RecoveryCodeList;
+pub use step_up::{follow_step_up, use_step_up, SignInRequired, StepUp};
 
 use form_fields::AuthFormFields;
 
@@ -26,8 +42,13 @@ use leptos::*;
 #[cfg(feature = "ssr")]
 use crate::supabase::{AppUser, AuthSession};
 
+/// Signs `user` in and sends them on to `callback`, by way of the 2FA challenge if they have one
 #[cfg(feature = "ssr")]
-pub async fn login(mut user: AppUser, mut session: AuthSession) -> Result<(), ServerFnError> {
+pub async fn login(
+    mut user: AppUser,
+    mut session: AuthSession,
+    callback: &str,
+) -> Result<(), ServerFnError> {
     let supabase = expect_context::<crate::supabase::Supabase>();
     // Check if the user has verified any 2FA
     let factors = supabase
@@ -36,22 +57,75 @@ pub async fn login(mut user: AppUser, mut session: AuthSession) -> Result<(), Se
         .await
         .map_err(crate::supabase::map_err)?;
     let factors = filter_verified_factors(factors, &user.identity.auth_token).await;
-    user.identity.has_mfa = !factors.is_empty();
+    user.identity.has_mfa =
+        !factors.is_empty() || supabase.has_passkeys(&user.identity.user_id).await;
 
+    if user.identity.needs_second_factor() {
+        user.identity.trusted_device = trusted_device::verify(&user.identity.user_id).await;
+    }
+
+    record_device(&session).await?;
     if session.login(&user).await.is_err() {
         expect_context::<leptos_axum::ResponseOptions>()
             .set_status(http::StatusCode::INTERNAL_SERVER_ERROR);
         return Err(ServerFnError::ServerError("Sign in Error".to_string()));
     }
 
-    if factors.is_empty() {
-        leptos_axum::redirect("/");
+    // A passkey sign in already counts as both factors, trusted browsers skip the challenge
+    let callback = redirect::safe_callback(callback);
+    if !user.identity.needs_second_factor() || user.identity.trusted_device.is_some() {
+        leptos_axum::redirect(&callback);
     } else {
-        leptos_axum::redirect(&format!("/user/authenticate?cb={}", "/"));
+        leptos_axum::redirect(&StepUp::new(&callback).location);
+    }
+    Ok(())
+}
+
+/// Keeps the client's user agent and address with the session about to be created,
+/// for the devices list in the user settings
+#[cfg(feature = "ssr")]
+pub async fn record_device(session: &AuthSession) -> Result<(), ServerFnError> {
+    use crate::supabase::DeviceInfo;
+    use axum::extract::ConnectInfo;
+    use std::net::SocketAddr;
+
+    let headers = leptos_axum::extract::<http::HeaderMap>().await?;
+    let peer = leptos_axum::extract::<ConnectInfo<SocketAddr>>()
+        .await
+        .ok()
+        .map(|ConnectInfo(addr)| addr);
+
+    let device = DeviceInfo::from_request(&headers, peer);
+    if let Err(e) = session
+        .session
+        .insert(DeviceInfo::SESSION_KEY, device)
+        .await
+    {
+        tracing::error!("\nrecord_device - {e:?}");
     }
     Ok(())
 }
 
+/// Checks `password` against the account by signing in on the side, that session is
+/// ended right away. Responds `401` when it doesn't match.
+#[cfg(feature = "ssr")]
+pub async fn verify_password(email: &str, password: &str) -> Result<(), ServerFnError> {
+    let supabase = expect_context::<crate::supabase::Supabase>();
+
+    match supabase.client.sign_in_password(email, password).await {
+        Ok(access_token) => {
+            let _ = supabase.logout_session(&access_token.access_token).await;
+            Ok(())
+        }
+        Err(e) if e.http_status > 499 => Err(crate::supabase::map_err(e)),
+        Err(_) => {
+            expect_context::<leptos_axum::ResponseOptions>()
+                .set_status(http::StatusCode::UNAUTHORIZED);
+            Err(ServerFnError::new("The current password is incorrect"))
+        }
+    }
+}
+
 #[cfg(feature = "ssr")]
 pub async fn filter_verified_factors(
     factors: Vec<supabase_rust::schema::MFAFactor>,
@@ -0,0 +1,65 @@
+/// An OAuth provider enabled in Supabase and offered on the sign in page
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct OAuthProvider {
+    /// Supabase's name for the provider, e.g. `discord`
+    pub id: String,
+    pub label: String,
+    /// UIkit icon name
+    pub icon: String,
+    pub scopes: Vec<String>,
+}
+
+#[cfg(feature = "ssr")]
+impl OAuthProvider {
+    /// Providers listed in `OAUTH_PROVIDERS` (comma separated, `discord` when unset).
+    /// Each can override its defaults with `OAUTH_<ID>_LABEL`, `OAUTH_<ID>_ICON` and
+    /// `OAUTH_<ID>_SCOPES` (space separated).
+    pub fn from_env() -> Vec<Self> {
+        let enabled = std::env::var("OAUTH_PROVIDERS").unwrap_or("discord".to_string());
+
+        enabled
+            .split(',')
+            .map(|id| id.trim().to_lowercase())
+            .filter(|id| !id.is_empty())
+            .map(|id| {
+                let (label, icon, scopes) = Self::defaults(&id);
+                let var = |name: &str| {
+                    std::env::var(format!("OAUTH_{}_{name}", id.to_uppercase()))
+                        .ok()
+                        .filter(|value| !value.trim().is_empty())
+                };
+
+                Self {
+                    label: var("LABEL").unwrap_or(label),
+                    icon: var("ICON").unwrap_or(icon.to_string()),
+                    scopes: var("SCOPES")
+                        .unwrap_or(scopes.to_string())
+                        .split_whitespace()
+                        .map(str::to_string)
+                        .collect(),
+                    id,
+                }
+            })
+            .collect()
+    }
+
+    /// Label, icon and scopes of the providers known to work out of the box
+    fn defaults(id: &str) -> (String, &'static str, &'static str) {
+        match id {
+            "discord" => ("Discord".to_string(), "discord", "email identify"),
+            "github" => ("GitHub".to_string(), "github", "read:user user:email"),
+            "gitlab" => ("GitLab".to_string(), "gitlab", "read_user"),
+            "google" => ("Google".to_string(), "google", "email profile"),
+            "azure" => ("Microsoft".to_string(), "microsoft", "email"),
+            "twitter" => ("Twitter".to_string(), "twitter", ""),
+            other => {
+                let mut chars = other.chars();
+                let label = chars
+                    .next()
+                    .map(|first| first.to_uppercase().chain(chars).collect())
+                    .unwrap_or_default();
+                (label, "sign-in", "")
+            }
+        }
+    }
+}
@@ -0,0 +1,276 @@
+//! Passkeys, either to sign in without a password or as the second factor.
+//! Each ceremony is two round trips to the server with `navigator.credentials` in between.
+
+use leptos::*;
+use webauthn_rs_proto::{PublicKeyCredential, RequestChallengeResponse};
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct PasskeyInfo {
+    pub id: u64,
+    pub name: String,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+}
+
+/// Options for the browser along with the id the server tracks the ceremony by
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct PasskeyChallenge<T> {
+    pub ceremony_id: String,
+    pub options: T,
+}
+
+#[cfg(not(feature = "ssr"))]
+mod browser {
+    use wasm_bindgen::{JsCast, JsValue};
+    use wasm_bindgen_futures::JsFuture;
+    use webauthn_rs_proto::{
+        CreationChallengeResponse, PublicKeyCredential, RegisterPublicKeyCredential,
+        RequestChallengeResponse,
+    };
+
+    fn js_error(e: JsValue) -> String {
+        e.dyn_ref::<js_sys::Error>()
+            .map(|e| String::from(e.message()))
+            .unwrap_or("The passkey request was cancelled".to_string())
+    }
+
+    pub async fn create_credential(
+        options: CreationChallengeResponse,
+    ) -> Result<RegisterPublicKeyCredential, String> {
+        let options: web_sys::CredentialCreationOptions = options.into();
+        let promise = leptos::window()
+            .navigator()
+            .credentials()
+            .create_with_options(&options)
+            .map_err(js_error)?;
+        let credential = JsFuture::from(promise).await.map_err(js_error)?;
+        Ok(credential
+            .unchecked_into::<web_sys::PublicKeyCredential>()
+            .into())
+    }
+
+    pub async fn get_credential(
+        options: RequestChallengeResponse,
+    ) -> Result<PublicKeyCredential, String> {
+        let options: web_sys::CredentialRequestOptions = options.into();
+        let promise = leptos::window()
+            .navigator()
+            .credentials()
+            .get_with_options(&options)
+            .map_err(js_error)?;
+        let credential = JsFuture::from(promise).await.map_err(js_error)?;
+        Ok(credential
+            .unchecked_into::<web_sys::PublicKeyCredential>()
+            .into())
+    }
+}
+
+/// Islands only run their ceremonies in the browser
+#[cfg(feature = "ssr")]
+mod browser {
+    use webauthn_rs_proto::{
+        CreationChallengeResponse, PublicKeyCredential, RegisterPublicKeyCredential,
+        RequestChallengeResponse,
+    };
+
+    pub async fn create_credential(
+        _: CreationChallengeResponse,
+    ) -> Result<RegisterPublicKeyCredential, String> {
+        Err("Passkeys are only available in the browser".to_string())
+    }
+
+    pub async fn get_credential(
+        _: RequestChallengeResponse,
+    ) -> Result<PublicKeyCredential, String> {
+        Err("Passkeys are only available in the browser".to_string())
+    }
+}
+
+pub use browser::{create_credential, get_credential};
+
+#[island]
+pub fn PasskeySignin(callback: String) -> impl IntoView {
+    let signin_action = create_action(move |_: &()| {
+        let callback = callback.clone();
+        async move {
+            let challenge = start_passkey_login().await.map_err(|e| e.to_string())?;
+            let credential = get_credential(challenge.options).await?;
+            finish_passkey_login(challenge.ceremony_id, credential, callback)
+                .await
+                .map_err(|e| e.to_string())
+        }
+    });
+
+    view! {
+      <button
+        type="button"
+        class="uk-button uk-button-default uk-dark uk-box-shadow-small uk-margin-top uk-inline"
+        disabled=signin_action.pending()
+        on:click=move |_| signin_action.dispatch(())
+      >
+        <span uk-icon="icon: fingerprint" class="uk-form-icon"></span>
+        <div class="uk-margin-left">"Sign in with a passkey"</div>
+      </button>
+      {move || {
+          signin_action
+              .value()
+              .get()
+              .and_then(Result::err)
+              .map(|e| view! { <p class="uk-text-danger">{e}</p> })
+      }}
+    }
+}
+
+#[island]
+pub fn PasskeyVerify(callback: String) -> impl IntoView {
+    let verify_action = create_action(move |_: &()| {
+        let callback = callback.clone();
+        async move {
+            let challenge = start_passkey_assertion().await.map_err(|e| e.to_string())?;
+            let credential = get_credential(challenge.options).await?;
+            finish_passkey_assertion(challenge.ceremony_id, credential, callback)
+                .await
+                .map_err(|e| e.to_string())
+        }
+    });
+
+    view! {
+      <div class="uk-flex uk-flex-column uk-flex-middle">
+        <button
+          type="button"
+          class="uk-button uk-button-default"
+          disabled=verify_action.pending()
+          on:click=move |_| verify_action.dispatch(())
+        >
+          <span uk-icon="icon: fingerprint" class="uk-margin-small-right"></span>
+          "Use a passkey instead"
+        </button>
+        {move || {
+            verify_action
+                .value()
+                .get()
+                .and_then(Result::err)
+                .map(|e| view! { <p class="uk-text-danger">{e}</p> })
+        }}
+      </div>
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[path = ""]
+mod ssr {
+    pub use crate::compose_from_fn;
+    pub use crate::middlewares::require_login;
+}
+#[cfg(feature = "ssr")]
+pub use ssr::*;
+
+#[server(name = StartPasskeyLogin, prefix = "/auth", endpoint = "passkey_login_start", client = crate::components::csrf::CsrfClient)]
+async fn start_passkey_login() -> Result<PasskeyChallenge<RequestChallengeResponse>, ServerFnError>
+{
+    use crate::supabase::Supabase;
+
+    let supabase = expect_context::<Supabase>();
+    let (ceremony_id, options) = supabase
+        .start_passkey_login()
+        .map_err(crate::supabase::map_err)?;
+    Ok(PasskeyChallenge {
+        ceremony_id,
+        options,
+    })
+}
+
+/// A passkey holds both factors, the new session starts at `aal2`
+#[server(name = FinishPasskeyLogin, prefix = "/auth", endpoint = "passkey_login", input = server_fn::codec::Json, client = crate::components::csrf::CsrfClient)]
+async fn finish_passkey_login(
+    ceremony_id: String,
+    credential: PublicKeyCredential,
+    callback: String,
+) -> Result<(), ServerFnError> {
+    use crate::supabase::{AuthSession, Supabase, SupabaseError};
+    use axum::Extension;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let res_options = expect_context::<leptos_axum::ResponseOptions>();
+
+    let user_id = match supabase
+        .finish_passkey_login(&ceremony_id, &credential)
+        .await
+    {
+        Ok(user_id) => user_id,
+        Err(e) => {
+            let (code, err) = SupabaseError(e).into();
+            res_options.set_status(code);
+            return Err(err);
+        }
+    };
+
+    let access_token = supabase
+        .sign_in_as(&user_id)
+        .await
+        .map_err(crate::supabase::map_err)?;
+    let mut user = supabase
+        .new_session(access_token)
+        .await
+        .map_err(crate::supabase::map_err)?;
+    user.identity.verify_second_factor();
+
+    let callback = if callback.is_empty() { "/" } else { &callback };
+    crate::components::auth::login(user, auth_session, callback).await
+}
+
+#[server(name = StartPasskeyAssertion, prefix = "/auth", endpoint = "passkey_verify_start", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_login))]
+async fn start_passkey_assertion(
+) -> Result<PasskeyChallenge<RequestChallengeResponse>, ServerFnError> {
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let identity = auth_session.user.unwrap().identity;
+
+    let (ceremony_id, options) = supabase
+        .start_passkey_authentication(&identity.user_id)
+        .await
+        .map_err(crate::supabase::map_err)?;
+    Ok(PasskeyChallenge {
+        ceremony_id,
+        options,
+    })
+}
+
+/// Raises the session to `aal2` in the app, the Supabase token keeps the level it had
+#[server(name = FinishPasskeyAssertion, prefix = "/auth", endpoint = "passkey_verify", input = server_fn::codec::Json, client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_login))]
+async fn finish_passkey_assertion(
+    ceremony_id: String,
+    credential: PublicKeyCredential,
+    callback: String,
+) -> Result<(), ServerFnError> {
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+    use axum_extra::extract::CookieJar;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let mut identity = auth_session.user.unwrap().identity;
+
+    if let Err(e) = supabase
+        .finish_passkey_authentication(&identity.user_id, &ceremony_id, &credential)
+        .await
+    {
+        expect_context::<leptos_axum::ResponseOptions>().set_status(http::StatusCode::UNAUTHORIZED);
+        return Err(crate::supabase::map_err(e));
+    }
+
+    let headers = leptos_axum::extract::<http::HeaderMap>().await?;
+    let cookies = CookieJar::from_headers(&headers);
+    let session_id = cookies.get("auth").unwrap().value();
+    identity.verify_second_factor();
+    supabase.update_identity(session_id, identity).await;
+
+    crate::components::auth::redirect::redirect(&callback);
+    Ok(())
+}
@@ -0,0 +1,63 @@
+//! Proof Key for Code Exchange shared by every flow that comes back through a `code`:
+//! OAuth, password recovery and magic links
+
+use axum_extra::extract::cookie::{Cookie, SameSite};
+use axum_extra::extract::CookieJar;
+use base64::prelude::{Engine as _, BASE64_URL_SAFE_NO_PAD};
+use leptos::{expect_context, ServerFnError};
+use leptos_axum::ResponseOptions;
+use sha2::{Digest, Sha256};
+
+const COOKIE_NAME: &str = "pkce_flow";
+
+fn generate_code_verifier() -> String {
+    use rand::{Rng, RngCore};
+
+    const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ\
+                        abcdefghijklmnopqrstuvwxyz\
+                        0123456789-._~";
+    let mut buf = vec![0u8; rand::thread_rng().gen_range(43..=128)];
+    rand::thread_rng().fill_bytes(&mut buf);
+    let random_string: String = buf
+        .iter()
+        .map(|&b| CHARSET[b as usize % CHARSET.len()] as char)
+        .collect();
+    random_string
+}
+
+/// Sets the cookie to verify the `auth_code` during the callback and returns the S256 challenge
+pub fn start_flow() -> String {
+    let code_verifier = generate_code_verifier();
+
+    let cookie = Cookie::build((COOKIE_NAME, code_verifier.clone()))
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax);
+
+    expect_context::<ResponseOptions>().insert_header(
+        http::header::SET_COOKIE,
+        cookie.build().encoded().to_string().parse().unwrap(),
+    );
+
+    BASE64_URL_SAFE_NO_PAD.encode(Sha256::new().chain_update(code_verifier).finalize())
+}
+
+/// The verifier of the flow started by this browser, the cookie is cleared as it's single use
+pub async fn take_verifier() -> Result<Option<String>, ServerFnError> {
+    let headers = leptos_axum::extract::<http::HeaderMap>().await?;
+    let cookies = CookieJar::from_headers(&headers);
+    let Some(code_verifier) = cookies.get(COOKIE_NAME).map(|c| c.value().to_string()) else {
+        return Ok(None);
+    };
+
+    let cookie = Cookie::build(COOKIE_NAME)
+        .path("/")
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::seconds(-1));
+
+    expect_context::<ResponseOptions>().insert_header(
+        http::header::SET_COOKIE,
+        cookie.build().encoded().to_string().parse().unwrap(),
+    );
+    Ok(Some(code_verifier))
+}
@@ -21,11 +21,11 @@ pub fn AuthProvider(#[prop(optional)] unprotected: bool, children: Children) ->
 
         if let Some(OriginalUri(uri)) = use_context::<OriginalUri>() {
             if let Some(u) = user {
-                if u.identity.has_mfa
-                    && u.identity.aal == "aal1"
+                if u.identity.needs_second_factor()
+                    && u.identity.trusted_device.is_none()
                     && uri.path() != "/user/authenticate"
                 {
-                    leptos_axum::redirect(&format!("/user/authenticate?cb={}", uri));
+                    leptos_axum::redirect(&super::StepUp::new(&uri.to_string()).location);
                 }
             }
 
@@ -34,7 +34,7 @@ pub fn AuthProvider(#[prop(optional)] unprotected: bool, children: Children) ->
                     leptos_axum::redirect("/");
                 }
             } else if !authenticated() && !unprotected {
-                leptos_axum::redirect("/signin");
+                leptos_axum::redirect(&super::SignInRequired::new(&uri.to_string()).location);
             }
         }
     }
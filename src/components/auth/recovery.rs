@@ -0,0 +1,294 @@
+//! Password recovery and magic link sign in, both through a link emailed by Supabase
+//! that comes back to `callback_email` with a PKCE `code`
+
+use leptos::*;
+use leptos_router::ActionForm;
+
+use crate::components::auth::follow_step_up;
+use crate::components::csrf::CsrfField;
+
+#[island]
+pub fn EmailLinkForm() -> impl IntoView {
+    let email = RwSignal::new(String::default());
+    let password_reset_action = create_server_action::<SendPasswordReset>();
+    let magic_link_action = create_server_action::<SendMagicLink>();
+
+    let result = move || {
+        password_reset_action
+            .value()
+            .get()
+            .or_else(|| magic_link_action.value().get())
+    };
+
+    view! {
+      <hr class="uk-divider-small"/>
+      <div class="uk-flex uk-flex-column uk-flex-middle">
+        <p class="uk-text-meta">"Forgot your password, or rather not type it?"</p>
+        <div class="uk-inline uk-box-shadow-small">
+          <span uk-icon="mail" class="uk-form-icon uk-text-middle"></span>
+          <input
+            class="uk-input uk-form-blank"
+            type="email"
+            placeholder="user@email.com"
+            aria-label="Email for the link"
+            prop:value=email
+            on:input=move |ev| email.set(event_target_value(&ev))
+          />
+        </div>
+        <div class="uk-margin-small-top">
+          <button
+            type="button"
+            class="uk-button uk-button-text uk-margin-right"
+            on:click=move |_| magic_link_action.dispatch(SendMagicLink { email: email() })
+          >
+            "Email me a sign-in link"
+          </button>
+          <button
+            type="button"
+            class="uk-button uk-button-text"
+            on:click=move |_| password_reset_action.dispatch(SendPasswordReset { email: email() })
+          >
+            "Reset my password"
+          </button>
+        </div>
+        {move || match result() {
+            Some(Ok(())) => {
+                view! {
+                  <p class="uk-text-success">
+                    "If the address has an account, a link is on its way. "
+                    "Open it in this browser."
+                  </p>
+                }
+                    .into_view()
+            }
+            Some(Err(e)) => view! { <p class="uk-text-danger">{e.to_string()}</p> }.into_view(),
+            None => ().into_view(),
+        }}
+      </div>
+    }
+}
+
+#[island]
+pub fn SetNewPasswordForm() -> impl IntoView {
+    let set_password_action = create_server_action::<SetNewPassword>();
+    follow_step_up(set_password_action);
+
+    view! {
+      <ActionForm action=set_password_action>
+        <CsrfField/>
+        <div class="uk-flex uk-flex-column uk-flex-middle">
+          <input
+            class="uk-input uk-form-width-large uk-margin-small"
+            name="password"
+            type="password"
+            placeholder="New password"
+            aria-label="New password"
+            minlength="8"
+            required
+          />
+          <input
+            class="uk-input uk-form-width-large uk-margin-small"
+            name="confirm"
+            type="password"
+            placeholder="Repeat the new password"
+            aria-label="Repeat the new password"
+            minlength="8"
+            required
+          />
+          <button type="submit" class="uk-button uk-button-primary uk-margin-small">
+            "Set password"
+          </button>
+          {move || {
+              set_password_action
+                  .value()
+                  .get()
+                  .and_then(|result| result.err())
+                  .map(|e| view! { <p class="uk-text-danger">{e.to_string()}</p> })
+          }}
+        </div>
+      </ActionForm>
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[path = ""]
+mod ssr {
+    pub use crate::compose_from_fn;
+    pub use crate::middlewares::{rate_limit, require_aal2, EMAIL_LINK_LIMIT};
+
+    use leptos::{expect_context, ServerFnError};
+
+    use crate::components::auth::pkce;
+    use crate::supabase::Supabase;
+
+    pub const RECOVERY_FLOW: &str = "recovery";
+    pub const MAGIC_LINK_FLOW: &str = "magiclink";
+
+    /// Marks a session signed in through a recovery link, allowed to set a password
+    /// without knowing the current one
+    pub const RECOVERY_SESSION_KEY: &str = "password_recovery";
+
+    pub async fn send_email_link(email: String, flow: &str) -> Result<(), ServerFnError> {
+        let res_options = expect_context::<leptos_axum::ResponseOptions>();
+
+        let email = email.trim().to_lowercase();
+        if !email.contains('@') {
+            res_options.set_status(http::StatusCode::BAD_REQUEST);
+            return Err(ServerFnError::Args("email".to_string()));
+        }
+
+        // The attempt stays counted whether the address is known or not, `EMAIL_LINK_LIMIT`
+        // caps the sends
+
+        let supabase = expect_context::<Supabase>();
+        let code_challenge = pkce::start_flow();
+        let redirect_to = format!("{}/auth/callback_email?flow={flow}", supabase.site_url());
+
+        let sent = if flow == RECOVERY_FLOW {
+            supabase
+                .send_password_recovery(&email, &redirect_to, &code_challenge)
+                .await
+        } else {
+            supabase
+                .send_magic_link(&email, &redirect_to, &code_challenge)
+                .await
+        };
+
+        match sent {
+            Err(e) if e.http_status == 429 || e.http_status > 499 => {
+                Err(crate::supabase::map_err(e))
+            }
+            // Unknown addresses look the same as known ones
+            Err(e) => {
+                tracing::warn!("\n{flow} email - {e:?}");
+                Ok(())
+            }
+            Ok(()) => Ok(()),
+        }
+    }
+}
+#[cfg(feature = "ssr")]
+pub use ssr::*;
+
+#[server(name = SendPasswordReset, prefix = "/auth", endpoint = "recover", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(rate_limit(&EMAIL_LINK_LIMIT)))]
+async fn send_password_reset(email: String) -> Result<(), ServerFnError> {
+    send_email_link(email, RECOVERY_FLOW).await
+}
+
+#[server(name = SendMagicLink, prefix = "/auth", endpoint = "magic_link", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(rate_limit(&EMAIL_LINK_LIMIT)))]
+async fn send_magic_link(email: String) -> Result<(), ServerFnError> {
+    send_email_link(email, MAGIC_LINK_FLOW).await
+}
+
+#[server(prefix = "/auth", endpoint = "callback_email", client = crate::components::csrf::CsrfClient)]
+async fn callback_email() -> Result<(), ServerFnError> {
+    use crate::components::auth::pkce;
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::extract::Query;
+    use axum::Extension;
+
+    #[derive(serde::Deserialize, Debug)]
+    struct EmailCode {
+        // Missing when the link expired, Supabase sends `error_description` instead
+        code: Option<String>,
+        flow: String,
+    }
+
+    let Query(EmailCode { code, flow }) = leptos_axum::extract::<Query<EmailCode>>().await?;
+
+    // The verifier is only known to the browser that asked for the link
+    let (Some(code), Some(pkce_code_verifier)) = (code, pkce::take_verifier().await?) else {
+        leptos_axum::redirect("/signin");
+        return Ok(());
+    };
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+
+    let access_token = supabase
+        .client
+        .exchange_code_for_session(&code, &pkce_code_verifier)
+        .await
+        .map_err(crate::supabase::map_err)?;
+
+    let user = supabase
+        .new_session(access_token)
+        .await
+        .map_err(crate::supabase::map_err)?;
+
+    if flow != RECOVERY_FLOW {
+        return crate::components::auth::login(user, auth_session, "/").await;
+    }
+
+    if let Err(e) = auth_session
+        .session
+        .insert(RECOVERY_SESSION_KEY, true)
+        .await
+    {
+        tracing::error!("\ncallback_email - {e:?}");
+        expect_context::<leptos_axum::ResponseOptions>()
+            .set_status(http::StatusCode::INTERNAL_SERVER_ERROR);
+        return Err(ServerFnError::ServerError(
+            "Recovery session error".to_string(),
+        ));
+    }
+    crate::components::auth::login(user, auth_session, "/user/password").await
+}
+
+#[server(name = SetNewPassword, prefix = "/auth", endpoint = "set_password", client = crate::components::csrf::CsrfClient)]
+// A recovery link only proves the email, users with a second factor verify it too
+#[middleware(compose_from_fn!(require_aal2))]
+async fn set_new_password(password: String, confirm: String) -> Result<(), ServerFnError> {
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let res_options = expect_context::<leptos_axum::ResponseOptions>();
+    let identity = auth_session.user.unwrap().identity;
+
+    if password.chars().count() < 8 || password != confirm {
+        res_options.set_status(http::StatusCode::BAD_REQUEST);
+        return Err(ServerFnError::Args("password".to_string()));
+    }
+
+    let recovering = auth_session
+        .session
+        .get::<bool>(RECOVERY_SESSION_KEY)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+    if !recovering {
+        res_options.set_status(http::StatusCode::FORBIDDEN);
+        return Err(ServerFnError::new(
+            "This session wasn't opened from a recovery link, request a new one",
+        ));
+    }
+
+    supabase
+        .update_user(
+            &identity.auth_token,
+            serde_json::json!({ "password": password }),
+            None,
+        )
+        .await
+        .map_err(crate::supabase::map_err)?;
+
+    let _ = auth_session
+        .session
+        .remove::<bool>(RECOVERY_SESSION_KEY)
+        .await;
+
+    // Whoever knew the old password is signed out
+    let current_id = auth_session.session.id().map(|id| id.to_string());
+    supabase
+        .revoke_user_sessions(&identity.user_id, None, current_id.as_deref())
+        .await
+        .map_err(crate::supabase::map_err)?;
+
+    leptos_axum::redirect("/");
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use leptos::*;
+
+const RECOVERY_CODES_FILE_NAME: &str = "todo-supabase-recovery-codes.txt";
+
+/// `data:` URL of the codes as a text file
+fn recovery_codes_url(codes: &[String]) -> String {
+    let text = format!(
+        "Todo Supabase Leptos recovery codes\n\
+        Each code can be used once in place of your authenticator.\n\n{}\n",
+        codes.join("\n")
+    );
+    let encoded: String = text
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect();
+    format!("data:text/plain;charset=utf-8,{encoded}")
+}
+
+/// Newly generated codes, they can't be shown again once the page is left
+#[component]
+pub fn RecoveryCodeList(codes: Vec<String>) -> impl IntoView {
+    let url = recovery_codes_url(&codes);
+
+    view! {
+      <div class="uk-alert-warning uk-width-1-2" uk-alert>
+        <p>
+          "Keep these recovery codes somewhere safe. Each one can be used once to sign in "
+          "if you lose access to your authenticator, they won't be shown again."
+        </p>
+        <ul class="uk-list uk-child-width-1-2 uk-grid-small" uk-grid>
+          {codes
+              .into_iter()
+              .map(|code| {
+                  view! {
+                    <li>
+                      <code>{code}</code>
+                    </li>
+                  }
+              })
+              .collect_view()}
+        </ul>
+        <p class="uk-text-right">
+          <a
+            href=url
+            download=RECOVERY_CODES_FILE_NAME
+            class="uk-button uk-button-small uk-button-default uk-margin-small-right"
+          >
+            "Download"
+          </a>
+          <button
+            type="button"
+            class="uk-button uk-button-small uk-button-default uk-margin-small-right"
+            on:click=move |_| {
+                let _ = window().print();
+            }
+          >
+            "Print"
+          </button>
+          <a href="/user" class="uk-button uk-button-small uk-button-primary">
+            "Done"
+          </a>
+        </p>
+      </div>
+    }
+}
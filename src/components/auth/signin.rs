@@ -1,59 +1,88 @@
-use super::AuthFormFields;
+use super::{AuthFormFields, OAuthProvider};
 
 use leptos::*;
 use leptos_router::ActionForm;
 
+use crate::components::csrf::CsrfField;
+
 #[island]
-pub fn Signin() -> impl IntoView {
+pub fn Signin(providers: Vec<OAuthProvider>, callback: String) -> impl IntoView {
     let login_action = create_server_action::<Login>();
     let oauth_connect_action = create_server_action::<ConnectOauth>();
 
     view! {
-      <button
-        class="uk-button uk-button-default uk-dark uk-box-shadow-small \
-        uk-margin-top uk-inline uk-text-capitalize"
-        on:click=move |_| {
-            oauth_connect_action
-                .dispatch(ConnectOauth {
-                    provider: "discord".into(),
-                    scopes: vec!["email".into(), "identify".into()],
-                })
-        }
-      >
-
-        <span uk-icon="discord" class="uk-form-icon"></span>
-        <div class="uk-margin-left">"Discord Connect"</div>
-      </button>
+      {providers
+          .into_iter()
+          .map(|provider| {
+              let id = provider.id.clone();
+              view! {
+                <button
+                  class="uk-button uk-button-default uk-dark uk-box-shadow-small \
+                  uk-margin-top uk-inline uk-text-capitalize uk-margin-small-right"
+                  on:click=move |_| {
+                      oauth_connect_action.dispatch(ConnectOauth { provider: id.clone() })
+                  }
+                >
+
+                  <span uk-icon=provider.icon class="uk-form-icon"></span>
+                  <div class="uk-margin-left">{provider.label} " Connect"</div>
+                </button>
+              }
+          })
+          .collect_view()}
 
       <hr class="uk-divider-small"/>
       <ActionForm action=login_action>
+        <CsrfField/>
+        <input type="hidden" name="callback" value=callback/>
         <AuthFormFields legend="" button=" Login "/>
       </ActionForm>
     }
 }
 
-#[server(prefix = "/auth", endpoint = "signin")]
-async fn login(email: String, password: String) -> Result<(), ServerFnError> {
+#[cfg(feature = "ssr")]
+#[path = ""]
+mod ssr {
+    pub use crate::compose_from_fn;
+    pub use crate::middlewares::{rate_limit, SIGN_IN_LIMIT};
+}
+#[cfg(feature = "ssr")]
+pub use ssr::*;
+
+#[server(prefix = "/auth", endpoint = "signin", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(rate_limit(&SIGN_IN_LIMIT)))]
+async fn login(email: String, password: String, callback: String) -> Result<(), ServerFnError> {
+    use crate::middlewares::RateLimitGuard;
     use crate::supabase::{AuthSession, SupabaseError};
     use axum::Extension;
 
     let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let Extension(rate_limit) = leptos_axum::extract::<Extension<RateLimitGuard>>().await?;
     let res_options = expect_context::<leptos_axum::ResponseOptions>();
 
     match auth_session.authenticate((email, password)).await {
-        Ok(Some(user)) => crate::components::auth::login(user, auth_session).await,
+        Ok(Some(user)) => {
+            rate_limit.record_success().await;
+            let callback = if callback.is_empty() { "/" } else { &callback };
+            crate::components::auth::login(user, auth_session, callback).await
+        }
         Ok(None) => {
             res_options.set_status(http::StatusCode::UNAUTHORIZED);
             Err(ServerFnError::new("".to_string()))
         }
         Err(e) => match e {
             axum_login::Error::Session(_) => {
+                rate_limit.release().await;
                 res_options.set_status(http::StatusCode::INTERNAL_SERVER_ERROR);
                 Err(ServerFnError::ServerError(
                     "/signin session error - 02".to_string(),
                 ))
             }
             axum_login::Error::Backend(e) => {
+                // Supabase being down isn't a failed attempt
+                if e.http_status > 499 {
+                    rate_limit.release().await;
+                }
                 let (code, err) = SupabaseError(e).into();
                 res_options.set_status(code);
                 Err(err)
@@ -62,59 +91,30 @@ async fn login(email: String, password: String) -> Result<(), ServerFnError> {
     }
 }
 
-#[cfg(feature = "ssr")]
-fn generate_code_verifier() -> String {
-    use rand::{Rng, RngCore};
-
-    const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ\
-                        abcdefghijklmnopqrstuvwxyz\
-                        0123456789-._~";
-    let mut buf = vec![0u8; rand::thread_rng().gen_range(43..=128)];
-    rand::thread_rng().fill_bytes(&mut buf);
-    let random_string: String = buf
-        .iter()
-        .map(|&b| CHARSET[b as usize % CHARSET.len()] as char)
-        .collect();
-    random_string
-}
-
-#[server(prefix = "/auth", endpoint = "connect_oauth")]
-async fn connect_oauth(provider: String, scopes: Vec<String>) -> Result<(), ServerFnError> {
+#[server(prefix = "/auth", endpoint = "connect_oauth", client = crate::components::csrf::CsrfClient)]
+async fn connect_oauth(provider: String) -> Result<(), ServerFnError> {
+    use crate::components::auth::pkce;
     use crate::supabase::Supabase;
-    use axum_extra::extract::cookie::{Cookie, SameSite};
-    use base64::prelude::{Engine as _, BASE64_URL_SAFE_NO_PAD};
-    use leptos_axum::ResponseOptions;
-    use sha2::{Digest, Sha256};
     use supabase_rust::auth::{OAuthOptions, PKCECodeChallenge};
 
-    let code_verifier = generate_code_verifier();
-
-    // Set the cookie to verify the `auth_code` during the callback
-    let cookie = Cookie::build(("pkce_flow", code_verifier.clone()))
-        .path("/")
-        .http_only(true)
-        .same_site(SameSite::Lax);
-
-    expect_context::<ResponseOptions>().insert_header(
-        http::header::SET_COOKIE,
-        cookie.build().encoded().to_string().parse().unwrap(),
-    );
+    let supabase = expect_context::<Supabase>();
+    let Some(provider) = supabase.oauth_provider(&provider) else {
+        expect_context::<leptos_axum::ResponseOptions>().set_status(http::StatusCode::BAD_REQUEST);
+        return Err(ServerFnError::Args("provider".to_string()));
+    };
 
-    let hashed_code_verifier =
-        BASE64_URL_SAFE_NO_PAD.encode(Sha256::new().chain_update(code_verifier).finalize());
-    let code_challenge = PKCECodeChallenge::S256(hashed_code_verifier);
+    let code_challenge = PKCECodeChallenge::S256(pkce::start_flow());
 
     let oauth_options = OAuthOptions {
-        scopes,
-        // Could be an env var : SITE_URL/auth/callback
-        redirect_to: Some("http://localhost:3000/auth/callback".to_string()),
+        scopes: provider.scopes.clone(),
+        redirect_to: Some(format!("{}/auth/callback", supabase.site_url())),
         pkce: Some(code_challenge),
         ..Default::default()
     };
 
-    let redirect_url = expect_context::<Supabase>()
+    let redirect_url = supabase
         .client
-        .sign_in_oauth(&provider, oauth_options)
+        .sign_in_oauth(&provider.id, oauth_options)
         .await;
 
     leptos_axum::redirect(&redirect_url);
@@ -122,49 +122,30 @@ async fn connect_oauth(provider: String, scopes: Vec<String>) -> Result<(), Serv
     Ok(())
 }
 
-#[server(prefix = "/auth", endpoint = "callback")]
+#[server(prefix = "/auth", endpoint = "callback", client = crate::components::csrf::CsrfClient)]
 async fn callback_oauth() -> Result<(), ServerFnError> {
+    use crate::components::auth::pkce;
     use crate::supabase::{AuthSession, Supabase};
     use axum::extract::Query;
     use axum::Extension;
-    use axum_extra::extract::cookie::{Cookie, SameSite};
-    use axum_extra::extract::CookieJar;
-    use leptos_axum::ResponseOptions;
-    use time::Duration;
 
     #[derive(serde::Deserialize, Debug)]
     struct OAuthCode {
         code: String,
     }
 
-    let headers = leptos_axum::extract::<http::HeaderMap>().await?;
-    let cookies = CookieJar::from_headers(&headers);
-    let pkce_code_verifier = if let Some(cookie) = cookies.get("pkce_flow") {
-        cookie.value()
-    } else {
+    let Some(pkce_code_verifier) = pkce::take_verifier().await? else {
         leptos_axum::redirect("/signin");
         return Ok(());
     };
 
-    let res_options = expect_context::<ResponseOptions>();
-
-    let cookie = Cookie::build("pkce_flow")
-        .path("/")
-        .same_site(SameSite::Lax)
-        .max_age(Duration::seconds(-1));
-
-    res_options.insert_header(
-        http::header::SET_COOKIE,
-        cookie.build().encoded().to_string().parse().unwrap(),
-    );
-
     let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
     let supabase = expect_context::<Supabase>();
     let Query(OAuthCode { code }) = leptos_axum::extract::<Query<OAuthCode>>().await?;
 
     let access_token = supabase
         .client
-        .exchange_code_for_session(&code, pkce_code_verifier)
+        .exchange_code_for_session(&code, &pkce_code_verifier)
         .await
         .map_err(crate::supabase::map_err)?;
 
@@ -173,5 +154,5 @@ async fn callback_oauth() -> Result<(), ServerFnError> {
         .await
         .map_err(crate::supabase::map_err)?;
 
-    crate::components::auth::login(user, auth_session).await
+    crate::components::auth::login(user, auth_session, "/").await
 }
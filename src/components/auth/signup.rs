@@ -3,18 +3,21 @@ use super::AuthFormFields;
 use leptos::*;
 use leptos_router::ActionForm;
 
+use crate::components::csrf::CsrfField;
+
 #[island]
 pub fn Signup() -> impl IntoView {
     let signup_action = create_server_action::<SignupSFn>();
 
     view! {
       <ActionForm action=signup_action>
+        <CsrfField/>
         <AuthFormFields legend="New User" button="Sign up"/>
       </ActionForm>
     }
 }
 
-#[server(SignupSFn, prefix = "/auth", endpoint = "signup")]
+#[server(SignupSFn, prefix = "/auth", endpoint = "signup", client = crate::components::csrf::CsrfClient)]
 async fn signup(email: String, password: String) -> Result<(), ServerFnError> {
     use crate::supabase::{AuthSession, Supabase};
     use axum::Extension;
@@ -34,6 +37,7 @@ async fn signup(email: String, password: String) -> Result<(), ServerFnError> {
         .await
         .map_err(crate::supabase::map_err)?;
 
+    crate::components::auth::record_device(&auth_session).await?;
     if auth_session.login(&user).await.is_err() {
         res_options.set_status(http::StatusCode::INTERNAL_SERVER_ERROR);
         return Err(ServerFnError::ServerError(
@@ -0,0 +1,179 @@
+//! Step-up for sensitive actions. Server fns behind `require_aal2` or `require_fresh_auth`
+//! answer with a step-up error when the session has to verify again. Islands keep what they
+//! were sending, go verify and send it again once back on the page.
+//! Server fns behind `require_login` answer `401` with a sign-in error the same way.
+
+use leptos::*;
+
+/// Starts the message of step-up errors, followed by where to verify
+const STEP_UP_REQUIRED: &str = "step_up_required:";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StepUp {
+    /// The verification page, sending the user back to `callback` afterwards
+    pub location: String,
+}
+
+impl StepUp {
+    #[cfg(feature = "ssr")]
+    pub fn new(callback: &str) -> Self {
+        let callback: String = form_urlencoded::byte_serialize(callback.as_bytes()).collect();
+        Self {
+            location: format!("/user/authenticate?cb={callback}"),
+        }
+    }
+
+    /// Finds the step-up in a server fn error, or in anything displaying one
+    pub fn from_error(e: &impl std::fmt::Display) -> Option<Self> {
+        let message = e.to_string();
+        let (_, location) = message.split_once(STEP_UP_REQUIRED)?;
+        Some(Self {
+            location: location.to_string(),
+        })
+    }
+}
+
+impl From<StepUp> for ServerFnError {
+    fn from(step_up: StepUp) -> Self {
+        ServerFnError::ServerError(format!("{STEP_UP_REQUIRED}{}", step_up.location))
+    }
+}
+
+/// Starts the message of sign-in errors, followed by where to sign in
+const SIGN_IN_REQUIRED: &str = "sign_in_required:";
+
+/// The session is gone, the user has to sign in again to carry on
+#[derive(Clone, Debug, PartialEq)]
+pub struct SignInRequired {
+    /// The sign in page, sending the user back to `callback` afterwards
+    pub location: String,
+}
+
+impl SignInRequired {
+    #[cfg(feature = "ssr")]
+    pub fn new(callback: &str) -> Self {
+        let callback: String = form_urlencoded::byte_serialize(callback.as_bytes()).collect();
+        Self {
+            location: format!("/signin?cb={callback}"),
+        }
+    }
+
+    /// Finds the sign-in error in a server fn error, or in anything displaying one
+    pub fn from_error(e: &impl std::fmt::Display) -> Option<Self> {
+        let message = e.to_string();
+        let (_, location) = message.split_once(SIGN_IN_REQUIRED)?;
+        Some(Self {
+            location: location.to_string(),
+        })
+    }
+}
+
+impl From<SignInRequired> for ServerFnError {
+    fn from(sign_in: SignInRequired) -> Self {
+        ServerFnError::ServerError(format!("{SIGN_IN_REQUIRED}{}", sign_in.location))
+    }
+}
+
+/// Sends the user to verify when `action` runs into a step-up, then dispatches it again with
+/// the same input once they're back. Works for `ActionForm` submissions as well.
+/// A sign-in error sends the user to sign in, without the replay.
+pub fn use_step_up<I, O, E>(action: Action<I, Result<O, E>>)
+where
+    I: Clone + serde::Serialize + serde::de::DeserializeOwned + 'static,
+    O: Clone + 'static,
+    E: Clone + std::fmt::Display + 'static,
+{
+    #[cfg(not(feature = "ssr"))]
+    browser::use_step_up(action, true);
+    #[cfg(feature = "ssr")]
+    let _ = action;
+}
+
+/// Like `use_step_up` without the replay, for inputs that mustn't be kept in the browser
+/// such as passwords and for destructive actions. The user submits again once back.
+pub fn follow_step_up<I, O, E>(action: Action<I, Result<O, E>>)
+where
+    I: Clone + serde::Serialize + serde::de::DeserializeOwned + 'static,
+    O: Clone + 'static,
+    E: Clone + std::fmt::Display + 'static,
+{
+    #[cfg(not(feature = "ssr"))]
+    browser::use_step_up(action, false);
+    #[cfg(feature = "ssr")]
+    let _ = action;
+}
+
+#[cfg(not(feature = "ssr"))]
+mod browser {
+    use leptos::*;
+
+    use super::{SignInRequired, StepUp};
+
+    /// How long a stashed action waits for the user to come back from verifying, in ms
+    const REPLAY_TIMEOUT: f64 = 5.0 * 60.0 * 1000.0;
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct PendingAction<I> {
+        stashed_at: f64,
+        input: I,
+    }
+
+    fn storage() -> Option<web_sys::Storage> {
+        window().session_storage().ok().flatten()
+    }
+
+    pub fn use_step_up<I, O, E>(action: Action<I, Result<O, E>>, replay: bool)
+    where
+        I: Clone + serde::Serialize + serde::de::DeserializeOwned + 'static,
+        O: Clone + 'static,
+        E: Clone + std::fmt::Display + 'static,
+    {
+        let key = format!("step_up:{}", std::any::type_name::<(I, O, E)>());
+        let last_input = StoredValue::new(None::<I>);
+
+        // The action clears its input once done, keep it around for the replay
+        create_effect(move |_| {
+            if let Some(input) = action.input().get() {
+                last_input.set_value(Some(input));
+            }
+        });
+
+        let stash_key = key.clone();
+        create_effect(move |_| {
+            let Some(Err(e)) = action.value().get() else {
+                return;
+            };
+            if let Some(sign_in) = SignInRequired::from_error(&e) {
+                let _ = window().location().set_href(&sign_in.location);
+                return;
+            }
+            let Some(step_up) = StepUp::from_error(&e) else {
+                return;
+            };
+            let input = last_input.get_value().filter(|_| replay);
+            if let (Some(storage), Some(input)) = (storage(), input) {
+                let pending = PendingAction {
+                    stashed_at: js_sys::Date::now(),
+                    input,
+                };
+                if let Ok(pending) = serde_json::to_string(&pending) {
+                    let _ = storage.set_item(&stash_key, &pending);
+                }
+            }
+            let _ = window().location().set_href(&step_up.location);
+        });
+
+        // Back from verifying, a stale action is dropped rather than sent out of the blue
+        let Some(storage) = storage() else {
+            return;
+        };
+        if let Ok(Some(pending)) = storage.get_item(&key) {
+            let _ = storage.remove_item(&key);
+            if let Ok(pending) = serde_json::from_str::<PendingAction<I>>(&pending) {
+                if js_sys::Date::now() - pending.stashed_at < REPLAY_TIMEOUT {
+                    action.dispatch(pending.input);
+                }
+            }
+        }
+    }
+}
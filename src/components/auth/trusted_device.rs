@@ -0,0 +1,61 @@
+//! "Trust this browser" for the 2FA challenge, the cookie only names a device the server
+//! keeps and can revoke
+
+use axum_extra::extract::cookie::{Cookie, SameSite};
+use axum_extra::extract::CookieJar;
+use leptos::{expect_context, ServerFnError};
+use leptos_axum::ResponseOptions;
+
+use crate::supabase::{DeviceInfo, Supabase, TRUSTED_DEVICE_DAYS};
+
+const COOKIE_NAME: &str = "trusted_device";
+
+/// Trusts the browser making the request for `user_id`
+pub async fn remember(user_id: &str) -> Result<(), ServerFnError> {
+    use axum::extract::ConnectInfo;
+    use std::net::SocketAddr;
+
+    let supabase = expect_context::<Supabase>();
+    let headers = leptos_axum::extract::<http::HeaderMap>().await?;
+    let peer = leptos_axum::extract::<ConnectInfo<SocketAddr>>()
+        .await
+        .ok()
+        .map(|ConnectInfo(addr)| addr);
+
+    let value = supabase
+        .trust_device(user_id, DeviceInfo::from_request(&headers, peer))
+        .await
+        .map_err(crate::supabase::map_err)?;
+
+    let cookie = Cookie::build((COOKIE_NAME, value))
+        .path("/")
+        .http_only(true)
+        .secure(supabase.site_url().starts_with("https://"))
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::days(TRUSTED_DEVICE_DAYS));
+
+    expect_context::<ResponseOptions>().insert_header(
+        http::header::SET_COOKIE,
+        cookie.build().encoded().to_string().parse().unwrap(),
+    );
+    Ok(())
+}
+
+/// The id of the trusted device this browser is for `user_id`, if any
+pub async fn verify(user_id: &str) -> Option<u64> {
+    let headers = leptos_axum::extract::<http::HeaderMap>().await.ok()?;
+    let cookies = CookieJar::from_headers(&headers);
+    let value = cookies.get(COOKIE_NAME)?.value().to_string();
+
+    expect_context::<Supabase>()
+        .verify_trusted_device(user_id, &value)
+        .await
+}
+
+/// Id named by this browser's cookie, unverified so only fit for display
+pub async fn current_id() -> Option<u64> {
+    let headers = leptos_axum::extract::<http::HeaderMap>().await.ok()?;
+    let cookies = CookieJar::from_headers(&headers);
+    let value = cookies.get(COOKIE_NAME)?.value().to_string();
+    value.split('.').next()?.parse().ok()
+}
@@ -0,0 +1,64 @@
+//! Browser side of the CSRF protection: the token comes in a cookie, forms post it back in a
+//! hidden field and server fn calls from islands in a header. See `middlewares::csrf_protect`.
+
+use leptos::server_fn::client::browser::BrowserClient;
+use leptos::server_fn::client::Client;
+use leptos::server_fn::request::browser::BrowserRequest;
+use leptos::server_fn::response::browser::BrowserResponse;
+use leptos::*;
+
+/// Cookie holding the token, readable by scripts so islands can send it back
+pub const CSRF_COOKIE: &str = "csrf_token";
+/// Header server fn calls from islands carry the token in
+pub const CSRF_HEADER: &str = "x-csrf-token";
+/// Form field posts carry the token in
+pub const CSRF_FIELD: &str = "csrf_token";
+
+/// Token of the request being rendered, provided by the server
+#[derive(Clone, Debug)]
+pub struct CsrfToken(pub String);
+
+/// The token as the server set it, read from the context or the cookie
+pub fn csrf_token() -> Option<String> {
+    #[cfg(feature = "ssr")]
+    {
+        use_context::<CsrfToken>().map(|CsrfToken(token)| token)
+    }
+    #[cfg(not(feature = "ssr"))]
+    {
+        use wasm_bindgen::{JsCast, JsValue};
+        use web_sys::HtmlDocument;
+
+        let document = Into::<JsValue>::into(document()).unchecked_into::<HtmlDocument>();
+        let cookies = document.cookie().ok()?;
+        cookies
+            .split(';')
+            .filter_map(|cookie| cookie.trim().split_once('='))
+            .find(|(name, _)| *name == CSRF_COOKIE)
+            .map(|(_, token)| token.to_string())
+    }
+}
+
+/// Hidden field with the token, for forms that also post without JavaScript
+#[component]
+pub fn CsrfField() -> impl IntoView {
+    view! { <input type="hidden" name=CSRF_FIELD value=csrf_token().unwrap_or_default()/> }
+}
+
+/// Server fn client adding the token to every call, for the server fns islands dispatch
+pub struct CsrfClient;
+
+impl<CustErr> Client<CustErr> for CsrfClient {
+    type Request = BrowserRequest;
+    type Response = BrowserResponse;
+
+    fn send(
+        req: Self::Request,
+    ) -> impl std::future::Future<Output = Result<Self::Response, ServerFnError<CustErr>>> + Send
+    {
+        if let Some(token) = csrf_token() {
+            req.headers().append(CSRF_HEADER, &token);
+        }
+        BrowserClient::send(req)
+    }
+}
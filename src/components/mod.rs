@@ -1,4 +1,5 @@
 pub mod auth;
+pub mod csrf;
 pub mod todo;
 mod top_navbar;
 
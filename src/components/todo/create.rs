@@ -1,7 +1,37 @@
 use leptos::*;
 use leptos_router::{ActionForm, FromFormData};
+use time::format_description::FormatItem;
+use time::{OffsetDateTime, UtcOffset};
 
-use super::{Task, TaskSchema, Tasks};
+use super::quick_add::{self, QuickAdd};
+use super::{Task, TaskSchema, Tasks, TITLE_MAX_LEN};
+use crate::components::csrf::CsrfField;
+
+const DUE_FORMAT: &[FormatItem<'static>] = time::macros::format_description!(
+    "[weekday repr:short] [day padding:none] [month repr:short], [hour]:[minute]"
+);
+
+/// Minutes east of UTC of the browser, sent along so the server resolves `tomorrow` in the
+/// same timezone as the preview
+fn utc_offset_minutes() -> i32 {
+    #[cfg(not(feature = "ssr"))]
+    {
+        -js_sys::Date::new_0().get_timezone_offset() as i32
+    }
+    #[cfg(feature = "ssr")]
+    {
+        0
+    }
+}
+
+fn local_now(offset_minutes: i32) -> OffsetDateTime {
+    #[cfg(not(feature = "ssr"))]
+    let now = OffsetDateTime::from_unix_timestamp_nanos(js_sys::Date::now() as i128 * 1_000_000)
+        .unwrap_or(OffsetDateTime::UNIX_EPOCH);
+    #[cfg(feature = "ssr")]
+    let now = OffsetDateTime::now_utc();
+    now.to_offset(UtcOffset::from_whole_seconds(offset_minutes * 60).unwrap_or(UtcOffset::UTC))
+}
 
 #[island]
 pub fn NewTaskForm() -> impl IntoView {
@@ -9,6 +39,8 @@ pub fn NewTaskForm() -> impl IntoView {
     let new_task_action = create_server_action::<TodoCreate>();
     let title = RwSignal::new("".to_string());
     let description = RwSignal::new("".to_string());
+    let utc_offset = utc_offset_minutes();
+    let preview = move || quick_add::parse(&title(), local_now(utc_offset));
 
     let on_submit = move |ev: ev::SubmitEvent| {
         ev.prevent_default();
@@ -32,6 +64,10 @@ pub fn NewTaskForm() -> impl IntoView {
                     title: t.title.unwrap(),
                     description: t.description.unwrap(),
                     completed: t.completed.unwrap(),
+                    due_at: t.due_at,
+                    tags: t.tags.unwrap_or_default(),
+                    priority: t.priority,
+                    ..Default::default()
                 };
                 tasks
                     .signal
@@ -43,13 +79,59 @@ pub fn NewTaskForm() -> impl IntoView {
 
       </Transition>
       <ActionForm action=new_task_action on:submit=on_submit>
-        <NewTaskFormFields title=title description=description/>
+        <NewTaskFormFields title=title description=description utc_offset=utc_offset/>
       </ActionForm>
+      {move || {
+          let parsed = preview();
+          let found = parsed.due.is_some() || parsed.priority.is_some() || !parsed.tags.is_empty();
+          found.then(|| view! { <QuickAddPreview parsed=parsed/> })
+      }}
+    }
+}
+
+/// What `todo_create` will make of the quick-add syntax in the title
+#[component]
+fn QuickAddPreview(parsed: QuickAdd) -> impl IntoView {
+    view! {
+      <div class="uk-text-small uk-margin-small-top">
+        <span class="uk-text-emphasis">{parsed.title}</span>
+        {parsed
+            .due
+            .map(|due| {
+                view! {
+                  <span class="uk-label uk-margin-small-left">
+                    {due.format(DUE_FORMAT).unwrap_or_default()}
+                  </span>
+                }
+            })}
+        {parsed
+            .priority
+            .map(|priority| {
+                view! {
+                  <span class="uk-label uk-label-warning uk-margin-small-left">
+                    {priority.as_str()}
+                  </span>
+                }
+            })}
+        {parsed
+            .tags
+            .into_iter()
+            .map(|tag| {
+                view! {
+                  <span class="uk-label uk-label-success uk-margin-small-left">"#" {tag}</span>
+                }
+            })
+            .collect_view()}
+      </div>
     }
 }
 
 #[component]
-pub fn NewTaskFormFields(title: RwSignal<String>, description: RwSignal<String>) -> impl IntoView {
+pub fn NewTaskFormFields(
+    title: RwSignal<String>,
+    description: RwSignal<String>,
+    utc_offset: i32,
+) -> impl IntoView {
     view! {
       <div class="uk-grid-row-collapse uk-flex-middle" uk-grid>
         <div class="uk-width-1-4">
@@ -58,13 +140,15 @@ pub fn NewTaskFormFields(title: RwSignal<String>, description: RwSignal<String>)
             <input
               name="title"
               type="text"
-              placeholder="New Task Title"
+              placeholder="Pay rent tomorrow 9am #home !high"
               aria-label="New Task Title"
-              maxlength="60"
+              maxlength=TITLE_MAX_LEN
               required
               class="uk-input uk-form-blank"
               prop:value=title
+              on:input=move |ev| title.set(event_target_value(&ev))
             />
+            <input name="utc_offset" type="hidden" prop:value=utc_offset/>
           </div>
         </div>
         <div class="uk-width-large@s">
@@ -101,25 +185,48 @@ mod ssr {
 #[cfg(feature = "ssr")]
 pub use ssr::*;
 
-#[server(prefix = "/todo", endpoint = "create")]
+#[server(prefix = "/todo", endpoint = "create", client = crate::components::csrf::CsrfClient)]
 #[middleware(compose_from_fn!(require_login))]
-async fn todo_create(title: String, description: String) -> Result<TaskSchema, ServerFnError> {
+async fn todo_create(
+    title: String,
+    description: String,
+    utc_offset: i32,
+) -> Result<TaskSchema, ServerFnError> {
     use super::TaskSchema;
     use crate::supabase::{AuthSession, Supabase};
+    use crate::webhooks::TaskEvent;
     use axum::Extension;
+    use time::format_description::well_known::Iso8601;
 
     let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
     let supabase = expect_context::<Supabase>();
 
-    let (user_id, user_token) = {
-        let user = auth_session.user.unwrap();
-        (user.identity.user_id, user.identity.auth_token)
+    let identity = auth_session.user.unwrap().identity;
+
+    let offset = utc_offset
+        .checked_mul(60)
+        .and_then(|seconds| UtcOffset::from_whole_seconds(seconds).ok());
+    let Some(offset) = offset else {
+        expect_context::<leptos_axum::ResponseOptions>().set_status(http::StatusCode::BAD_REQUEST);
+        return Err(ServerFnError::Args("utc_offset".to_string()));
     };
+    // Parsed again rather than trusting the preview, `now` being the server's clock
+    let parsed = quick_add::parse(&title, OffsetDateTime::now_utc().to_offset(offset));
+    // Checked after parsing, the quick-add syntax doesn't count towards the stored title
+    if parsed.title.trim().is_empty() || parsed.title.chars().count() > TITLE_MAX_LEN {
+        expect_context::<leptos_axum::ResponseOptions>().set_status(http::StatusCode::BAD_REQUEST);
+        return Err(ServerFnError::Args(format!(
+            "title must be 1 to {TITLE_MAX_LEN} characters"
+        )));
+    }
 
     let task = serde_json::to_string(&TaskSchema {
-        title: Some(title),
+        title: Some(parsed.title),
         description: Some(description),
-        author_id: Some(user_id.clone()),
+        due_at: parsed.due.map(|due| due.format(&Iso8601::DEFAULT).unwrap()),
+        tags: Some(parsed.tags),
+        priority: parsed.priority,
+        author_id: Some(identity.user_id.clone()),
         ..Default::default()
     })
     .unwrap();
@@ -130,12 +237,21 @@ async fn todo_create(title: String, description: String) -> Result<TaskSchema, S
         .query()
         .from("tasks")
         .insert(task)
-        .auth(user_token)
+        .auth(&identity.auth_token)
         .execute()
         .await;
 
     let new_task = supabase_rust::parse_response::<TaskSchema>(query_response)
         .await
-        .map_err(crate::supabase::map_err)?;
-    Ok(new_task[0].clone())
+        .map_err(crate::supabase::map_err)?[0]
+        .clone();
+
+    tokio::spawn(crate::webhooks::emit(
+        supabase,
+        identity.user_id,
+        identity.auth_token,
+        TaskEvent::Created,
+        new_task.clone(),
+    ));
+    Ok(new_task)
 }
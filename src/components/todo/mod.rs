@@ -1,5 +1,6 @@
 mod create;
 mod provider;
+pub mod quick_add;
 mod table;
 mod tablerow;
 
@@ -10,8 +11,12 @@ pub use table::TasksTable;
 use std::collections::HashMap;
 
 use leptos::RwSignal;
+use quick_add::Priority;
 use tablerow::{TaskCheckbox, TaskDelete, TaskDescription, TaskEdit, TaskTitle};
 
+/// Longest title a task is stored with, in characters
+pub const TITLE_MAX_LEN: usize = 60;
+
 #[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub struct Tasks {
     signal: RwSignal<HashMap<u32, RwSignal<Task>>>,
@@ -22,9 +27,17 @@ pub struct Task {
     pub title: String,
     pub completed: bool,
     pub description: String,
+    /// UTC ISO 8601 timestamp
+    pub remind_at: Option<String>,
+    /// UTC ISO 8601 timestamp
+    pub due_at: Option<String>,
+    pub tags: Vec<String>,
+    pub priority: Option<Priority>,
 }
 
+/// Wire format of a task, shared by the server fns and the REST API
 #[derive(serde::Deserialize, serde::Serialize, Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
 pub struct TaskSchema {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<u32>,
@@ -35,4 +48,20 @@ pub struct TaskSchema {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub completed: Option<bool>,
     pub description: Option<String>,
+    /// ISO 8601 timestamp
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    /// Embedded from the `reminders` table, never written back with the task
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reminder: Option<TaskReminder>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+pub struct TaskReminder {
+    pub remind_at: String,
 }
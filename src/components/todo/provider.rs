@@ -3,6 +3,7 @@ use std::collections::HashMap;
 use leptos::*;
 
 use super::{Task, TaskSchema, Tasks};
+use crate::components::auth::SignInRequired;
 
 #[island]
 pub fn TasksProvider(children: Children) -> impl IntoView {
@@ -15,6 +16,15 @@ pub fn TasksProvider(children: Children) -> impl IntoView {
     });
     provide_context(tasks_resource);
 
+    // The session ran out since the page was served
+    create_effect(move |_| {
+        if let Some(Err(e)) = tasks_resource() {
+            if let Some(sign_in) = SignInRequired::from_error(&e) {
+                let _ = window().location().set_href(&sign_in.location);
+            }
+        }
+    });
+
     let fetched = move || {
         if let Some(Ok(t)) = tasks_resource() {
             let tasks = t
@@ -24,6 +34,10 @@ pub fn TasksProvider(children: Children) -> impl IntoView {
                         title: task.title.unwrap_or_default(),
                         description: task.description.unwrap_or_default(),
                         completed: task.completed.unwrap_or_default(),
+                        remind_at: task.reminder.map(|r| r.remind_at),
+                        due_at: task.due_at,
+                        tags: task.tags.unwrap_or_default(),
+                        priority: task.priority,
                     });
                     (id, task_signal)
                 })
@@ -51,7 +65,7 @@ use server_fn::codec::GetUrl;
 #[cfg(feature = "ssr")]
 pub use ssr::*;
 
-#[server(prefix = "/todo", endpoint = "fetch", input = GetUrl)]
+#[server(prefix = "/todo", endpoint = "fetch", input = GetUrl, client = crate::components::csrf::CsrfClient)]
 #[middleware(compose_from_fn!(require_login))]
 async fn todo_fetch(/* filter? */) -> Result<HashMap<u32, TaskSchema>, ServerFnError> {
     use crate::supabase::{AuthSession, Supabase};
@@ -70,7 +84,7 @@ async fn todo_fetch(/* filter? */) -> Result<HashMap<u32, TaskSchema>, ServerFnE
         .client
         .query()
         .from("tasks")
-        .select("id,title,description,completed")
+        .select("id,title,description,completed,due_at,tags,priority,reminder:reminders(remind_at)")
         .eq("author_id", user_id.clone())
         .auth(user_token)
         .execute()
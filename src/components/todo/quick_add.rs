@@ -0,0 +1,583 @@
+//! Quick-add syntax for new tasks: `Pay rent tomorrow 9am #home !high`
+//!
+//! Kept free of any IO so the same parser previews the task in `NewTaskForm`
+//! and authoritatively creates it in `todo_create`.
+//!
+//! Understood phrases, optionally preceded by `on`, `at`, `by` or `due`:
+//! - dates: `today`, `tomorrow`, weekdays (`fri`, `next friday`), `next week`, `next month`,
+//!   `in 3 days`, `in 2 weeks`, `in a month`, `2024-08-15`, `aug 15`, `15th august`
+//! - times: `9am`, `9:30 pm`, `21:00`, `noon`, `midnight`
+//! - both: `tonight`, `in 2 hours`, `in 30 minutes`
+//! - `#tag` (any number) and `!high` / `!medium` / `!low` (or `!h`, `!1`, `!!!`, ...)
+
+use time::{Date, Duration, Month, OffsetDateTime, PrimitiveDateTime, Time, Weekday};
+
+/// Time of day given to due dates without one
+const DEFAULT_TIME: Time = time::macros::time!(9:00);
+const TONIGHT: Time = time::macros::time!(20:00);
+const PREPOSITIONS: [&str; 4] = ["on", "at", "by", "due"];
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Low,
+    Medium,
+    High,
+}
+
+impl Priority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Priority::Low => "low",
+            Priority::Medium => "medium",
+            Priority::High => "high",
+        }
+    }
+
+    fn parse(word: &str) -> Option<Self> {
+        match word.to_lowercase().as_str() {
+            "high" | "hi" | "h" | "urgent" | "1" | "!!" => Some(Priority::High),
+            "medium" | "med" | "m" | "normal" | "2" | "!" => Some(Priority::Medium),
+            "low" | "lo" | "l" | "3" => Some(Priority::Low),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuickAdd {
+    pub title: String,
+    /// In the offset of the `now` given to [`parse`]
+    pub due: Option<OffsetDateTime>,
+    pub tags: Vec<String>,
+    pub priority: Option<Priority>,
+}
+
+enum Match {
+    Date(Date),
+    Time(Time),
+    DateTime(PrimitiveDateTime),
+}
+
+/// Extracts the due date, tags and priority from `input`, the rest is the title.
+/// If nothing would be left of the title the input is taken verbatim.
+pub fn parse(input: &str, now: OffsetDateTime) -> QuickAdd {
+    let tokens: Vec<&str> = input.split_whitespace().collect();
+    let mut consumed = vec![false; tokens.len()];
+    let mut tags: Vec<String> = vec![];
+    let mut priority = None;
+
+    for (i, token) in tokens.iter().enumerate() {
+        if let Some(tag) = token.strip_prefix('#') {
+            let valid = !tag.is_empty()
+                && tag
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+            if valid {
+                let tag = tag.to_lowercase();
+                if !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+                consumed[i] = true;
+            }
+        } else if let Some(p) = token.strip_prefix('!').and_then(Priority::parse) {
+            if priority.is_none() {
+                priority = Some(p);
+                consumed[i] = true;
+            }
+        }
+    }
+
+    let local_now = PrimitiveDateTime::new(now.date(), now.time());
+    let (mut date, mut time, mut datetime) = (None, None, None);
+
+    let mut i = 0;
+    while i < tokens.len() {
+        // Phrases can't span over tags or priorities
+        let words: Vec<String> = tokens[i..]
+            .iter()
+            .zip(&consumed[i..])
+            .take_while(|(_, consumed)| !**consumed)
+            .map(|(token, _)| token.trim_end_matches([',', ';']).to_lowercase())
+            .collect();
+
+        let matched = match match_phrase(&words, local_now) {
+            Some((Match::Date(d), len)) if date.is_none() && datetime.is_none() => {
+                date = Some(d);
+                Some(len)
+            }
+            Some((Match::Time(t), len)) if time.is_none() && datetime.is_none() => {
+                time = Some(t);
+                Some(len)
+            }
+            Some((Match::DateTime(dt), len))
+                if date.is_none() && time.is_none() && datetime.is_none() =>
+            {
+                datetime = Some(dt);
+                Some(len)
+            }
+            _ => None,
+        };
+
+        match matched {
+            Some(len) => {
+                consumed[i..i + len].fill(true);
+                if i > 0
+                    && !consumed[i - 1]
+                    && PREPOSITIONS.contains(&tokens[i - 1].to_lowercase().as_str())
+                {
+                    consumed[i - 1] = true;
+                }
+                i += len;
+            }
+            None => i += 1,
+        }
+    }
+
+    let due = match (datetime, date, time) {
+        (Some(dt), _, _) => Some(dt),
+        (None, Some(d), t) => Some(d.with_time(t.unwrap_or(DEFAULT_TIME))),
+        (None, None, Some(t)) => {
+            let today = local_now.date().with_time(t);
+            // A time that already passed today means tomorrow
+            Some(if today <= local_now {
+                today + Duration::days(1)
+            } else {
+                today
+            })
+        }
+        (None, None, None) => None,
+    }
+    .map(|dt| dt.assume_offset(now.offset()));
+
+    let title = tokens
+        .iter()
+        .zip(&consumed)
+        .filter(|(_, consumed)| !**consumed)
+        .map(|(token, _)| *token)
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if title.is_empty() {
+        return QuickAdd {
+            title: input.trim().to_string(),
+            due: None,
+            tags: vec![],
+            priority: None,
+        };
+    }
+
+    QuickAdd {
+        title,
+        due,
+        tags,
+        priority,
+    }
+}
+
+/// Tries every phrase at the start of `words`, returning it with the number of words it spans
+fn match_phrase(words: &[String], now: PrimitiveDateTime) -> Option<(Match, usize)> {
+    let today = now.date();
+    let first = words.first()?.as_str();
+    let second = words.get(1).map(String::as_str);
+
+    match first {
+        "today" => return Some((Match::Date(today), 1)),
+        "tomorrow" | "tmrw" | "tmr" => return Some((Match::Date(today.next_day()?), 1)),
+        "tonight" => return Some((Match::DateTime(today.with_time(TONIGHT)), 1)),
+        "noon" => return Some((Match::Time(time::macros::time!(12:00)), 1)),
+        "midnight" => return Some((Match::Time(Time::MIDNIGHT), 1)),
+        "next" => {
+            return match second? {
+                "week" => Some((Match::Date(today + Duration::weeks(1)), 2)),
+                "month" => Some((Match::Date(add_months(today, 1)?), 2)),
+                day => Some((Match::Date(next_weekday(today, parse_weekday(day)?)), 2)),
+            };
+        }
+        "in" => return parse_in(words, now),
+        _ => {}
+    }
+
+    if let Some(weekday) = parse_weekday(first) {
+        return Some((Match::Date(next_weekday(today, weekday)), 1));
+    }
+    if let Some(date) = parse_iso_date(first) {
+        return Some((Match::Date(date), 1));
+    }
+    if let Some((date, len)) = parse_month_day(words, today) {
+        return Some((Match::Date(date), len));
+    }
+    parse_time(words).map(|(t, len)| (Match::Time(t), len))
+}
+
+/// `in 3 days`, `in a week`, `in 2 hours`, `in 45 minutes`, `in 2h`
+fn parse_in(words: &[String], now: PrimitiveDateTime) -> Option<(Match, usize)> {
+    let amount_word = words.get(1)?.as_str();
+
+    // Compact form: `in 2h`, `in 3d`
+    if let Some(unit_at) = amount_word.find(|c: char| !c.is_ascii_digit()) {
+        if unit_at > 0 {
+            let amount: i64 = amount_word[..unit_at].parse().ok()?;
+            return in_amount(amount, &amount_word[unit_at..], now).map(|m| (m, 2));
+        }
+    }
+
+    let amount: i64 = match amount_word {
+        "a" | "an" | "one" => 1,
+        n => n.parse().ok()?,
+    };
+    in_amount(amount, words.get(2)?, now).map(|m| (m, 3))
+}
+
+fn in_amount(amount: i64, unit: &str, now: PrimitiveDateTime) -> Option<Match> {
+    if !(0..=1000).contains(&amount) {
+        return None;
+    }
+    let today = now.date();
+    match unit {
+        "minute" | "minutes" | "min" | "mins" | "m" => {
+            Some(Match::DateTime(now + Duration::minutes(amount)))
+        }
+        "hour" | "hours" | "hr" | "hrs" | "h" => {
+            Some(Match::DateTime(now + Duration::hours(amount)))
+        }
+        "day" | "days" | "d" => Some(Match::Date(today + Duration::days(amount))),
+        "week" | "weeks" | "wk" | "wks" | "w" => Some(Match::Date(today + Duration::weeks(amount))),
+        "month" | "months" | "mo" => Some(Match::Date(add_months(today, amount as u32)?)),
+        _ => None,
+    }
+}
+
+fn parse_weekday(word: &str) -> Option<Weekday> {
+    match word {
+        "mon" | "monday" => Some(Weekday::Monday),
+        "tue" | "tues" | "tuesday" => Some(Weekday::Tuesday),
+        "wed" | "wednesday" => Some(Weekday::Wednesday),
+        "thu" | "thur" | "thurs" | "thursday" => Some(Weekday::Thursday),
+        "fri" | "friday" => Some(Weekday::Friday),
+        "sat" | "saturday" => Some(Weekday::Saturday),
+        "sun" | "sunday" => Some(Weekday::Sunday),
+        _ => None,
+    }
+}
+
+/// The first `weekday` strictly after `today`
+fn next_weekday(today: Date, weekday: Weekday) -> Date {
+    let ahead =
+        (weekday.number_days_from_monday() + 7 - today.weekday().number_days_from_monday()) % 7;
+    today + Duration::days(if ahead == 0 { 7 } else { ahead as i64 })
+}
+
+/// Same day of the month `months` later, clamped to the length of that month
+fn add_months(date: Date, months: u32) -> Option<Date> {
+    let month_index = date.month() as u32 - 1 + months;
+    let year = date.year() + (month_index / 12) as i32;
+    let month = Month::try_from((month_index % 12 + 1) as u8).ok()?;
+    let day = date.day().min(time::util::days_in_year_month(year, month));
+    Date::from_calendar_date(year, month, day).ok()
+}
+
+fn parse_iso_date(word: &str) -> Option<Date> {
+    let mut parts = word.splitn(3, '-');
+    let year: i32 = parts.next()?.parse().ok()?;
+    let month: u8 = parts.next()?.parse().ok()?;
+    let day: u8 = parts.next()?.parse().ok()?;
+    if year < 1000 {
+        return None;
+    }
+    Date::from_calendar_date(year, Month::try_from(month).ok()?, day).ok()
+}
+
+fn parse_month(word: &str) -> Option<Month> {
+    const MONTHS: [(&str, Month); 12] = [
+        ("january", Month::January),
+        ("february", Month::February),
+        ("march", Month::March),
+        ("april", Month::April),
+        ("may", Month::May),
+        ("june", Month::June),
+        ("july", Month::July),
+        ("august", Month::August),
+        ("september", Month::September),
+        ("october", Month::October),
+        ("november", Month::November),
+        ("december", Month::December),
+    ];
+    if word.len() < 3 {
+        return None;
+    }
+    MONTHS
+        .iter()
+        .find(|(name, _)| name.starts_with(word))
+        .map(|(_, month)| *month)
+}
+
+/// `15`, `15th`, `1st`
+fn parse_day(word: &str) -> Option<u8> {
+    let digits = word.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let suffix = &word[digits.len()..];
+    if !matches!(suffix, "" | "st" | "nd" | "rd" | "th") {
+        return None;
+    }
+    digits.parse().ok().filter(|d| (1..=31).contains(d))
+}
+
+/// `aug 15` or `15th august`, the next such date from `today`
+fn parse_month_day(words: &[String], today: Date) -> Option<(Date, usize)> {
+    let second = words.get(1)?;
+    let (month, day) = match (parse_month(&words[0]), parse_day(second)) {
+        (Some(month), Some(day)) => (month, day),
+        _ => (parse_month(second)?, parse_day(&words[0])?),
+    };
+
+    let this_year = Date::from_calendar_date(today.year(), month, day).ok();
+    match this_year {
+        Some(date) if date >= today => Some((date, 2)),
+        _ => Date::from_calendar_date(today.year() + 1, month, day)
+            .ok()
+            .map(|date| (date, 2)),
+    }
+}
+
+/// `9am`, `9:30pm`, `9 am`, `21:00`. Bare numbers aren't taken as times.
+fn parse_time(words: &[String]) -> Option<(Time, usize)> {
+    let word = words.first()?.as_str();
+
+    for (suffix, pm) in [("am", false), ("a.m.", false), ("pm", true), ("p.m.", true)] {
+        if let Some(clock) = word.strip_suffix(suffix).filter(|c| !c.is_empty()) {
+            return clock_time(clock, Some(pm)).map(|t| (t, 1));
+        }
+    }
+    if let Some(pm) = match words.get(1).map(String::as_str) {
+        Some("am" | "a.m.") => Some(false),
+        Some("pm" | "p.m.") => Some(true),
+        _ => None,
+    } {
+        if let Some(t) = clock_time(word, Some(pm)) {
+            return Some((t, 2));
+        }
+    }
+    if word.contains(':') {
+        return clock_time(word, None).map(|t| (t, 1));
+    }
+    None
+}
+
+/// `h`, `h:mm` on a 12 hour clock when `pm` is given, `hh:mm` on a 24 hour clock otherwise
+fn clock_time(clock: &str, pm: Option<bool>) -> Option<Time> {
+    let (hour, minute) = match clock.split_once(':') {
+        Some((hour, minute)) if minute.len() == 2 => (hour, minute.parse::<u8>().ok()?),
+        Some(_) => return None,
+        None if pm.is_some() => (clock, 0),
+        None => return None,
+    };
+    if hour.is_empty() || hour.len() > 2 {
+        return None;
+    }
+    let hour: u8 = hour.parse().ok()?;
+
+    let hour = match pm {
+        Some(_) if !(1..=12).contains(&hour) => return None,
+        Some(false) => hour % 12,
+        Some(true) => hour % 12 + 12,
+        None => hour,
+    };
+    Time::from_hms(hour, minute, 0).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    /// A Wednesday
+    const NOW: OffsetDateTime = datetime!(2024-08-14 10:00 UTC);
+
+    fn due(input: &str) -> Option<OffsetDateTime> {
+        parse(input, NOW).due
+    }
+
+    #[test]
+    fn parses_everything_at_once() {
+        let parsed = parse("Pay rent tomorrow 9am #home !high", NOW);
+        assert_eq!(
+            parsed,
+            QuickAdd {
+                title: "Pay rent".to_string(),
+                due: Some(datetime!(2024-08-15 9:00 UTC)),
+                tags: vec!["home".to_string()],
+                priority: Some(Priority::High),
+            }
+        );
+    }
+
+    #[test]
+    fn relative_dates() {
+        assert_eq!(due("Ship today"), Some(datetime!(2024-08-14 9:00 UTC)));
+        assert_eq!(
+            due("Call mom in 3 days"),
+            Some(datetime!(2024-08-17 9:00 UTC))
+        );
+        assert_eq!(due("Call mom in 3d"), Some(datetime!(2024-08-17 9:00 UTC)));
+        assert_eq!(
+            due("Review next week"),
+            Some(datetime!(2024-08-21 9:00 UTC))
+        );
+        assert_eq!(
+            due("Review in 2 weeks"),
+            Some(datetime!(2024-08-28 9:00 UTC))
+        );
+        assert_eq!(
+            due("Invoice next month"),
+            Some(datetime!(2024-09-14 9:00 UTC))
+        );
+        assert_eq!(
+            due("Invoice in a month"),
+            Some(datetime!(2024-09-14 9:00 UTC))
+        );
+        assert_eq!(
+            due("Deploy in 2 hours"),
+            Some(datetime!(2024-08-14 12:00 UTC))
+        );
+        assert_eq!(
+            due("Tea in 45 minutes"),
+            Some(datetime!(2024-08-14 10:45 UTC))
+        );
+        assert_eq!(due("Movie tonight"), Some(datetime!(2024-08-14 20:00 UTC)));
+    }
+
+    #[test]
+    fn weekdays_are_always_ahead() {
+        assert_eq!(due("Demo fri"), Some(datetime!(2024-08-16 9:00 UTC)));
+        assert_eq!(
+            due("Demo next friday"),
+            Some(datetime!(2024-08-16 9:00 UTC))
+        );
+        // Today is a Wednesday, the next one is a week away
+        assert_eq!(
+            due("Standup wednesday"),
+            Some(datetime!(2024-08-21 9:00 UTC))
+        );
+    }
+
+    #[test]
+    fn months_are_clamped_to_their_length() {
+        let parsed = parse("Rent in a month", datetime!(2024-01-31 10:00 UTC));
+        assert_eq!(parsed.due, Some(datetime!(2024-02-29 9:00 UTC)));
+    }
+
+    #[test]
+    fn absolute_dates() {
+        assert_eq!(due("Trip 2024-09-01"), Some(datetime!(2024-09-01 9:00 UTC)));
+        assert_eq!(due("Party aug 15"), Some(datetime!(2024-08-15 9:00 UTC)));
+        assert_eq!(
+            due("Party 15th august"),
+            Some(datetime!(2024-08-15 9:00 UTC))
+        );
+        // Already passed this year
+        assert_eq!(due("Birthday aug 1"), Some(datetime!(2025-08-01 9:00 UTC)));
+    }
+
+    #[test]
+    fn times() {
+        assert_eq!(due("Lunch noon"), Some(datetime!(2024-08-14 12:00 UTC)));
+        assert_eq!(due("Call 21:00"), Some(datetime!(2024-08-14 21:00 UTC)));
+        // Passed today, so tomorrow
+        assert_eq!(due("Coffee 9am"), Some(datetime!(2024-08-15 9:00 UTC)));
+
+        let parsed = parse("Dentist fri at 2:30 pm", NOW);
+        assert_eq!(parsed.title, "Dentist");
+        assert_eq!(parsed.due, Some(datetime!(2024-08-16 14:30 UTC)));
+    }
+
+    #[test]
+    fn prepositions_go_with_their_phrase() {
+        let parsed = parse("Taxes due 2024-09-01", NOW);
+        assert_eq!(parsed.title, "Taxes");
+        assert_eq!(parsed.due, Some(datetime!(2024-09-01 9:00 UTC)));
+
+        // Not followed by a phrase, kept in the title
+        assert_eq!(parse("Meet at the park", NOW).title, "Meet at the park");
+    }
+
+    #[test]
+    fn bare_numbers_are_not_times() {
+        let parsed = parse("Buy 3 apples", NOW);
+        assert_eq!(parsed.title, "Buy 3 apples");
+        assert_eq!(parsed.due, None);
+    }
+
+    #[test]
+    fn due_dates_are_in_the_offset_of_now() {
+        // Already the 15th here, still the 14th in UTC
+        let now = datetime!(2024-08-15 1:00 +9);
+        let parsed = parse("Ship today", now);
+        assert_eq!(parsed.due, Some(datetime!(2024-08-15 9:00 +9)));
+        assert_eq!(parsed.due.unwrap().offset(), now.offset());
+
+        let parsed = parse("Ship tomorrow", now);
+        assert_eq!(parsed.due, Some(datetime!(2024-08-16 9:00 +9)));
+
+        // Late in the evening a time that passed rolls over to the next local day
+        let now = datetime!(2024-08-14 23:30 -7);
+        assert_eq!(
+            parse("Backup 11pm", now).due,
+            Some(datetime!(2024-08-15 23:00 -7))
+        );
+        assert_eq!(
+            parse("Backup midnight", now).due,
+            Some(datetime!(2024-08-15 0:00 -7))
+        );
+    }
+
+    #[test]
+    fn multiple_tags() {
+        let parsed = parse("Groceries #Home #errands #home #a.b #", NOW);
+        assert_eq!(parsed.tags, vec!["home".to_string(), "errands".to_string()]);
+        // Not valid tags, left in the title
+        assert_eq!(parsed.title, "Groceries #a.b #");
+    }
+
+    #[test]
+    fn priority_tokens() {
+        for (token, priority) in [
+            ("!high", Priority::High),
+            ("!h", Priority::High),
+            ("!1", Priority::High),
+            ("!!!", Priority::High),
+            ("!medium", Priority::Medium),
+            ("!2", Priority::Medium),
+            ("!!", Priority::Medium),
+            ("!low", Priority::Low),
+            ("!3", Priority::Low),
+        ] {
+            let parsed = parse(&format!("Task {token}"), NOW);
+            assert_eq!(parsed.priority, Some(priority), "{token}");
+            assert_eq!(parsed.title, "Task", "{token}");
+        }
+
+        // Only the first counts, the others stay in the title
+        let parsed = parse("Task !low !high", NOW);
+        assert_eq!(parsed.priority, Some(Priority::Low));
+        assert_eq!(parsed.title, "Task !high");
+
+        let parsed = parse("Wow!", NOW);
+        assert_eq!(parsed.priority, None);
+    }
+
+    #[test]
+    fn input_of_only_tokens_is_taken_verbatim() {
+        let parsed = parse("  #home !high tomorrow 9am ", NOW);
+        assert_eq!(
+            parsed,
+            QuickAdd {
+                title: "#home !high tomorrow 9am".to_string(),
+                due: None,
+                tags: vec![],
+                priority: None,
+            }
+        );
+    }
+}
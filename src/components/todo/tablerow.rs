@@ -5,14 +5,16 @@
 
 use leptos::*;
 
-use super::{Task, TaskSchema, Tasks};
+use super::{Task, TaskSchema, Tasks, TITLE_MAX_LEN};
 
 #[island]
 pub fn TaskEdit(task: RwSignal<Task>, id: u32) -> impl IntoView {
     let tasks = expect_context::<Tasks>();
     let update_task_action = create_server_action::<TodoUpdate>();
+    let set_reminder_action = create_server_action::<TodoSetReminder>();
     let title_input = RwSignal::new(String::default());
     let description_input = RwSignal::new(String::default());
+    let remind_input = RwSignal::new(String::default());
     let prefers_dark = RwSignal::new(false);
 
     prefers_dark.set(crate::PrefersDark::check());
@@ -27,9 +29,21 @@ pub fn TaskEdit(task: RwSignal<Task>, id: u32) -> impl IntoView {
             },
         });
 
+        let reminder_changed = remind_input() != utc_to_local_input(task().remind_at);
+        let remind_at = local_input_to_utc(&remind_input());
+        if reminder_changed {
+            set_reminder_action.dispatch(TodoSetReminder {
+                id,
+                remind_at: remind_at.clone().unwrap_or_default(),
+            });
+        }
+
         task.update(|t| {
             t.title = title_input();
             t.description = description_input();
+            if reminder_changed {
+                t.remind_at = remind_at;
+            }
         });
         tasks.signal.update(|_| {});
     };
@@ -37,12 +51,14 @@ pub fn TaskEdit(task: RwSignal<Task>, id: u32) -> impl IntoView {
     let reset_inputs = move |_| {
         title_input.set(task().title);
         description_input.set(task().description);
+        remind_input.set(utc_to_local_input(task().remind_at));
     };
 
     view! {
       {move || {
           title_input.set(task().title);
           description_input.set(task().description);
+          remind_input.set(utc_to_local_input(task().remind_at));
       }}
 
       <div id=&format!("edit-task_{id}") class="uk-flex-top" uk-modal>
@@ -58,7 +74,7 @@ pub fn TaskEdit(task: RwSignal<Task>, id: u32) -> impl IntoView {
             type="text"
             placeholder="Task Title"
             aria-label="Task Title"
-            maxlength="60"
+            maxlength=TITLE_MAX_LEN
             required
             class="uk-modal-title uk-input "
             on:input=move |ev| title_input.set(event_target_value(&ev))
@@ -78,6 +94,20 @@ pub fn TaskEdit(task: RwSignal<Task>, id: u32) -> impl IntoView {
             on:input=move |ev| description_input.set(event_target_value(&ev))
             prop:value=description_input
           ></textarea>
+          <div class="uk-margin uk-text-left">
+            <label class="uk-form-label" for=&format!("remind-at_{id}")>
+              "Remind me at"
+            </label>
+            <input
+              id=&format!("remind-at_{id}")
+              name="remind_at"
+              type="datetime-local"
+              aria-label="Remind me at"
+              class="uk-input"
+              on:input=move |ev| remind_input.set(event_target_value(&ev))
+              prop:value=remind_input
+            />
+          </div>
           <p class="uk-text-right">
             <button
               class="uk-button uk-button-default uk-modal-close"
@@ -106,6 +136,50 @@ pub fn TaskEdit(task: RwSignal<Task>, id: u32) -> impl IntoView {
     }
 }
 
+/// `datetime-local` inputs carry no offset, the browser converts them to UTC
+fn local_input_to_utc(value: &str) -> Option<String> {
+    if value.is_empty() {
+        return None;
+    }
+    #[cfg(not(feature = "ssr"))]
+    {
+        let date = js_sys::Date::new(&value.into());
+        if date.get_time().is_nan() {
+            return None;
+        }
+        Some(String::from(date.to_iso_string()))
+    }
+    #[cfg(feature = "ssr")]
+    {
+        Some(value.to_string())
+    }
+}
+
+/// Formats a UTC timestamp as the browser's local `YYYY-MM-DDTHH:MM`
+fn utc_to_local_input(remind_at: Option<String>) -> String {
+    let Some(remind_at) = remind_at else {
+        return String::default();
+    };
+    #[cfg(not(feature = "ssr"))]
+    {
+        let date = js_sys::Date::new(&remind_at.as_str().into());
+        if date.get_time().is_nan() {
+            return String::default();
+        }
+        // Shift by the timezone offset so the ISO string reads as local time
+        let offset_ms = date.get_timezone_offset() * 60_000.0;
+        let local = js_sys::Date::new(&(date.get_time() - offset_ms).into());
+        String::from(local.to_iso_string())
+            .chars()
+            .take(16)
+            .collect()
+    }
+    #[cfg(feature = "ssr")]
+    {
+        remind_at.chars().take(16).collect()
+    }
+}
+
 #[island]
 pub fn TaskTitle(task: RwSignal<Task>) -> impl IntoView {
     view! {
@@ -120,6 +194,45 @@ pub fn TaskTitle(task: RwSignal<Task>) -> impl IntoView {
               view! { <p class="uk-text-emphasis">{move || task().title}</p> }
           }
       }}
+      <TaskMeta task=task/>
+    }
+}
+
+/// Due date, priority and tags set through the quick-add syntax
+#[component]
+fn TaskMeta(task: RwSignal<Task>) -> impl IntoView {
+    let due = move || {
+        task()
+            .due_at
+            .map(|due_at| utc_to_local_input(Some(due_at)).replacen('T', " ", 1))
+    };
+
+    view! {
+      <div class="uk-text-small">
+        {move || due().map(|due| view! { <span class="uk-label">{due}</span> })}
+        {move || {
+            task()
+                .priority
+                .map(|priority| {
+                    view! {
+                      <span class="uk-label uk-label-warning uk-margin-small-left">
+                        {priority.as_str()}
+                      </span>
+                    }
+                })
+        }}
+        {move || {
+            task()
+                .tags
+                .into_iter()
+                .map(|tag| {
+                    view! {
+                      <span class="uk-label uk-label-success uk-margin-small-left">"#" {tag}</span>
+                    }
+                })
+                .collect_view()
+        }}
+      </div>
     }
 }
 
@@ -230,20 +343,18 @@ mod ssr {
 #[cfg(feature = "ssr")]
 pub use ssr::*;
 
-#[server(prefix = "/todo", endpoint = "update")]
+#[server(prefix = "/todo", endpoint = "update", client = crate::components::csrf::CsrfClient)]
 #[middleware(compose_from_fn!(require_login))]
 async fn todo_update(id: u32, updated_task: TaskSchema) -> Result<(), ServerFnError> {
     use super::TaskSchema;
     use crate::supabase::{AuthSession, Supabase};
+    use crate::webhooks::TaskEvent;
     use axum::Extension;
 
     let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
     let supabase = expect_context::<Supabase>();
 
-    let user_token = {
-        let user = auth_session.user.unwrap();
-        user.identity.auth_token
-    };
+    let identity = auth_session.user.unwrap().identity;
 
     // TODO: Pass the built query to a function that caches the response
     let query_response = supabase
@@ -252,30 +363,126 @@ async fn todo_update(id: u32, updated_task: TaskSchema) -> Result<(), ServerFnEr
         .from("tasks")
         .update(serde_json::to_string(&updated_task).unwrap())
         .eq("id", id.to_string())
-        .auth(user_token)
+        .auth(&identity.auth_token)
         .execute()
         .await;
 
-    supabase_rust::parse_response::<TaskSchema>(query_response)
+    let updated = supabase_rust::parse_response::<TaskSchema>(query_response)
         .await
         .map_err(crate::supabase::map_err)?;
+
+    if let Some(task) = updated.into_iter().next() {
+        let event = if updated_task.completed == Some(true) {
+            TaskEvent::Completed
+        } else {
+            TaskEvent::Updated
+        };
+        tokio::spawn(crate::webhooks::emit(
+            supabase,
+            identity.user_id,
+            identity.auth_token,
+            event,
+            task,
+        ));
+    }
     Ok(())
 }
 
-#[server(prefix = "/todo", endpoint = "delete")]
+/// Schedules the task's reminder, an empty `remind_at` removes it
+#[server(prefix = "/todo", endpoint = "set_reminder", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_login))]
+async fn todo_set_reminder(id: u32, remind_at: String) -> Result<(), ServerFnError> {
+    use crate::reminders::{lead_minutes, ReminderSchema};
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+    use time::format_description::well_known::Iso8601;
+    use time::OffsetDateTime;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+
+    let identity = auth_session.user.unwrap().identity;
+
+    if remind_at.is_empty() {
+        let query_response = supabase
+            .client
+            .query()
+            .from("reminders")
+            .delete()
+            .eq("task_id", id.to_string())
+            .auth(&identity.auth_token)
+            .execute()
+            .await;
+
+        supabase_rust::parse_response::<ReminderSchema>(query_response)
+            .await
+            .map_err(crate::supabase::map_err)?;
+        return Ok(());
+    }
+
+    let Ok(remind_at) = OffsetDateTime::parse(&remind_at, &Iso8601::DEFAULT) else {
+        expect_context::<leptos_axum::ResponseOptions>().set_status(http::StatusCode::BAD_REQUEST);
+        return Err(ServerFnError::Args("remind_at".to_string()));
+    };
+
+    // Reminders are delivered with the service key, only the task's author may set one
+    let query_response = supabase
+        .client
+        .query()
+        .from("tasks")
+        .select("id")
+        .eq("id", id.to_string())
+        .eq("author_id", identity.user_id.clone())
+        .auth(&identity.auth_token)
+        .execute()
+        .await;
+    let owned = supabase_rust::parse_response::<TaskSchema>(query_response)
+        .await
+        .map_err(crate::supabase::map_err)?;
+    if owned.is_empty() {
+        expect_context::<leptos_axum::ResponseOptions>().set_status(http::StatusCode::NOT_FOUND);
+        return Err(ServerFnError::ServerError("Task not found".to_string()));
+    }
+    let lead_time = time::Duration::minutes(lead_minutes(&supabase, &identity).await);
+
+    let reminder = serde_json::to_string(&ReminderSchema {
+        task_id: Some(id),
+        author_id: Some(identity.user_id.clone()),
+        remind_at: Some(remind_at.format(&Iso8601::DEFAULT).unwrap()),
+        notify_at: Some((remind_at - lead_time).format(&Iso8601::DEFAULT).unwrap()),
+        // Rescheduling re-arms an already delivered reminder
+        sent_at: None,
+    })
+    .unwrap();
+
+    let query_response = supabase
+        .client
+        .query()
+        .from("reminders")
+        .upsert(reminder)
+        .on_conflict("task_id")
+        .auth(&identity.auth_token)
+        .execute()
+        .await;
+
+    supabase_rust::parse_response::<ReminderSchema>(query_response)
+        .await
+        .map_err(crate::supabase::map_err)?;
+    Ok(())
+}
+
+#[server(prefix = "/todo", endpoint = "delete", client = crate::components::csrf::CsrfClient)]
 #[middleware(compose_from_fn!(require_login))]
 async fn todo_delete(id: u32) -> Result<(), ServerFnError> {
     use super::TaskSchema;
     use crate::supabase::{AuthSession, Supabase};
+    use crate::webhooks::TaskEvent;
     use axum::Extension;
 
     let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
     let supabase = expect_context::<Supabase>();
 
-    let user_token = {
-        let user = auth_session.user.unwrap();
-        user.identity.auth_token
-    };
+    let identity = auth_session.user.unwrap().identity;
 
     // TODO: Pass the built query to a function that caches the response
     let query_response = supabase
@@ -284,12 +491,22 @@ async fn todo_delete(id: u32) -> Result<(), ServerFnError> {
         .from("tasks")
         .delete()
         .eq("id", id.to_string())
-        .auth(user_token)
+        .auth(&identity.auth_token)
         .execute()
         .await;
 
-    supabase_rust::parse_response::<TaskSchema>(query_response)
+    let deleted = supabase_rust::parse_response::<TaskSchema>(query_response)
         .await
         .map_err(crate::supabase::map_err)?;
+
+    if let Some(task) = deleted.into_iter().next() {
+        tokio::spawn(crate::webhooks::emit(
+            supabase,
+            identity.user_id,
+            identity.auth_token,
+            TaskEvent::Deleted,
+            task,
+        ));
+    }
     Ok(())
 }
@@ -1,6 +1,8 @@
 use leptos::*;
 use leptos_router::ActionForm;
 
+use crate::components::csrf::CsrfField;
+
 #[component]
 pub fn TopNavBar(authenticated: bool) -> impl IntoView {
     let prefers_dark = RwSignal::new(false);
@@ -64,6 +66,7 @@ pub fn TopNavBar(authenticated: bool) -> impl IntoView {
                     </li>
                     <li>
                       <ActionForm action=sign_out>
+                        <CsrfField/>
                         <button
                           type="submit"
                           class="uk-navbar-item uk-text-primary uk-button uk-button-link"
@@ -103,7 +106,7 @@ use crate::middlewares::require_login;
 #[cfg(feature = "ssr")]
 use crate::compose_from_fn;
 
-#[server(prefix = "/auth", endpoint = "signout")]
+#[server(prefix = "/auth", endpoint = "signout", client = crate::components::csrf::CsrfClient)]
 #[middleware(compose_from_fn!(require_login))]
 async fn sign_out() -> Result<(), ServerFnError> {
     use crate::supabase::{AuthSession, Supabase, SupabaseError};
@@ -146,7 +149,7 @@ async fn sign_out() -> Result<(), ServerFnError> {
     }
 }
 
-#[server(endpoint = "toggle_dark")]
+#[server(endpoint = "toggle_dark", client = crate::components::csrf::CsrfClient)]
 async fn toggle_dark_mode() -> Result<bool, ServerFnError> {
     use axum_extra::extract::cookie::{Cookie, SameSite};
     use axum_extra::extract::CookieJar;
@@ -1,10 +1,14 @@
 #[cfg(feature = "ssr")]
 #[path = ""]
 mod ssr {
+    pub mod api;
     pub mod middlewares;
 
     pub mod fileserv;
+    pub mod notifications;
+    pub mod reminders;
     pub mod supabase;
+    pub mod webhooks;
 
     #[derive(axum::extract::FromRef, Clone, Debug)]
     pub struct AppState {
@@ -20,6 +24,8 @@ mod components;
 pub mod error_template;
 mod pages;
 
+pub use components::todo::TaskSchema;
+
 #[cfg(feature = "hydrate")]
 #[wasm_bindgen::prelude::wasm_bindgen]
 pub fn hydrate() {
@@ -15,7 +15,13 @@ async fn main() {
     use leptos_axum::{generate_route_list, handle_server_fns_with_context, LeptosRoutes};
     use todo_leptos_supabase::app::*;
     use todo_leptos_supabase::fileserv::file_and_error_handler;
-    use todo_leptos_supabase::supabase::{AuthSession, SupabaseBackend};
+    use todo_leptos_supabase::middlewares::{
+        csrf_protect, CsrfToken, MemoryAttemptStore, RateLimiter,
+    };
+    use todo_leptos_supabase::supabase::{
+        durable, run_session_reaper, AuthSession, SupabaseBackend,
+    };
+    use todo_leptos_supabase::{api, notifications, reminders, webhooks};
     use todo_leptos_supabase::{AppState, PrefersDark};
     use tower_sessions_moka_store::MokaStore;
 
@@ -32,7 +38,38 @@ async fn main() {
     let routes = generate_route_list(App);
 
     let session_store_cache = MokaStore::new(Some(100));
-    let supabase = SupabaseBackend::new(session_store_cache);
+    let supabase = SupabaseBackend::new(session_store_cache, durable::from_env().await);
+
+    let reminder_scan_interval = std::env::var("REMINDER_SCAN_SECS")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or(60);
+    tokio::spawn(reminders::run_scheduler(
+        Arc::clone(&supabase),
+        notifications::from_env(),
+        std::time::Duration::from_secs(reminder_scan_interval),
+    ));
+    // Moves sessions to the current key after a rotation, plaintext ones after upgrading
+    let reencrypting = Arc::clone(&supabase);
+    tokio::spawn(async move {
+        match reencrypting.reencrypt_sessions().await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!(count, "Re-encrypted stored sessions"),
+            Err(e) => tracing::error!("reencrypt_sessions - {e:?}"),
+        }
+    });
+    let session_reap_interval = std::env::var("SESSION_REAP_SECS")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or(600);
+    tokio::spawn(run_session_reaper(
+        Arc::clone(&supabase),
+        std::time::Duration::from_secs(session_reap_interval),
+    ));
+    tokio::spawn(webhooks::run_worker(
+        Arc::clone(&supabase),
+        std::time::Duration::from_secs(10),
+    ));
 
     let auth_layer = AuthManagerLayerBuilder::new(
         supabase.as_auth_backend(),
@@ -42,6 +79,8 @@ async fn main() {
     )
     .build();
 
+    let rate_limiter = RateLimiter::new(Arc::new(MemoryAttemptStore::default()));
+
     async fn server_fn_handler(
         State(AppState {
             leptos_options,
@@ -67,6 +106,7 @@ async fn main() {
             supabase,
         }): State<AppState>,
         Extension(auth_session): Extension<AuthSession>,
+        Extension(csrf_token): Extension<CsrfToken>,
         uri: axum::extract::OriginalUri,
         raw_query: axum::extract::RawQuery,
         req: Request<Body>,
@@ -86,6 +126,7 @@ async fn main() {
                 provide_context(raw_query.clone());
                 provide_context(Arc::clone(&supabase));
                 provide_context(prefers_dark.clone());
+                provide_context(csrf_token.clone());
             },
             App,
         );
@@ -113,14 +154,24 @@ async fn main() {
         )
         .leptos_routes_with_handler(routes, leptos_routes_handler)
         .layer(auth_layer)
+        // Checks the token of every server fn post, see `CsrfField` and `CsrfClient`
+        .layer(axum::middleware::from_fn(csrf_protect))
+        // Read by the `rate_limit` middleware of the sign in and 2FA server fns
+        .layer(Extension(rate_limiter.clone()))
+        // Authenticated by personal access tokens, not the session cookie
+        .nest("/api/v1", api::router().layer(Extension(rate_limiter)))
         .fallback(file_and_error_handler)
         .with_state(app_state);
 
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
     logging::log!("listening on http://{}", &addr);
-    axum::serve(listener, app.into_make_service())
-        .await
-        .unwrap();
+    axum::serve(
+        listener,
+        // The peer address is kept with new sessions, see `DeviceInfo`
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
 
 #[cfg(not(feature = "ssr"))]
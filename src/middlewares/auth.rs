@@ -1,7 +1,36 @@
 use axum::body::Body;
-use http::{Request, Response, StatusCode};
+use futures_util::future::BoxFuture;
+use http::{header, Request, Response, StatusCode};
 
-use crate::supabase::AuthSession;
+use crate::components::auth::{redirect, SignInRequired};
+use crate::supabase::{AuthSession, IdentityData};
+
+/// What a server fn may require of the user, granted to roles through `ROLE_PERMISSIONS`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    ListUsers,
+    ManageUsers,
+    ViewAuditLog,
+}
+
+/// Roles, as set in `app_metadata.role`, and what they're allowed
+const ROLE_PERMISSIONS: &[(&str, &[Permission])] = &[(
+    "admin",
+    &[
+        Permission::ListUsers,
+        Permission::ManageUsers,
+        Permission::ViewAuditLog,
+    ],
+)];
+
+impl Permission {
+    pub fn granted_to(self, identity: &IdentityData) -> bool {
+        ROLE_PERMISSIONS
+            .iter()
+            .filter(|(role, _)| identity.has_role(role))
+            .any(|(_, permissions)| permissions.contains(&self))
+    }
+}
 
 pub async fn require_login(req: Request<Body>) -> Result<Request<Body>, Response<Body>> {
     let Some(auth_session) = req.extensions().get::<AuthSession>() else {
@@ -12,8 +41,271 @@ pub async fn require_login(req: Request<Body>) -> Result<Request<Body>, Response
     };
 
     if auth_session.user.is_none() {
-        return Err(Response::builder().body(Body::empty()).unwrap());
+        return Err(sign_in(&req));
     }
 
     Ok(req)
 }
+
+/// Sends the user to sign in, coming back to the page the request was made from.
+/// Form posts are redirected, calls from islands get a `401` to act on.
+fn sign_in(req: &Request<Body>) -> Response<Body> {
+    use leptos::server_fn::error::ServerFnErrorSerde;
+
+    let sign_in = SignInRequired::new(&referer_path(req).unwrap_or("/".to_string()));
+    if accepts_html(req) {
+        return Response::builder()
+            .status(StatusCode::SEE_OTHER)
+            .header(header::LOCATION, sign_in.location)
+            .body(Body::empty())
+            .unwrap();
+    }
+
+    let body = leptos::ServerFnError::from(sign_in)
+        .ser()
+        .unwrap_or_default();
+    Response::builder()
+        .status(StatusCode::UNAUTHORIZED)
+        .body(Body::from(body))
+        .unwrap()
+}
+
+/// Whether the request comes from the browser itself, a form post or a link without
+/// JavaScript, rather than from an island
+pub(super) fn accepts_html(req: &Request<Body>) -> bool {
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("text/html"))
+}
+
+/// Path and query of the page the request was made from, when safe to come back to
+pub(super) fn referer_path(req: &Request<Body>) -> Option<String> {
+    req.headers()
+        .get(header::REFERER)
+        .and_then(|referer| referer.to_str().ok())
+        .and_then(|referer| referer.parse::<http::Uri>().ok())
+        .and_then(|uri| uri.path_and_query().map(|path| path.to_string()))
+        .filter(|path| redirect::is_safe(path))
+}
+
+/// Middleware letting through users with `role` only
+pub fn require_role(
+    role: &'static str,
+) -> impl Fn(Request<Body>) -> BoxFuture<'static, Result<Request<Body>, Response<Body>>> {
+    move |req| {
+        Box::pin(async move {
+            let req = require_login(req).await?;
+            authorize(req, role, |identity| identity.has_role(role))
+        })
+    }
+}
+
+/// Middleware letting through users whose role grants `permission`
+pub fn require_permission(
+    permission: Permission,
+) -> impl Fn(Request<Body>) -> BoxFuture<'static, Result<Request<Body>, Response<Body>>> {
+    move |req| {
+        Box::pin(async move {
+            let req = require_login(req).await?;
+            authorize(req, permission, |identity| permission.granted_to(identity))
+        })
+    }
+}
+
+fn authorize(
+    req: Request<Body>,
+    requirement: impl std::fmt::Debug,
+    allowed: impl Fn(&IdentityData) -> bool,
+) -> Result<Request<Body>, Response<Body>> {
+    use leptos::server_fn::error::ServerFnErrorSerde;
+
+    // Only called past `require_login`
+    let identity = &req
+        .extensions()
+        .get::<AuthSession>()
+        .and_then(|auth_session| auth_session.user.as_ref())
+        .unwrap()
+        .identity;
+    if allowed(identity) {
+        return Ok(req);
+    }
+
+    tracing::warn!(
+        target: "audit",
+        user_id = %identity.user_id,
+        role = ?identity.role,
+        "Denied {} {}, requires {requirement:?}",
+        req.method(),
+        req.uri().path()
+    );
+    let body = leptos::ServerFnError::new("You aren't allowed to do that")
+        .ser()
+        .unwrap_or_default();
+    Err(Response::builder()
+        .status(StatusCode::FORBIDDEN)
+        .body(Body::from(body))
+        .unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::routing::get;
+    use axum::Router;
+    use axum_login::tower_sessions::SessionManagerLayer;
+    use axum_login::AuthManagerLayerBuilder;
+    use base64::prelude::{Engine as _, BASE64_URL_SAFE_NO_PAD};
+    use leptos::serde_json;
+    use time::OffsetDateTime;
+    use tower::ServiceExt;
+    use tower_sessions_moka_store::MokaStore;
+
+    use super::*;
+    use crate::compose_from_fn;
+    use crate::supabase::AppUser;
+
+    fn identity(role: Option<String>) -> IdentityData {
+        IdentityData {
+            auth_token: "token".to_string(),
+            refresh_token: "refresh".to_string(),
+            expiry_date: OffsetDateTime::now_utc() + time::Duration::hours(1),
+            last_accessed: OffsetDateTime::now_utc(),
+            user_id: "2d4bd2a4-4b6f-4b47-8f2e-0d5fb0a5a4c1".to_string(),
+            email: "user@example.com".to_string(),
+            has_mfa: false,
+            aal: "aal1".to_string(),
+            trusted_device: None,
+            verified_at: None,
+            role,
+        }
+    }
+
+    /// Access token whose claims are `claims`, the signature isn't looked at past `jwt_valid`
+    fn token(claims: serde_json::Value) -> String {
+        let encode = |value: serde_json::Value| BASE64_URL_SAFE_NO_PAD.encode(value.to_string());
+        format!(
+            "{}.{}.signature",
+            encode(serde_json::json!({ "alg": "HS256", "typ": "JWT" })),
+            encode(claims)
+        )
+    }
+
+    /// `/signin` signs `user` in, `/admin` is behind `require_role("admin")` and `/users`
+    /// behind `require_permission(Permission::ManageUsers)`
+    fn app(user: Option<IdentityData>) -> Router {
+        let supabase = crate::supabase::test_backend(None);
+        let auth_layer = AuthManagerLayerBuilder::new(
+            supabase.as_auth_backend(),
+            SessionManagerLayer::new(MokaStore::new(Some(10))).with_name("auth"),
+        )
+        .build();
+
+        Router::new()
+            .route(
+                "/signin",
+                get(move |mut auth_session: AuthSession| async move {
+                    if let Some(identity) = user {
+                        auth_session.login(&AppUser { identity }).await.unwrap();
+                    }
+                }),
+            )
+            .route(
+                "/admin",
+                get(|| async { "admin" }).layer(compose_from_fn!(require_role("admin"))),
+            )
+            .route(
+                "/users",
+                get(|| async { "users" }).layer(compose_from_fn!(require_permission(
+                    Permission::ManageUsers
+                ))),
+            )
+            .layer(auth_layer)
+    }
+
+    /// Signs `user` in and calls `path` from an island with the session
+    async fn call(user: Option<IdentityData>, path: &str) -> StatusCode {
+        let _runtime = leptos::create_runtime();
+        let app = app(user);
+
+        let res = app
+            .clone()
+            .oneshot(Request::get("/signin").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let cookie = res
+            .headers()
+            .get(header::SET_COOKIE)
+            .and_then(|cookie| cookie.to_str().ok())
+            .and_then(|cookie| cookie.split(';').next())
+            .unwrap_or_default()
+            .to_string();
+
+        let res = app
+            .oneshot(
+                Request::get(path)
+                    .header(header::COOKIE, cookie)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        res.status()
+    }
+
+    #[tokio::test]
+    async fn role_guards_let_the_role_through() {
+        let admin = identity(Some("admin".to_string()));
+        assert_eq!(call(Some(admin.clone()), "/admin").await, StatusCode::OK);
+        assert_eq!(call(Some(admin), "/users").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn role_guards_deny_other_roles() {
+        for role in [Some("editor"), Some("Admin"), Some("")] {
+            let user = identity(role.map(str::to_string));
+            assert_eq!(
+                call(Some(user.clone()), "/admin").await,
+                StatusCode::FORBIDDEN
+            );
+            assert_eq!(call(Some(user), "/users").await, StatusCode::FORBIDDEN);
+        }
+    }
+
+    #[tokio::test]
+    async fn role_guards_deny_missing_or_malformed_roles() {
+        for claims in [
+            serde_json::json!({ "sub": "user" }),
+            serde_json::json!({ "app_metadata": {} }),
+            serde_json::json!({ "app_metadata": { "role": 1 } }),
+            serde_json::json!({ "app_metadata": { "role": ["admin"] } }),
+            serde_json::json!({ "app_metadata": "admin" }),
+            // Only the service role can write app_metadata, user_metadata is up to the user
+            serde_json::json!({ "user_metadata": { "role": "admin" } }),
+        ] {
+            let role = IdentityData::role_from_token(&token(claims.clone()));
+            assert_eq!(role, None, "{claims}");
+            let user = identity(role);
+            assert_eq!(
+                call(Some(user.clone()), "/admin").await,
+                StatusCode::FORBIDDEN
+            );
+            assert_eq!(call(Some(user), "/users").await, StatusCode::FORBIDDEN);
+        }
+        assert_eq!(IdentityData::role_from_token("not a token"), None);
+    }
+
+    #[tokio::test]
+    async fn role_guards_ask_to_sign_in_first() {
+        assert_eq!(call(None, "/admin").await, StatusCode::UNAUTHORIZED);
+        assert_eq!(call(None, "/users").await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn role_is_read_from_app_metadata() {
+        let claims = serde_json::json!({ "app_metadata": { "role": "admin" } });
+        assert_eq!(
+            IdentityData::role_from_token(&token(claims)),
+            Some("admin".to_string())
+        );
+    }
+}
@@ -0,0 +1,160 @@
+//! Double-submit CSRF protection for the server fns. Every browser gets a random token in a
+//! cookie, state-changing requests have to send it back in the `x-csrf-token` header or the
+//! `csrf_token` form field, and come from the site itself when they say where from.
+
+use std::sync::OnceLock;
+
+use axum::body::Body;
+use axum::middleware::Next;
+use axum_extra::extract::cookie::{Cookie, SameSite};
+use axum_extra::extract::CookieJar;
+use http::{header, Method, Request, Response, StatusCode};
+
+pub use crate::components::csrf::CsrfToken;
+use crate::components::csrf::{CSRF_COOKIE, CSRF_FIELD, CSRF_HEADER};
+
+/// Form bodies read looking for the token, server fn arguments are far smaller
+const MAX_FORM_BODY: usize = 64 * 1024;
+
+/// Scheme, host and port of `SITE_URL`, what `Origin` has to be
+fn site_origin() -> &'static str {
+    static ORIGIN: OnceLock<String> = OnceLock::new();
+    ORIGIN.get_or_init(|| {
+        let site_url = std::env::var("SITE_URL").unwrap_or("http://localhost:3000".to_string());
+        let (scheme, rest) = site_url.split_once("://").unwrap_or(("http", &site_url));
+        let host = rest.split('/').next().unwrap_or_default();
+        format!("{scheme}://{host}").to_ascii_lowercase()
+    })
+}
+
+/// Router middleware issuing the token and checking it on anything but `GET`, `HEAD` and
+/// `OPTIONS`. Pages get the token in their context for `CsrfField`.
+pub async fn csrf_protect(req: Request<Body>, next: Next) -> Response<Body> {
+    let existing = CookieJar::from_headers(req.headers())
+        .get(CSRF_COOKIE)
+        .map(|cookie| cookie.value().to_string())
+        .filter(|token| !token.is_empty());
+
+    let mut req = req;
+    if !matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        if !same_origin(&req) {
+            tracing::warn!(
+                target: "audit",
+                "Rejected cross-site {} {}",
+                req.method(),
+                req.uri().path()
+            );
+            return forbidden("Cross-site requests aren't allowed");
+        }
+
+        let Some(expected) = existing.as_deref() else {
+            return forbidden("Missing CSRF token, reload the page and try again");
+        };
+        let (checked, sent) = submitted_token(req).await;
+        req = checked;
+        if !sent.is_some_and(|sent| tokens_match(&sent, expected)) {
+            return forbidden("Invalid CSRF token, reload the page and try again");
+        }
+    }
+
+    let issued = existing.is_none().then(generate_token);
+    let token = existing.or(issued.clone()).unwrap_or_default();
+    req.extensions_mut().insert(CsrfToken(token));
+
+    let mut res = next.run(req).await;
+    if let Some(token) = issued {
+        // Not `HttpOnly`, islands read it to set the header
+        let cookie = Cookie::build((CSRF_COOKIE, token))
+            .path("/")
+            .secure(site_origin().starts_with("https://"))
+            .same_site(SameSite::Strict)
+            .build();
+        if let Ok(value) = cookie.encoded().to_string().parse() {
+            res.headers_mut().append(header::SET_COOKIE, value);
+        }
+    }
+    res
+}
+
+/// `Origin`, or `Referer` when there's no `Origin`, has to be the site. Requests with neither,
+/// from older browsers and other clients, are left to the token.
+fn same_origin(req: &Request<Body>) -> bool {
+    let header_value = |name: header::HeaderName| {
+        req.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_ascii_lowercase)
+    };
+
+    if let Some(origin) = header_value(header::ORIGIN) {
+        return origin == site_origin();
+    }
+    if let Some(referer) = header_value(header::REFERER) {
+        return referer
+            .strip_prefix(site_origin())
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'));
+    }
+    true
+}
+
+/// The token from the header, or from the body of a form post, handing back the request
+/// with its body intact
+async fn submitted_token(req: Request<Body>) -> (Request<Body>, Option<String>) {
+    if let Some(token) = req
+        .headers()
+        .get(CSRF_HEADER)
+        .and_then(|value| value.to_str().ok())
+    {
+        let token = token.to_string();
+        return (req, Some(token));
+    }
+
+    let is_form = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/x-www-form-urlencoded"));
+    if !is_form {
+        return (req, None);
+    }
+
+    let (parts, body) = req.into_parts();
+    let Ok(bytes) = axum::body::to_bytes(body, MAX_FORM_BODY).await else {
+        return (Request::from_parts(parts, Body::empty()), None);
+    };
+    let token = form_urlencoded::parse(&bytes)
+        .find(|(key, _)| key == CSRF_FIELD)
+        .map(|(_, token)| token.into_owned());
+    (Request::from_parts(parts, Body::from(bytes)), token)
+}
+
+fn generate_token() -> String {
+    use base64::prelude::{Engine as _, BASE64_URL_SAFE_NO_PAD};
+    use rand::RngCore;
+
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    BASE64_URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Compares without returning early, so timing doesn't tell how much of a guess was right
+fn tokens_match(sent: &str, expected: &str) -> bool {
+    sent.len() == expected.len()
+        && sent
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn forbidden(message: &str) -> Response<Body> {
+    use leptos::server_fn::error::ServerFnErrorSerde;
+
+    let body = leptos::ServerFnError::new(message)
+        .ser()
+        .unwrap_or_default();
+    Response::builder()
+        .status(StatusCode::FORBIDDEN)
+        .body(Body::from(body))
+        .unwrap()
+}
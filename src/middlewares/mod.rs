@@ -1,5 +1,15 @@
 mod auth;
+mod csrf;
 mod macros;
+mod rate_limit;
+mod step_up;
 
-pub use auth::require_login;
+pub use auth::{require_login, require_permission, require_role, Permission};
+pub use csrf::{csrf_protect, CsrfToken};
 pub use macros::MiddlewareLayer;
+pub use rate_limit::{
+    rate_limit, rate_limit_sign_in, AttemptRecord, AttemptStore, AttemptUpdate, MemoryAttemptStore,
+    RateLimitGuard, RateLimitPolicy, RateLimiter, ACCOUNT_DELETION_LIMIT, EMAIL_LINK_LIMIT,
+    MFA_LIMIT, PASSWORD_LIMIT, SIGN_IN_LIMIT,
+};
+pub use step_up::{require_aal2, require_fresh_auth, FRESH_AUTH_MAX_AGE};
@@ -0,0 +1,502 @@
+//! Failed attempt limits per client address and per account. The middleware counts every
+//! attempt as failed before it runs, turning away locked out clients, and the server fn
+//! takes back the attempts that turn out fine through `RateLimitGuard`. Counting up front
+//! keeps parallel guesses from all slipping in before the first failure is recorded.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::async_trait;
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use futures_util::future::BoxFuture;
+use http::{header, Request, Response, StatusCode};
+use time::OffsetDateTime;
+
+use crate::supabase::AuthSession;
+
+/// Form bodies are read for the account, anything bigger isn't a sign in form
+const MAX_FORM_BYTES: usize = 16 * 1024;
+
+#[derive(Debug)]
+pub struct RateLimitPolicy {
+    /// Keeps the counts of different endpoints apart
+    pub scope: &'static str,
+    /// Failures allowed for one account within `window`
+    pub max_failures: usize,
+    /// Failures allowed from one address within `window`, shared by everyone behind a NAT
+    pub max_ip_failures: usize,
+    pub window: Duration,
+    /// First lockout, doubled with every following one
+    pub lockout: Duration,
+    pub max_lockout: Duration,
+}
+
+pub const SIGN_IN_LIMIT: RateLimitPolicy = RateLimitPolicy {
+    scope: "signin",
+    max_failures: 5,
+    max_ip_failures: 50,
+    window: Duration::from_secs(15 * 60),
+    lockout: Duration::from_secs(60),
+    max_lockout: Duration::from_secs(24 * 60 * 60),
+};
+
+pub const MFA_LIMIT: RateLimitPolicy = RateLimitPolicy {
+    scope: "mfa",
+    max_failures: 5,
+    max_ip_failures: 50,
+    window: Duration::from_secs(15 * 60),
+    lockout: Duration::from_secs(60),
+    max_lockout: Duration::from_secs(24 * 60 * 60),
+};
+
+/// Wrong passwords given by a signed in user to change it or to confirm it's them
+pub const PASSWORD_LIMIT: RateLimitPolicy = RateLimitPolicy {
+    scope: "password",
+    max_failures: 5,
+    max_ip_failures: 50,
+    window: Duration::from_secs(15 * 60),
+    lockout: Duration::from_secs(60),
+    max_lockout: Duration::from_secs(24 * 60 * 60),
+};
+
+/// Every email sent counts, not only failures
+pub const EMAIL_LINK_LIMIT: RateLimitPolicy = RateLimitPolicy {
+    scope: "email_link",
+    max_failures: 3,
+    max_ip_failures: 20,
+    window: Duration::from_secs(60 * 60),
+    lockout: Duration::from_secs(60 * 60),
+    max_lockout: Duration::from_secs(24 * 60 * 60),
+};
+
+/// Wrong confirmations of an account deletion, which can't be undone
+pub const ACCOUNT_DELETION_LIMIT: RateLimitPolicy = RateLimitPolicy {
+    scope: "delete_account",
+    max_failures: 3,
+    max_ip_failures: 20,
+    window: Duration::from_secs(60 * 60),
+    lockout: Duration::from_secs(60 * 60),
+    max_lockout: Duration::from_secs(24 * 60 * 60),
+};
+
+/// Recent failures of one key, timestamps in unix seconds
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct AttemptRecord {
+    pub failures: Vec<i64>,
+    /// Lockouts so far, the next one lasts twice as long
+    pub lockouts: u32,
+    pub locked_until: Option<i64>,
+}
+
+/// Changes an [`AttemptRecord`], returning the seconds left on its lockout if there's one
+pub type AttemptUpdate = Box<dyn FnOnce(&mut AttemptRecord) -> Option<i64> + Send>;
+
+/// Where attempt records are kept, in memory unless instances need to share them
+#[async_trait]
+pub trait AttemptStore: Send + Sync {
+    /// Runs `update` on the record of `key`, a default one if there's none, and saves it
+    /// with no other update of `key` in between. `ttl` is how long the record matters,
+    /// stores may drop it afterwards. Returns what `update` returned.
+    async fn update(&self, key: &str, ttl: Duration, update: AttemptUpdate) -> Option<i64>;
+    async fn remove(&self, key: &str);
+}
+
+#[derive(Default)]
+pub struct MemoryAttemptStore {
+    records: Mutex<HashMap<String, (AttemptRecord, i64)>>,
+}
+
+#[async_trait]
+impl AttemptStore for MemoryAttemptStore {
+    async fn update(&self, key: &str, ttl: Duration, update: AttemptUpdate) -> Option<i64> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let mut records = self.records.lock().unwrap();
+        records.retain(|_, (_, expires)| *expires > now);
+
+        let (record, expires) = records.entry(key.to_string()).or_default();
+        let locked_for = update(record);
+        *expires = now + ttl.as_secs() as i64;
+        locked_for
+    }
+
+    async fn remove(&self, key: &str) {
+        self.records.lock().unwrap().remove(key);
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimiter {
+    store: Arc<dyn AttemptStore>,
+    /// Proxies in front of the app appending to `X-Forwarded-For`, the client address is the
+    /// entry that many from the right. None are trusted unless `RATE_LIMIT_TRUST_PROXY` is set.
+    trusted_hops: usize,
+}
+
+impl RateLimiter {
+    pub fn new(store: Arc<dyn AttemptStore>) -> Self {
+        let trust_proxy = std::env::var("RATE_LIMIT_TRUST_PROXY").is_ok_and(|v| v == "true");
+        let trusted_hops = if trust_proxy {
+            std::env::var("RATE_LIMIT_TRUSTED_HOPS")
+                .ok()
+                .and_then(|hops| hops.parse().ok())
+                .unwrap_or(1)
+        } else {
+            0
+        };
+        Self {
+            store,
+            trusted_hops,
+        }
+    }
+
+    /// Address the attempt is counted against. Clients can put anything in
+    /// `X-Forwarded-For`, only the entries appended by the trusted proxies are believed.
+    fn client_address(
+        &self,
+        headers: &http::HeaderMap,
+        peer: Option<SocketAddr>,
+    ) -> Option<String> {
+        let peer = peer.map(|addr| addr.ip().to_string());
+        if self.trusted_hops == 0 {
+            return peer;
+        }
+        let forwarded: Vec<&str> = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect();
+        forwarded
+            .len()
+            .checked_sub(self.trusted_hops)
+            .and_then(|client| forwarded[client].parse::<std::net::IpAddr>().ok())
+            .map(|ip| ip.to_string())
+            .or(peer)
+    }
+
+    /// Counts an attempt for `key` unless it's locked out, locking it out once the attempt
+    /// makes `max_failures`. Returns the seconds left on the lockout when turned away.
+    async fn reserve(
+        &self,
+        policy: &'static RateLimitPolicy,
+        key: &str,
+        max_failures: usize,
+        at: i64,
+    ) -> Result<(), i64> {
+        let owned_key = key.to_string();
+        let update: AttemptUpdate = Box::new(move |record: &mut AttemptRecord| {
+            let now = OffsetDateTime::now_utc().unix_timestamp();
+            if let Some(until) = record.locked_until.filter(|until| *until > now) {
+                return Some(until - now);
+            }
+
+            let window = policy.window.as_secs() as i64;
+            record.failures.retain(|failed_at| now - failed_at < window);
+            record.failures.push(at);
+
+            if record.failures.len() >= max_failures {
+                let lockout = policy
+                    .lockout
+                    .saturating_mul(2u32.saturating_pow(record.lockouts))
+                    .min(policy.max_lockout);
+                record.lockouts += 1;
+                record.locked_until = Some(now + lockout.as_secs() as i64);
+                record.failures.clear();
+
+                tracing::warn!(
+                    target: "audit",
+                    scope = policy.scope,
+                    key = owned_key,
+                    lockouts = record.lockouts,
+                    "Locked out for {}s after {max_failures} failed attempts",
+                    lockout.as_secs()
+                );
+            }
+            None
+        });
+
+        // Lockouts keep counting up until a whole lockout's worth of time passes quietly
+        let ttl = policy.window.max(policy.max_lockout);
+        match self.store.update(key, ttl, update).await {
+            Some(retry_after) => Err(retry_after),
+            None => Ok(()),
+        }
+    }
+
+    /// Takes back the attempt `reserve` counted at `at`
+    async fn release(&self, policy: &RateLimitPolicy, key: &str, at: i64) {
+        let update: AttemptUpdate = Box::new(move |record: &mut AttemptRecord| {
+            if let Some(reserved) = record
+                .failures
+                .iter()
+                .rposition(|failed_at| *failed_at == at)
+            {
+                record.failures.remove(reserved);
+            }
+            None
+        });
+        let ttl = policy.window.max(policy.max_lockout);
+        self.store.update(key, ttl, update).await;
+    }
+}
+
+/// Handed to the server fn behind `rate_limit`. The attempt counts as failed unless the
+/// server fn says otherwise.
+#[derive(Clone)]
+pub struct RateLimitGuard {
+    limiter: RateLimiter,
+    policy: &'static RateLimitPolicy,
+    ip_key: Option<String>,
+    account_key: Option<String>,
+    /// When the attempt was counted, to take it back
+    reserved_at: i64,
+}
+
+impl RateLimitGuard {
+    /// Clears the account's failures, those from the address keep counting but this one
+    pub async fn record_success(&self) {
+        if let Some(key) = &self.ip_key {
+            self.limiter
+                .release(self.policy, key, self.reserved_at)
+                .await;
+        }
+        if let Some(key) = &self.account_key {
+            self.limiter.store.remove(key).await;
+        }
+    }
+
+    /// Takes back the attempt, for when it didn't get to be tried like on server errors
+    pub async fn release(&self) {
+        for key in [&self.ip_key, &self.account_key].into_iter().flatten() {
+            self.limiter
+                .release(self.policy, key, self.reserved_at)
+                .await;
+        }
+    }
+}
+
+/// Middleware turning away clients locked out under `policy`, responding
+/// `429 Too Many Requests` with `Retry-After`. The account is the signed in user or
+/// the `email` field of a form.
+pub fn rate_limit(
+    policy: &'static RateLimitPolicy,
+) -> impl Fn(Request<Body>) -> BoxFuture<'static, Result<Request<Body>, Response<Body>>> {
+    move |req| Box::pin(check_rate_limit(policy, req))
+}
+
+/// [`rate_limit`] under [`SIGN_IN_LIMIT`] for plain axum routes, the account is the
+/// `email` field of a form or JSON body
+pub async fn rate_limit_sign_in(
+    req: Request<Body>,
+    next: axum::middleware::Next,
+) -> Response<Body> {
+    match check_rate_limit(&SIGN_IN_LIMIT, req).await {
+        Ok(req) => next.run(req).await,
+        Err(res) => res,
+    }
+}
+
+async fn check_rate_limit(
+    policy: &'static RateLimitPolicy,
+    req: Request<Body>,
+) -> Result<Request<Body>, Response<Body>> {
+    let Some(limiter) = req.extensions().get::<RateLimiter>().cloned() else {
+        return Err(Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(Body::empty())
+            .unwrap());
+    };
+
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| *addr);
+    let ip_address = limiter.client_address(req.headers(), peer);
+
+    let user_id = req
+        .extensions()
+        .get::<AuthSession>()
+        .and_then(|auth_session| auth_session.user.as_ref())
+        .map(|user| user.identity.user_id.clone());
+    let (mut req, account) = match user_id {
+        Some(user_id) => (req, Some(user_id)),
+        None => body_email(req).await?,
+    };
+
+    let guard = RateLimitGuard {
+        limiter: limiter.clone(),
+        policy,
+        ip_key: ip_address.map(|ip| format!("{}:ip:{ip}", policy.scope)),
+        account_key: account.map(|account| format!("{}:account:{account}", policy.scope)),
+        reserved_at: OffsetDateTime::now_utc().unix_timestamp(),
+    };
+
+    let limits = [
+        (&guard.ip_key, policy.max_ip_failures),
+        (&guard.account_key, policy.max_failures),
+    ];
+    let mut reserved = Vec::new();
+    for (key, max_failures) in limits {
+        let Some(key) = key else {
+            continue;
+        };
+        match limiter
+            .reserve(policy, key, max_failures, guard.reserved_at)
+            .await
+        {
+            Ok(()) => reserved.push(key),
+            Err(retry_after) => {
+                for key in reserved {
+                    limiter.release(policy, key, guard.reserved_at).await;
+                }
+                tracing::warn!(
+                    target: "audit",
+                    scope = policy.scope,
+                    key,
+                    "Rejected attempt during lockout"
+                );
+                return Err(Response::builder()
+                    .status(StatusCode::TOO_MANY_REQUESTS)
+                    .header(header::RETRY_AFTER, retry_after)
+                    .body(Body::from(too_many_attempts()))
+                    .unwrap());
+            }
+        }
+    }
+
+    req.extensions_mut().insert(guard);
+    Ok(req)
+}
+
+/// Serialized the way server fn clients decode their errors
+fn too_many_attempts() -> String {
+    use leptos::server_fn::error::ServerFnErrorSerde;
+
+    leptos::ServerFnError::new("Too many attempts, try again later")
+        .ser()
+        .unwrap_or_default()
+}
+
+/// Reads the `email` field of a urlencoded or JSON body, putting the body back for the
+/// handler
+async fn body_email(req: Request<Body>) -> Result<(Request<Body>, Option<String>), Response<Body>> {
+    let content_type = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let is_form = content_type.starts_with("application/x-www-form-urlencoded");
+    let is_json = content_type.starts_with("application/json");
+    if !is_form && !is_json {
+        return Ok((req, None));
+    }
+
+    let (parts, body) = req.into_parts();
+    let bytes = axum::body::to_bytes(body, MAX_FORM_BYTES)
+        .await
+        .map_err(|_| {
+            Response::builder()
+                .status(StatusCode::PAYLOAD_TOO_LARGE)
+                .body(Body::empty())
+                .unwrap()
+        })?;
+
+    let email = if is_form {
+        form_urlencoded::parse(&bytes)
+            .find(|(key, _)| key == "email")
+            .map(|(_, value)| value.trim().to_lowercase())
+    } else {
+        leptos::serde_json::from_slice::<leptos::serde_json::Value>(&bytes)
+            .ok()
+            .and_then(|body| Some(body.get("email")?.as_str()?.trim().to_lowercase()))
+    };
+    let email = email.filter(|value| !value.is_empty());
+
+    Ok((Request::from_parts(parts, Body::from(bytes)), email))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_LIMIT: RateLimitPolicy = RateLimitPolicy {
+        scope: "test",
+        max_failures: 5,
+        max_ip_failures: 50,
+        window: Duration::from_secs(15 * 60),
+        lockout: Duration::from_secs(60),
+        max_lockout: Duration::from_secs(24 * 60 * 60),
+    };
+
+    fn limiter(trusted_hops: usize) -> RateLimiter {
+        RateLimiter {
+            store: Arc::new(MemoryAttemptStore::default()),
+            trusted_hops,
+        }
+    }
+
+    #[tokio::test]
+    async fn parallel_attempts_cannot_outrun_the_lockout() {
+        let limiter = limiter(0);
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let attempts = (0..20).map(|_| {
+            let limiter = limiter.clone();
+            tokio::spawn(async move {
+                limiter
+                    .reserve(&TEST_LIMIT, "test:account:a", TEST_LIMIT.max_failures, now)
+                    .await
+            })
+        });
+
+        let mut allowed = 0;
+        for attempt in attempts {
+            if attempt.await.unwrap().is_ok() {
+                allowed += 1;
+            }
+        }
+        assert_eq!(allowed, TEST_LIMIT.max_failures);
+    }
+
+    #[tokio::test]
+    async fn released_attempts_are_not_counted() {
+        let limiter = limiter(0);
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        for _ in 0..TEST_LIMIT.max_failures * 2 {
+            let key = "test:ip:10.0.0.1";
+            limiter
+                .reserve(&TEST_LIMIT, key, TEST_LIMIT.max_failures, now)
+                .await
+                .unwrap();
+            limiter.release(&TEST_LIMIT, key, now).await;
+        }
+    }
+
+    #[test]
+    fn client_address_ignores_client_supplied_forwarding() {
+        let peer = Some(SocketAddr::from(([10, 0, 0, 2], 443)));
+        let mut headers = http::HeaderMap::new();
+        headers.insert("x-forwarded-for", "1.2.3.4, 203.0.113.7".parse().unwrap());
+
+        assert_eq!(
+            limiter(0).client_address(&headers, peer).as_deref(),
+            Some("10.0.0.2")
+        );
+        assert_eq!(
+            limiter(1).client_address(&headers, peer).as_deref(),
+            Some("203.0.113.7")
+        );
+        assert_eq!(
+            limiter(2).client_address(&headers, peer).as_deref(),
+            Some("1.2.3.4")
+        );
+        // Fewer entries than proxies, the header didn't come through all of them
+        assert_eq!(
+            limiter(3).client_address(&headers, peer).as_deref(),
+            Some("10.0.0.2")
+        );
+    }
+}
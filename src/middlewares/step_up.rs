@@ -0,0 +1,82 @@
+//! Step-up for sensitive server fns. Calls from islands get a step-up error to act on,
+//! plain form posts and links are sent to verify straight away.
+
+use std::time::Duration;
+
+use axum::body::Body;
+use futures_util::future::BoxFuture;
+use http::{header, Request, Response, StatusCode};
+use time::OffsetDateTime;
+
+use super::auth::{accepts_html, referer_path};
+use super::require_login;
+use crate::components::auth::StepUp;
+use crate::supabase::{AuthSession, IdentityData};
+
+/// How recently the user has to have proved who they are for access tokens and deleting
+/// the account
+pub const FRESH_AUTH_MAX_AGE: Duration = Duration::from_secs(15 * 60);
+
+/// Middleware requiring users with a second factor to have verified it in this session,
+/// a trusted browser isn't enough
+pub async fn require_aal2(req: Request<Body>) -> Result<Request<Body>, Response<Body>> {
+    let req = require_login(req).await?;
+
+    if identity(&req).needs_second_factor() {
+        return Err(step_up(&req));
+    }
+    Ok(req)
+}
+
+/// Middleware requiring the user to have signed in or verified again within `max_age`
+pub fn require_fresh_auth(
+    max_age: Duration,
+) -> impl Fn(Request<Body>) -> BoxFuture<'static, Result<Request<Body>, Response<Body>>> {
+    move |req| Box::pin(check_fresh_auth(max_age, req))
+}
+
+async fn check_fresh_auth(
+    max_age: Duration,
+    req: Request<Body>,
+) -> Result<Request<Body>, Response<Body>> {
+    let req = require_login(req).await?;
+
+    let fresh = identity(&req)
+        .verified_at
+        .is_some_and(|verified_at| OffsetDateTime::now_utc() - verified_at <= max_age);
+    if !fresh {
+        return Err(step_up(&req));
+    }
+    Ok(req)
+}
+
+/// Only called past `require_login`
+fn identity(req: &Request<Body>) -> &IdentityData {
+    let auth_session = req.extensions().get::<AuthSession>().unwrap();
+    &auth_session.user.as_ref().unwrap().identity
+}
+
+/// Sends the user to verify, coming back to the page the request was made from
+fn step_up(req: &Request<Body>) -> Response<Body> {
+    use leptos::server_fn::error::ServerFnErrorSerde;
+
+    let callback = referer_path(req).unwrap_or("/user".to_string());
+    let step_up = StepUp::new(&callback);
+
+    // Without JavaScript there's no island to handle the error
+    if accepts_html(req) {
+        return Response::builder()
+            .status(StatusCode::SEE_OTHER)
+            .header(header::LOCATION, step_up.location)
+            .body(Body::empty())
+            .unwrap();
+    }
+
+    let body = leptos::ServerFnError::from(step_up)
+        .ser()
+        .unwrap_or_default();
+    Response::builder()
+        .status(StatusCode::FORBIDDEN)
+        .body(Body::from(body))
+        .unwrap()
+}
@@ -0,0 +1,216 @@
+use axum::async_trait;
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use super::{Notifier, NotifyError, Reminder};
+
+pub struct EmailNotifier {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: String,
+}
+
+impl EmailNotifier {
+    pub fn new(
+        host: &str,
+        port: u16,
+        tls: bool,
+        credentials: Option<(String, String)>,
+        from: String,
+    ) -> Result<Self, NotifyError> {
+        let mut builder = if tls {
+            AsyncSmtpTransport::<Tokio1Executor>::relay(host)
+                .map_err(|e| NotifyError::Smtp(e.to_string()))?
+        } else {
+            // Plain connection, meant for local stand-ins like MailHog or Mailpit
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)
+        };
+        builder = builder.port(port);
+        if let Some((username, password)) = credentials {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from,
+        })
+    }
+
+    pub fn from_env() -> Option<Self> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /// Reads the `SMTP_*` settings through `var`, `None` when email isn't configured
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Option<Self> {
+        let host = var("SMTP_HOST").filter(|h| !h.is_empty())?;
+        let tls = var("SMTP_TLS").map_or(true, |v| v != "false");
+        let port = var("SMTP_PORT")
+            .and_then(|p| p.parse().ok())
+            .unwrap_or(if tls { 465 } else { 1025 });
+        let credentials = var("SMTP_USERNAME")
+            .filter(|u| !u.is_empty())
+            .zip(var("SMTP_PASSWORD"));
+        let Some(from) = var("SMTP_FROM").filter(|f| !f.is_empty()) else {
+            tracing::warn!("SMTP_HOST is set without SMTP_FROM, reminders won't be emailed");
+            return None;
+        };
+
+        match Self::new(&host, port, tls, credentials, from) {
+            Ok(notifier) => Some(notifier),
+            Err(e) => {
+                tracing::error!("EmailNotifier setup err: {e:?}");
+                None
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for EmailNotifier {
+    fn name(&self) -> &'static str {
+        "email"
+    }
+
+    async fn notify(&self, reminder: &Reminder) -> Result<(), NotifyError> {
+        let message = Message::builder()
+            .from(
+                self.from
+                    .parse()
+                    .map_err(|_| NotifyError::Address(self.from.clone()))?,
+            )
+            .to(reminder
+                .email
+                .parse()
+                .map_err(|_| NotifyError::Address(reminder.email.clone()))?)
+            .subject(format!("Reminder: {}", reminder.title))
+            .header(ContentType::TEXT_PLAIN)
+            .body(format!(
+                "{}\n\n{}\n\nDue at {}",
+                reminder.title, reminder.description, reminder.remind_at
+            ))
+            .map_err(|e| NotifyError::Smtp(e.to_string()))?;
+
+        self.transport
+            .send(message)
+            .await
+            .map_err(|e| NotifyError::Smtp(e.to_string()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    use super::*;
+
+    fn vars<'a>(set: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        |name| {
+            set.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    /// Accepts one SMTP conversation and hands back the message it was given
+    async fn smtp_stand_in() -> (u16, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let conversation = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (read, mut write) = stream.into_split();
+            let mut lines = BufReader::new(read).lines();
+            write.write_all(b"220 stand-in ESMTP\r\n").await.unwrap();
+
+            let mut message = String::new();
+            let mut in_data = false;
+            while let Ok(Some(line)) = lines.next_line().await {
+                if in_data {
+                    if line == "." {
+                        in_data = false;
+                        write.write_all(b"250 Queued\r\n").await.unwrap();
+                    } else {
+                        message.push_str(&line);
+                        message.push('\n');
+                    }
+                    continue;
+                }
+                let command = line.get(..4).unwrap_or_default().to_ascii_uppercase();
+                let reply: &[u8] = match command.as_str() {
+                    "DATA" => {
+                        in_data = true;
+                        b"354 End data with <CR><LF>.<CR><LF>\r\n"
+                    }
+                    "QUIT" => {
+                        write.write_all(b"221 Bye\r\n").await.unwrap();
+                        break;
+                    }
+                    _ => b"250 OK\r\n",
+                };
+                write.write_all(reply).await.unwrap();
+            }
+            message
+        });
+        (port, conversation)
+    }
+
+    #[test]
+    fn from_vars_needs_a_host() {
+        assert!(EmailNotifier::from_vars(vars(&[("SMTP_FROM", "todo@localhost")])).is_none());
+    }
+
+    #[test]
+    fn from_vars_without_from_is_disabled() {
+        assert!(EmailNotifier::from_vars(vars(&[("SMTP_HOST", "localhost")])).is_none());
+        assert!(
+            EmailNotifier::from_vars(vars(&[("SMTP_HOST", "localhost"), ("SMTP_FROM", "")]))
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn notify_delivers_to_a_local_stand_in() {
+        let (port, conversation) = smtp_stand_in().await;
+        let port = port.to_string();
+        let notifier = EmailNotifier::from_vars(vars(&[
+            ("SMTP_HOST", "127.0.0.1"),
+            ("SMTP_PORT", &port),
+            ("SMTP_TLS", "false"),
+            ("SMTP_FROM", "todo@localhost"),
+        ]))
+        .unwrap();
+
+        let reminder = Reminder {
+            task_id: 7,
+            email: "someone@localhost".to_string(),
+            title: "Water the plants".to_string(),
+            description: "The ones on the balcony".to_string(),
+            remind_at: time::macros::datetime!(2026-10-19 08:00 UTC),
+        };
+        notifier.notify(&reminder).await.unwrap();
+
+        let message = conversation.await.unwrap();
+        assert!(message.contains("Subject: Reminder: Water the plants"));
+        assert!(message.contains("To: someone@localhost"));
+        assert!(message.contains("The ones on the balcony"));
+    }
+
+    #[tokio::test]
+    async fn notify_rejects_an_invalid_address() {
+        let notifier =
+            EmailNotifier::new("127.0.0.1", 1, false, None, "todo@localhost".into()).unwrap();
+        let reminder = Reminder {
+            task_id: 7,
+            email: "not an address".to_string(),
+            title: String::new(),
+            description: String::new(),
+            remind_at: time::OffsetDateTime::now_utc(),
+        };
+        assert!(matches!(
+            notifier.notify(&reminder).await,
+            Err(NotifyError::Address(_))
+        ));
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use axum::async_trait;
+
+use super::{Notifier, NotifyError, Reminder};
+
+/// Reminders `LogNotifier` keeps, the older ones are only in the logs
+const MAX_KEPT: usize = 100;
+
+/// Logs reminders instead of sending them and keeps the latest it was given, a stand-in
+/// for running locally without SMTP or a webhook receiver
+#[derive(Debug, Default)]
+pub struct LogNotifier {
+    delivered: Mutex<VecDeque<Reminder>>,
+}
+
+impl LogNotifier {
+    pub fn from_env() -> Option<Self> {
+        std::env::var("REMINDER_LOG_NOTIFIER")
+            .is_ok_and(|enabled| enabled == "true")
+            .then(Self::default)
+    }
+
+    /// The last reminders delivered, oldest first
+    pub fn delivered(&self) -> Vec<Reminder> {
+        self.delivered.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+#[async_trait]
+impl Notifier for LogNotifier {
+    fn name(&self) -> &'static str {
+        "log"
+    }
+
+    async fn notify(&self, reminder: &Reminder) -> Result<(), NotifyError> {
+        tracing::info!(
+            task_id = reminder.task_id,
+            email = %reminder.email,
+            remind_at = %reminder.remind_at,
+            "Reminder: {}",
+            reminder.title
+        );
+        let mut delivered = self.delivered.lock().unwrap();
+        if delivered.len() == MAX_KEPT {
+            delivered.pop_front();
+        }
+        delivered.push_back(reminder.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reminder(task_id: u32) -> Reminder {
+        Reminder {
+            task_id,
+            email: "someone@localhost".to_string(),
+            title: format!("Task {task_id}"),
+            description: String::new(),
+            remind_at: time::OffsetDateTime::now_utc(),
+        }
+    }
+
+    #[tokio::test]
+    async fn notify_keeps_the_reminders() {
+        let notifier = LogNotifier::default();
+        for task_id in [1, 2] {
+            notifier.notify(&reminder(task_id)).await.unwrap();
+        }
+
+        let delivered = notifier.delivered();
+        assert_eq!(delivered.len(), 2);
+        assert_eq!(delivered[0].title, "Task 1");
+        assert_eq!(delivered[1].task_id, 2);
+    }
+
+    #[tokio::test]
+    async fn notify_keeps_only_the_latest() {
+        let notifier = LogNotifier::default();
+        for task_id in 0..MAX_KEPT as u32 + 5 {
+            notifier.notify(&reminder(task_id)).await.unwrap();
+        }
+
+        let delivered = notifier.delivered();
+        assert_eq!(delivered.len(), MAX_KEPT);
+        assert_eq!(delivered[0].task_id, 5);
+        assert_eq!(delivered[MAX_KEPT - 1].task_id, MAX_KEPT as u32 + 4);
+    }
+}
@@ -0,0 +1,61 @@
+mod email;
+mod log;
+mod webhook;
+
+use std::sync::Arc;
+
+use axum::async_trait;
+use time::OffsetDateTime;
+
+pub use email::EmailNotifier;
+pub use log::LogNotifier;
+pub use webhook::WebhookNotifier;
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct Reminder {
+    pub task_id: u32,
+    /// Account email of the task's author
+    pub email: String,
+    pub title: String,
+    pub description: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub remind_at: OffsetDateTime,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum NotifyError {
+    #[error("Invalid address: {0}")]
+    Address(String),
+    #[error("Email delivery failed: {0}")]
+    Smtp(String),
+    #[error("Webhook delivery failed: {0}")]
+    Webhook(String),
+}
+
+/// A channel a due [`Reminder`] can be delivered through
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn notify(&self, reminder: &Reminder) -> Result<(), NotifyError>;
+}
+
+/// Builds every notifier that has its env vars set.
+/// See `.env.example` for the `SMTP_*`, `REMINDER_WEBHOOK_URL` and `REMINDER_LOG_NOTIFIER`
+/// variables.
+pub fn from_env() -> Vec<Arc<dyn Notifier>> {
+    let mut notifiers: Vec<Arc<dyn Notifier>> = vec![];
+    if let Some(email) = EmailNotifier::from_env() {
+        notifiers.push(Arc::new(email));
+    }
+    if let Some(webhook) = WebhookNotifier::from_env() {
+        notifiers.push(Arc::new(webhook));
+    }
+    if let Some(log) = LogNotifier::from_env() {
+        notifiers.push(Arc::new(log));
+    }
+    if notifiers.is_empty() {
+        tracing::warn!("No reminder notifiers configured, due reminders won't be delivered");
+    }
+    notifiers
+}
@@ -0,0 +1,102 @@
+use axum::async_trait;
+
+use super::{Notifier, NotifyError, Reminder};
+
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+        }
+    }
+
+    pub fn from_env() -> Option<Self> {
+        std::env::var("REMINDER_WEBHOOK_URL")
+            .ok()
+            .filter(|url| !url.is_empty())
+            .map(Self::new)
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    async fn notify(&self, reminder: &Reminder) -> Result<(), NotifyError> {
+        self.client
+            .post(&self.url)
+            .json(reminder)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(|e| NotifyError::Webhook(e.to_string()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::http::StatusCode;
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use leptos::serde_json::Value;
+
+    use super::*;
+
+    /// Serves `status` on `/hook` and keeps the bodies it's sent
+    async fn http_stand_in(status: StatusCode) -> (String, Arc<Mutex<Vec<Value>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let kept = Arc::clone(&received);
+        let router = Router::new().route(
+            "/hook",
+            post(move |Json(body): Json<Value>| async move {
+                kept.lock().unwrap().push(body);
+                status
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        (url, received)
+    }
+
+    fn reminder() -> Reminder {
+        Reminder {
+            task_id: 7,
+            email: "someone@localhost".to_string(),
+            title: "Water the plants".to_string(),
+            description: String::new(),
+            remind_at: time::macros::datetime!(2026-10-19 08:00 UTC),
+        }
+    }
+
+    #[tokio::test]
+    async fn notify_posts_the_reminder() {
+        let (url, received) = http_stand_in(StatusCode::NO_CONTENT).await;
+        WebhookNotifier::new(url).notify(&reminder()).await.unwrap();
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0]["task_id"], 7);
+        assert_eq!(received[0]["title"], "Water the plants");
+        assert_eq!(received[0]["remind_at"], "2026-10-19T08:00:00Z");
+    }
+
+    #[tokio::test]
+    async fn notify_fails_on_an_error_status() {
+        let (url, _) = http_stand_in(StatusCode::INTERNAL_SERVER_ERROR).await;
+        assert!(matches!(
+            WebhookNotifier::new(url).notify(&reminder()).await,
+            Err(NotifyError::Webhook(_))
+        ));
+    }
+}
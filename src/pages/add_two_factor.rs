@@ -2,7 +2,8 @@ use leptos::*;
 use leptos_meta::Title;
 use leptos_router::ActionForm;
 
-use crate::components::auth::{AuthProvider, MFAEnrollConfirm};
+use crate::components::auth::{AuthProvider, MFAEnrollConfirm, RecoveryCodeList};
+use crate::components::csrf::CsrfField;
 
 #[component]
 pub fn AddNewAuthenticator() -> impl IntoView {
@@ -57,15 +58,38 @@ fn SetupAuthenticator(factor_id: String, qr_code: String, secret: String) -> imp
         verification_code.set(String::default());
     };
 
+    // Only set when this was the first factor, the codes are shown before moving on
+    let recovery_codes = move || {
+        mfa_verify_new_action
+            .value()
+            .get()
+            .and_then(Result::ok)
+            .flatten()
+    };
+
     view! {
-      <ActionForm action=mfa_verify_new_action on:submit=on_submit>
-        <NewAuthenticatorFields
-          name=authenticator_name
-          code=verification_code
-          qr_code=qr_code
-          secret=secret
-        />
-      </ActionForm>
+      {move || {
+          recovery_codes()
+              .map(|codes| {
+                  view! {
+                    <div class="uk-flex uk-flex-center uk-margin-top">
+                      <RecoveryCodeList codes=codes/>
+                    </div>
+                  }
+              })
+      }}
+
+      <div class:uk-hidden=move || recovery_codes().is_some()>
+        <ActionForm action=mfa_verify_new_action on:submit=on_submit>
+          <CsrfField/>
+          <NewAuthenticatorFields
+            name=authenticator_name
+            code=verification_code
+            qr_code=qr_code
+            secret=secret
+          />
+        </ActionForm>
+      </div>
     }
 }
 
@@ -169,7 +193,7 @@ mod ssr {
 #[cfg(feature = "ssr")]
 pub use ssr::*;
 
-#[server(name = MFAEnroll, prefix = "/auth", endpoint = "enroll")]
+#[server(name = MFAEnroll, prefix = "/auth", endpoint = "enroll", client = crate::components::csrf::CsrfClient)]
 async fn enroll_new() -> Result<MFAEnrollConfirm, ServerFnError> {
     use crate::supabase::{AuthSession, Supabase};
     use axum::Extension;
@@ -208,13 +232,14 @@ async fn enroll_new() -> Result<MFAEnrollConfirm, ServerFnError> {
     })
 }
 
-#[server(name = MFAuthenticationAdd, prefix = "/auth", endpoint = "add2fa")]
+/// Returns the user's recovery codes when they didn't have any yet
+#[server(name = MFAuthenticationAdd, prefix = "/auth", endpoint = "add2fa", client = crate::components::csrf::CsrfClient)]
 #[middleware(compose_from_fn!(require_login))]
 async fn add_authenticator(
     factor_id: String,
     mut code: String,
     authenticator_name: String,
-) -> Result<(), ServerFnError> {
+) -> Result<Option<Vec<String>>, ServerFnError> {
     use crate::supabase::{AuthSession, Supabase};
     use axum::Extension;
     use axum_extra::extract::CookieJar;
@@ -257,6 +282,19 @@ async fn add_authenticator(
     supabase
         .update_assurance_level(session_id, user_identity.clone(), access_token)
         .await;
+
+    let remaining_codes = supabase
+        .count_recovery_codes(&user_identity.user_id)
+        .await
+        .map_err(crate::supabase::map_err)?;
+    if remaining_codes == 0 {
+        let codes = supabase
+            .generate_recovery_codes(&user_identity.user_id)
+            .await
+            .map_err(crate::supabase::map_err)?;
+        return Ok(Some(codes));
+    }
+
     leptos_axum::redirect("/user");
-    Ok(())
+    Ok(None)
 }
@@ -0,0 +1,79 @@
+use leptos::*;
+
+use super::{AuditEntry, AuditEntryRow};
+
+/// Entries shown on the audit log page, the newest first
+const AUDIT_LOG_LIMIT: usize = 200;
+
+#[component]
+pub fn AdminAuditLog() -> impl IntoView {
+    let entries = create_resource(|| (), |_| list_audit_log());
+
+    view! {
+      <h4 class="uk-heading-line uk-text-center">
+        <span>Audit Log</span>
+      </h4>
+
+      <Transition fallback=|| {
+          view! { <div uk-spinner="ratio: 2"></div> }
+      }>
+        <table class="uk-table uk-table-divider uk-table-small uk-text-left">
+          <thead>
+            <tr>
+              <th>"When"</th>
+              <th>"Action"</th>
+              <th>"User"</th>
+              <th>"By"</th>
+              <th>"Details"</th>
+            </tr>
+          </thead>
+          <tbody>
+            {move || {
+                entries()
+                    .map(|entries| match entries {
+                        Ok(entries) => {
+                            entries
+                                .into_iter()
+                                .map(|entry| view! { <AuditEntryRow entry=entry/> })
+                                .collect_view()
+                        }
+                        Err(e) => {
+                            view! {
+                              <tr>
+                                <td colspan="5" class="uk-text-danger">
+                                  {e.to_string()}
+                                </td>
+                              </tr>
+                            }
+                                .into_view()
+                        }
+                    })
+            }}
+
+          </tbody>
+        </table>
+      </Transition>
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[path = ""]
+mod ssr {
+    pub use crate::compose_from_fn;
+    pub use crate::middlewares::{require_permission, Permission};
+}
+#[cfg(feature = "ssr")]
+pub use ssr::*;
+
+#[server(name = ListAuditLog, prefix = "/auth", endpoint = "admin_audit_log", input = server_fn::codec::GetUrl, client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_permission(Permission::ViewAuditLog)))]
+async fn list_audit_log() -> Result<Vec<AuditEntry>, ServerFnError> {
+    use crate::supabase::Supabase;
+
+    let supabase = expect_context::<Supabase>();
+    let entries = supabase
+        .list_admin_actions(None, AUDIT_LOG_LIMIT)
+        .await
+        .map_err(crate::supabase::map_err)?;
+    Ok(entries.into_iter().map(AuditEntry::from).collect())
+}
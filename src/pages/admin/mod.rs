@@ -0,0 +1,92 @@
+mod audit_log;
+mod user_detail;
+mod users;
+
+use leptos::*;
+use leptos_meta::Title;
+use leptos_router::Outlet;
+
+use crate::components::auth::AuthProvider;
+pub use audit_log::AdminAuditLog;
+pub use user_detail::AdminUserDetail;
+pub use users::AdminUsers;
+
+/// An operator action, as listed in the audit log
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct AuditEntry {
+    pub id: u64,
+    pub admin_id: String,
+    pub action: String,
+    pub target_user_id: String,
+    pub details: String,
+    pub created_at: String,
+}
+
+#[cfg(feature = "ssr")]
+impl From<crate::supabase::AdminAuditSchema> for AuditEntry {
+    fn from(entry: crate::supabase::AdminAuditSchema) -> Self {
+        Self {
+            id: entry.id.unwrap_or_default(),
+            admin_id: entry.admin_id.unwrap_or_default(),
+            action: entry.action.unwrap_or_default(),
+            target_user_id: entry.target_user_id.unwrap_or_default(),
+            details: entry
+                .details
+                .filter(|details| !details.is_null())
+                .map(|details| details.to_string())
+                .unwrap_or_default(),
+            created_at: entry.created_at.unwrap_or_default(),
+        }
+    }
+}
+
+/// Layout of the `/admin` routes, sending away anyone without the admin role.
+/// The server fns behind it check for themselves.
+#[component]
+pub fn AdminPage() -> impl IntoView {
+    #[cfg(feature = "ssr")]
+    {
+        use crate::middlewares::Permission;
+        use crate::supabase::AuthSession;
+
+        let allowed = use_context::<AuthSession>()
+            .and_then(|auth_session| auth_session.user)
+            .is_some_and(|user| Permission::ListUsers.granted_to(&user.identity));
+        if !allowed {
+            leptos_axum::redirect("/");
+        }
+    }
+
+    view! {
+      <Title text="Admin - Supabase Leptos"/>
+      <AuthProvider>
+        <hr class="uk-divider-small"/>
+        <div class="uk-container uk-margin-top">
+          <ul class="uk-subnav uk-subnav-divider uk-flex-center">
+            <li>
+              <a href="/admin">"Users"</a>
+            </li>
+            <li>
+              <a href="/admin/audit">"Audit Log"</a>
+            </li>
+          </ul>
+          <Outlet/>
+        </div>
+      </AuthProvider>
+    }
+}
+
+#[component]
+fn AuditEntryRow(entry: AuditEntry) -> impl IntoView {
+    view! {
+      <tr>
+        <td class="uk-text-nowrap">{entry.created_at}</td>
+        <td>{entry.action}</td>
+        <td>
+          <a href=format!("/admin/users/{}", entry.target_user_id)>{entry.target_user_id}</a>
+        </td>
+        <td>{entry.admin_id}</td>
+        <td class="uk-text-small">{entry.details}</td>
+      </tr>
+    }
+}
@@ -0,0 +1,377 @@
+use leptos::*;
+use leptos_router::use_params_map;
+
+use super::users::AdminUserRow;
+use super::{AuditEntry, AuditEntryRow};
+use crate::components::auth::follow_step_up;
+use crate::pages::user_settings::describe_user_agent;
+
+/// Audit entries shown with a user
+const USER_AUDIT_LIMIT: usize = 20;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct AdminSession {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: Option<String>,
+    pub refreshed_at: Option<String>,
+    pub expiry_date: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct AdminUserInfo {
+    pub user: AdminUserRow,
+    /// Names of the verified authenticators
+    pub factors: Vec<String>,
+    pub passkeys: usize,
+    pub sessions: Vec<AdminSession>,
+    pub audit: Vec<AuditEntry>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum AdminAction {
+    ForceLogout,
+    ResetMfa,
+    Disable,
+    Enable,
+}
+
+impl AdminAction {
+    fn label(self) -> &'static str {
+        match self {
+            Self::ForceLogout => "Sign out everywhere",
+            Self::ResetMfa => "Reset 2FA",
+            Self::Disable => "Disable account",
+            Self::Enable => "Enable account",
+        }
+    }
+
+    fn confirmation(self) -> &'static str {
+        match self {
+            Self::ForceLogout => "End every session of this user?",
+            Self::ResetMfa => {
+                "Remove all authenticators, passkeys, recovery codes and trusted browsers?"
+            }
+            Self::Disable => "Block this user from signing in?",
+            Self::Enable => "Let this user sign in again?",
+        }
+    }
+}
+
+#[component]
+pub fn AdminUserDetail() -> impl IntoView {
+    let params = use_params_map();
+    let user_id = move || params.with(|params| params.get("id").cloned().unwrap_or_default());
+    let info = create_resource(user_id, get_user_info);
+
+    view! {
+      <Transition fallback=|| {
+          view! { <div uk-spinner="ratio: 2"></div> }
+      }>
+        {move || {
+            info()
+                .map(|info| match info {
+                    Ok(info) => view! { <AdminUserPanel info=info/> }.into_view(),
+                    Err(e) => view! { <p class="uk-text-danger">{e.to_string()}</p> }.into_view(),
+                })
+        }}
+
+      </Transition>
+    }
+}
+
+#[component]
+fn AdminUserPanel(info: AdminUserInfo) -> impl IntoView {
+    let user = info.user;
+    let status_action = if user.disabled {
+        AdminAction::Enable
+    } else {
+        AdminAction::Disable
+    };
+
+    view! {
+      <h4 class="uk-heading-line uk-text-center">
+        <span>{user.email}</span>
+      </h4>
+
+      <section class="uk-text-left">
+        <p class="uk-text-meta">
+          {user.id.clone()} " · role " {user.role.unwrap_or("none".to_string())} " · created "
+          {user.created_at.unwrap_or_default()} " · last sign in "
+          {user.last_sign_in_at.unwrap_or("never".to_string())}
+          {user
+              .disabled
+              .then(|| {
+                  view! {
+                    <span class="uk-label uk-label-danger uk-margin-small-left">"Disabled"</span>
+                  }
+              })}
+
+        </p>
+        <div class="uk-flex uk-flex-wrap uk-margin-bottom">
+          <AdminActionButton user_id=user.id.clone() action=AdminAction::ForceLogout/>
+          <AdminActionButton user_id=user.id.clone() action=AdminAction::ResetMfa/>
+          <AdminActionButton user_id=user.id.clone() action=status_action/>
+        </div>
+
+        <h5>"Second factors"</h5>
+        <ul class="uk-list uk-list-bullet">
+          {info
+              .factors
+              .into_iter()
+              .map(|name| view! { <li>{name}</li> })
+              .collect_view()}
+          <li>{info.passkeys} " passkey(s)"</li>
+        </ul>
+
+        <h5>"Active sessions"</h5>
+        <ul class="uk-list uk-list-divider">
+          {info
+              .sessions
+              .into_iter()
+              .map(|session| {
+                  view! {
+                    <li>
+                      <span class="uk-text-bold">
+                        {describe_user_agent(session.user_agent.as_deref())}
+                      </span>
+                      <p class="uk-text-meta uk-margin-remove">
+                        {session.ip_address.unwrap_or("Unknown address".to_string())}
+                        " · signed in " {session.created_at.unwrap_or_default()}
+                        " · last active " {session.refreshed_at.unwrap_or("never".to_string())}
+                        " · expires " {session.expiry_date.unwrap_or_default()}
+                      </p>
+                    </li>
+                  }
+              })
+              .collect_view()}
+        </ul>
+
+        <h5>"Audit log"</h5>
+        <table class="uk-table uk-table-divider uk-table-small">
+          <tbody>
+            {info
+                .audit
+                .into_iter()
+                .map(|entry| view! { <AuditEntryRow entry=entry/> })
+                .collect_view()}
+          </tbody>
+        </table>
+      </section>
+    }
+}
+
+#[island]
+fn AdminActionButton(user_id: String, action: AdminAction) -> impl IntoView {
+    let prefers_dark = RwSignal::new(crate::PrefersDark::check());
+    let admin_action = create_action(|(user_id, action): &(String, AdminAction)| {
+        let user_id = user_id.clone();
+        let action = *action;
+        async move {
+            match action {
+                AdminAction::ForceLogout => admin_force_logout(user_id).await,
+                AdminAction::ResetMfa => admin_reset_mfa(user_id).await,
+                AdminAction::Disable => admin_set_disabled(user_id, true).await,
+                AdminAction::Enable => admin_set_disabled(user_id, false).await,
+            }
+        }
+    });
+    follow_step_up(admin_action);
+    let modal_id = format!("confirm-admin-{action:?}");
+    let button_class = match action {
+        AdminAction::Disable | AdminAction::ResetMfa => "uk-button-danger",
+        _ => "uk-button-default",
+    };
+
+    view! {
+      {move || match admin_action.value().get() {
+          Some(Ok(())) => {
+              window().location().reload().unwrap();
+              ().into_view()
+          }
+          Some(Err(e)) => view! { <p class="uk-text-danger">{e.to_string()}</p> }.into_view(),
+          None => ().into_view(),
+      }}
+
+      <div id=&modal_id class="uk-flex-top" uk-modal>
+        <div class=move || {
+            format!(
+                "uk-modal-dialog uk-modal-body uk-margin-auto-vertical uk-background-{0} uk-{1} bg-toggle",
+                if prefers_dark() { "secondary" } else { "default" },
+                if prefers_dark() { "light" } else { "dark" },
+            )
+        }>
+          <h4 class="uk-modal-title uk-text-center">{action.confirmation()}</h4>
+          <p class="uk-text-center">
+            <button class="uk-button uk-button-default uk-modal-close" type="button">
+              "Cancel"
+            </button>
+            <button
+              type="button"
+              class=format!("uk-button {button_class} uk-modal-close")
+              on:click=move |_| admin_action.dispatch((user_id.clone(), action))
+            >
+              {action.label()}
+            </button>
+          </p>
+        </div>
+      </div>
+
+      <button
+        uk-toggle=format!("target: #{modal_id}")
+        class=format!("uk-button uk-button-small {button_class} uk-margin-small-right")
+      >
+        {action.label()}
+      </button>
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[path = ""]
+mod ssr {
+    pub use crate::compose_from_fn;
+    pub use crate::middlewares::{require_aal2, require_permission, Permission};
+
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+    use leptos::serde_json::Value;
+    use leptos::ServerFnError;
+
+    /// Writes `action` on `user_id` to the audit table, signed by the admin making the request
+    pub async fn audit(action: &str, user_id: &str, details: Value) -> Result<(), ServerFnError> {
+        let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+        let supabase = leptos::expect_context::<Supabase>();
+        let admin_id = auth_session.user.unwrap().identity.user_id;
+
+        tracing::info!(target: "audit", %admin_id, user_id, %details, "Admin {action}");
+        supabase
+            .record_admin_action(&admin_id, action, user_id, details)
+            .await
+            .map_err(crate::supabase::map_err)
+    }
+}
+#[cfg(feature = "ssr")]
+pub use ssr::*;
+
+#[server(name = GetUserInfo, prefix = "/auth", endpoint = "admin_user", input = server_fn::codec::GetUrl, client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_permission(Permission::ListUsers)))]
+async fn get_user_info(user_id: String) -> Result<AdminUserInfo, ServerFnError> {
+    use crate::supabase::Supabase;
+
+    let supabase = expect_context::<Supabase>();
+    let user = supabase
+        .admin_get_user(&user_id)
+        .await
+        .map_err(crate::supabase::map_err)?;
+    let factors = supabase
+        .admin_list_factors(&user_id)
+        .await
+        .map_err(crate::supabase::map_err)?;
+    let passkeys = supabase
+        .list_passkeys(&user_id)
+        .await
+        .map_err(crate::supabase::map_err)?;
+    let sessions = supabase
+        .list_user_sessions(&user_id)
+        .await
+        .map_err(crate::supabase::map_err)?;
+    let audit = supabase
+        .list_admin_actions(Some(&user_id), USER_AUDIT_LIMIT)
+        .await
+        .map_err(crate::supabase::map_err)?;
+
+    Ok(AdminUserInfo {
+        user: user.into(),
+        factors,
+        passkeys: passkeys.len(),
+        sessions: sessions
+            .into_iter()
+            .map(|s| AdminSession {
+                user_agent: s.user_agent,
+                ip_address: s.ip_address,
+                created_at: s.created_at,
+                refreshed_at: s.refreshed_at,
+                expiry_date: s.expiry_date,
+            })
+            .collect(),
+        audit: audit.into_iter().map(AuditEntry::from).collect(),
+    })
+}
+
+#[server(name = AdminForceLogout, prefix = "/auth", endpoint = "admin_force_logout", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_permission(Permission::ManageUsers), require_aal2))]
+async fn admin_force_logout(user_id: String) -> Result<(), ServerFnError> {
+    use crate::supabase::Supabase;
+
+    // Nothing is revoked or audited for an id that isn't a user UUID
+    crate::supabase::user_uuid(&user_id).map_err(crate::supabase::map_err)?;
+    let supabase = expect_context::<Supabase>();
+    let revoked = supabase
+        .revoke_user_sessions(&user_id, None, None)
+        .await
+        .map_err(crate::supabase::map_err)?;
+
+    audit(
+        "force_logout",
+        &user_id,
+        serde_json::json!({ "sessions": revoked }),
+    )
+    .await
+}
+
+/// For users locked out of their second factor, their sessions end along with it
+#[server(name = AdminResetMfa, prefix = "/auth", endpoint = "admin_reset_mfa", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_permission(Permission::ManageUsers), require_aal2))]
+async fn admin_reset_mfa(user_id: String) -> Result<(), ServerFnError> {
+    use crate::supabase::Supabase;
+
+    let supabase = expect_context::<Supabase>();
+    supabase
+        .admin_reset_mfa(&user_id)
+        .await
+        .map_err(crate::supabase::map_err)?;
+    let revoked = supabase
+        .revoke_user_sessions(&user_id, None, None)
+        .await
+        .map_err(crate::supabase::map_err)?;
+
+    audit(
+        "reset_mfa",
+        &user_id,
+        serde_json::json!({ "sessions": revoked }),
+    )
+    .await
+}
+
+#[server(name = AdminSetDisabled, prefix = "/auth", endpoint = "admin_set_disabled", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_permission(Permission::ManageUsers), require_aal2))]
+async fn admin_set_disabled(user_id: String, disabled: bool) -> Result<(), ServerFnError> {
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+
+    if disabled && auth_session.user.unwrap().identity.user_id == user_id {
+        expect_context::<leptos_axum::ResponseOptions>().set_status(http::StatusCode::BAD_REQUEST);
+        return Err(ServerFnError::new("You can't disable your own account"));
+    }
+
+    supabase
+        .admin_set_disabled(&user_id, disabled)
+        .await
+        .map_err(crate::supabase::map_err)?;
+
+    // A banned user can't refresh, ending the sessions makes it immediate
+    let mut details = serde_json::json!({});
+    if disabled {
+        let revoked = supabase
+            .revoke_user_sessions(&user_id, None, None)
+            .await
+            .map_err(crate::supabase::map_err)?;
+        details = serde_json::json!({ "sessions": revoked });
+    }
+
+    let action = if disabled { "disable" } else { "enable" };
+    audit(action, &user_id, details).await
+}
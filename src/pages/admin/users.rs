@@ -0,0 +1,193 @@
+use leptos::*;
+
+/// Users per page of the list
+const USERS_PER_PAGE: u32 = 50;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct AdminUserRow {
+    pub id: String,
+    pub email: String,
+    pub role: Option<String>,
+    pub created_at: Option<String>,
+    pub last_sign_in_at: Option<String>,
+    pub disabled: bool,
+}
+
+#[cfg(feature = "ssr")]
+impl From<crate::supabase::AdminUser> for AdminUserRow {
+    fn from(user: crate::supabase::AdminUser) -> Self {
+        Self {
+            role: user.role(),
+            disabled: user.is_disabled(),
+            id: user.id,
+            email: user.email.unwrap_or_default(),
+            created_at: user.created_at,
+            last_sign_in_at: user.last_sign_in_at,
+        }
+    }
+}
+
+/// `?q=` searches the emails, `?page=` counts from 1
+#[component]
+pub fn AdminUsers() -> impl IntoView {
+    let search = RwSignal::new(String::default());
+    let page = RwSignal::new(1u32);
+    // The search as it goes back into the paging links
+    let search_query = RwSignal::new(String::default());
+    #[cfg(feature = "ssr")]
+    {
+        use axum::extract::RawQuery;
+        if let Some(RawQuery(Some(query))) = use_context::<std::sync::Arc<RawQuery>>().as_deref() {
+            for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+                match key.as_ref() {
+                    "q" => search.set(value.trim().to_string()),
+                    "page" => page.set(value.parse().unwrap_or(1).max(1)),
+                    _ => {}
+                }
+            }
+        }
+        if !search().is_empty() {
+            let encoded: String = form_urlencoded::byte_serialize(search().as_bytes()).collect();
+            search_query.set(format!("&q={encoded}"));
+        }
+    }
+
+    let users = create_resource(
+        move || (search(), page()),
+        |(search, page)| list_users((!search.is_empty()).then_some(search), page),
+    );
+
+    view! {
+      <h4 class="uk-heading-line uk-text-center">
+        <span>Users</span>
+      </h4>
+
+      <form method="get" action="/admin" class="uk-flex uk-flex-center uk-margin-bottom">
+        <input
+          type="search"
+          name="q"
+          placeholder="Search by email"
+          aria-label="Search by email"
+          class="uk-input uk-form-width-large"
+          value=search
+        />
+        <button type="submit" class="uk-button uk-button-default uk-margin-small-left">
+          "Search"
+        </button>
+      </form>
+
+      <Transition fallback=|| {
+          view! { <div uk-spinner="ratio: 2"></div> }
+      }>
+        {move || {
+            users()
+                .map(|users| match users {
+                    Ok(users) => {
+                        let has_next = users.len() as u32 == USERS_PER_PAGE;
+                        view! {
+                          <table class="uk-table uk-table-divider uk-table-hover uk-text-left">
+                            <thead>
+                              <tr>
+                                <th>"Email"</th>
+                                <th>"Role"</th>
+                                <th>"Created"</th>
+                                <th>"Last sign in"</th>
+                                <th>"Status"</th>
+                              </tr>
+                            </thead>
+                            <tbody>
+                              {users
+                                  .into_iter()
+                                  .map(|user| view! { <AdminUserRowView user=user/> })
+                                  .collect_view()}
+                            </tbody>
+                          </table>
+                          <ul class="uk-pagination uk-flex-center">
+                            {(page() > 1)
+                                .then(|| {
+                                    view! {
+                                      <li>
+                                        <a href=format!(
+                                            "/admin?page={}{}",
+                                            page() - 1,
+                                            search_query(),
+                                        )>
+                                          <span uk-pagination-previous></span>
+                                          "Previous"
+                                        </a>
+                                      </li>
+                                    }
+                                })}
+                            <li class="uk-active">
+                              <span>{page}</span>
+                            </li>
+                            {has_next
+                                .then(|| {
+                                    view! {
+                                      <li>
+                                        <a href=format!(
+                                            "/admin?page={}{}",
+                                            page() + 1,
+                                            search_query(),
+                                        )>
+                                          "Next"
+                                          <span uk-pagination-next></span>
+                                        </a>
+                                      </li>
+                                    }
+                                })}
+                          </ul>
+                        }
+                            .into_view()
+                    }
+                    Err(e) => view! { <p class="uk-text-danger">{e.to_string()}</p> }.into_view(),
+                })
+        }}
+
+      </Transition>
+    }
+}
+
+#[component]
+fn AdminUserRowView(user: AdminUserRow) -> impl IntoView {
+    view! {
+      <tr>
+        <td>
+          <a href=format!("/admin/users/{}", user.id)>{user.email}</a>
+        </td>
+        <td>{user.role.unwrap_or_default()}</td>
+        <td>{user.created_at.unwrap_or_default()}</td>
+        <td>{user.last_sign_in_at.unwrap_or("never".to_string())}</td>
+        <td>
+          {if user.disabled {
+              view! { <span class="uk-label uk-label-danger">"Disabled"</span> }
+          } else {
+              view! { <span class="uk-label uk-label-success">"Active"</span> }
+          }}
+
+        </td>
+      </tr>
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[path = ""]
+mod ssr {
+    pub use crate::compose_from_fn;
+    pub use crate::middlewares::{require_permission, Permission};
+}
+#[cfg(feature = "ssr")]
+pub use ssr::*;
+
+#[server(name = ListUsers, prefix = "/auth", endpoint = "admin_users", input = server_fn::codec::GetUrl, client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_permission(Permission::ListUsers)))]
+async fn list_users(search: Option<String>, page: u32) -> Result<Vec<AdminUserRow>, ServerFnError> {
+    use crate::supabase::Supabase;
+
+    let supabase = expect_context::<Supabase>();
+    let users = supabase
+        .admin_list_users(page, USERS_PER_PAGE, search.as_deref())
+        .await
+        .map_err(crate::supabase::map_err)?;
+    Ok(users.into_iter().map(AdminUserRow::from).collect())
+}
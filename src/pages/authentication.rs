@@ -1,9 +1,27 @@
-use crate::components::auth::AuthProvider;
+use crate::components::auth::{AuthProvider, EmailLinkForm, OAuthProvider, PasskeySignin};
 use crate::components::{Signin, Signup};
 use leptos::*;
 
 #[component]
 pub fn SignInPage() -> impl IntoView {
+    #[allow(unused_mut)]
+    let mut providers: Vec<OAuthProvider> = vec![];
+    // Where to go once signed in, set by `require_login` and the protected pages
+    let callback = RwSignal::new(String::default());
+    #[cfg(feature = "ssr")]
+    {
+        use axum::extract::RawQuery;
+        if let Some(supabase) = use_context::<crate::supabase::Supabase>() {
+            providers = supabase.oauth_providers().to_vec();
+        }
+        if let Some(RawQuery(Some(query))) = use_context::<std::sync::Arc<RawQuery>>().as_deref() {
+            let cb = form_urlencoded::parse(query.as_bytes()).find(|(key, _)| key == "cb");
+            if let Some((_, cb)) = cb {
+                callback.set(crate::components::auth::redirect::safe_callback(&cb));
+            }
+        }
+    }
+
     view! {
       <AuthProvider unprotected=true>
         <section class="  uk-section">
@@ -17,7 +35,9 @@ pub fn SignInPage() -> impl IntoView {
               </a>
             </ul>
             <div class="uk-margin">
-              <Signin/>
+              <Signin providers=providers callback=callback()/>
+              <PasskeySignin callback=callback()/>
+              <EmailLinkForm/>
             // <Signup/>
             </div>
           </div>
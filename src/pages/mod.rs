@@ -1,11 +1,15 @@
 mod add_two_factor;
+mod admin;
 mod authentication;
 mod home;
+mod reset_password;
 mod user_settings;
 mod verify_mfa;
 
 pub use add_two_factor::AddNewAuthenticator;
+pub use admin::{AdminAuditLog, AdminPage, AdminUserDetail, AdminUsers};
 pub use authentication::{SignInPage, SignUpPage};
 pub use home::HomePage;
+pub use reset_password::ResetPasswordPage;
 pub use user_settings::UserSettings;
 pub use verify_mfa::VerifyMultiFactorAuth;
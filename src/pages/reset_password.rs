@@ -0,0 +1,23 @@
+use leptos::*;
+use leptos_meta::Title;
+
+use crate::components::auth::{AuthProvider, SetNewPasswordForm};
+
+#[component]
+pub fn ResetPasswordPage() -> impl IntoView {
+    view! {
+      <AuthProvider>
+        <Title text="Reset Password - Supabase Leptos"/>
+        <hr class="uk-divider-small"/>
+        <div class="uk-container uk-margin-top">
+          <h4 class="uk-heading-line uk-text-center">
+            <span>"Choose a New Password"</span>
+          </h4>
+          <p class="uk-text-meta">"Your other devices will be signed out."</p>
+          <div class="uk-card uk-card-body">
+            <SetNewPasswordForm/>
+          </div>
+        </div>
+      </AuthProvider>
+    }
+}
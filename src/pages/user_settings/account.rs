@@ -0,0 +1,270 @@
+use leptos::*;
+use leptos_router::ActionForm;
+
+use crate::components::auth::{follow_step_up, use_step_up};
+use crate::components::csrf::CsrfField;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct AccountEmail {
+    pub email: String,
+    /// Waiting for the confirmation link to be followed
+    pub pending: Option<String>,
+}
+
+#[component]
+pub fn AccountSettings() -> impl IntoView {
+    let account_email = create_resource(|| (), |_| get_account_email());
+
+    view! {
+      <h4 class="uk-heading-line uk-text-center">
+        <span>Account</span>
+      </h4>
+
+      <section class="uk-flex uk-flex-column uk-flex-middle uk-text-left">
+        <Transition fallback=|| {
+            view! { <div uk-spinner="ratio: 2"></div> }
+        }>
+          {move || {
+              account_email()
+                  .and_then(|account| account.ok())
+                  .map(|account| {
+                      view! {
+                        <div class="uk-width-1-2 uk-margin-bottom">
+                          <h4 class="uk-margin-remove-bottom">{account.email}</h4>
+                          {account
+                              .pending
+                              .map(|pending| {
+                                  view! {
+                                    <p class="uk-text-meta uk-text-warning uk-margin-remove-top">
+                                      "Changing to " {pending}
+                                      ", follow the link sent to confirm the change."
+                                    </p>
+                                  }
+                              })}
+                        </div>
+                      }
+                  })
+          }}
+
+        </Transition>
+
+        <ChangeEmailForm/>
+        <ChangePasswordForm/>
+      </section>
+    }
+}
+
+#[island]
+fn ChangeEmailForm() -> impl IntoView {
+    let change_email_action = create_server_action::<ChangeEmail>();
+    use_step_up(change_email_action);
+
+    view! {
+      {move || {
+          if let Some(Ok(_)) = change_email_action.value().get() {
+              window().location().reload().unwrap();
+          }
+      }}
+
+      <ActionForm action=change_email_action class="uk-width-1-2 uk-margin-bottom">
+        <CsrfField/>
+        <div class="uk-grid-small uk-flex-middle" uk-grid>
+          <div class="uk-width-expand">
+            <input
+              type="email"
+              name="email"
+              placeholder="New email address"
+              aria-label="New email address"
+              class="uk-input"
+              required
+            />
+          </div>
+          <div class="uk-width-auto">
+            <button type="submit" class="uk-button uk-button-small uk-button-primary">
+              "Change email"
+            </button>
+          </div>
+        </div>
+        {move || {
+            change_email_action
+                .value()
+                .get()
+                .and_then(|result| result.err())
+                .map(|e| view! { <p class="uk-text-danger">{e.to_string()}</p> })
+        }}
+      </ActionForm>
+    }
+}
+
+#[island]
+fn ChangePasswordForm() -> impl IntoView {
+    let change_password_action = create_server_action::<ChangePassword>();
+    follow_step_up(change_password_action);
+
+    view! {
+      <ActionForm action=change_password_action class="uk-width-1-2 uk-margin-bottom">
+        <CsrfField/>
+        <div class="uk-grid-small uk-child-width-1-1" uk-grid>
+          <div>
+            <input
+              type="password"
+              name="current_password"
+              placeholder="Current password"
+              aria-label="Current password"
+              class="uk-input"
+              required
+            />
+          </div>
+          <div>
+            <input
+              type="password"
+              name="password"
+              placeholder="New password"
+              aria-label="New password"
+              class="uk-input"
+              minlength="8"
+              required
+            />
+          </div>
+          <div>
+            <input
+              type="password"
+              name="confirm"
+              placeholder="Repeat the new password"
+              aria-label="Repeat the new password"
+              class="uk-input"
+              minlength="8"
+              required
+            />
+          </div>
+          <div class="uk-text-right">
+            <button type="submit" class="uk-button uk-button-small uk-button-primary">
+              "Change password"
+            </button>
+          </div>
+        </div>
+        {move || match change_password_action.value().get() {
+            Some(Ok(())) => {
+                view! {
+                  <p class="uk-text-success">
+                    "Password changed, your other devices were signed out."
+                  </p>
+                }
+                    .into_view()
+            }
+            Some(Err(e)) => view! { <p class="uk-text-danger">{e.to_string()}</p> }.into_view(),
+            None => ().into_view(),
+        }}
+      </ActionForm>
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[path = ""]
+mod ssr {
+    pub use crate::compose_from_fn;
+    pub use crate::middlewares::{rate_limit, require_aal2, require_login, PASSWORD_LIMIT};
+}
+#[cfg(feature = "ssr")]
+pub use ssr::*;
+
+#[server(name = GetAccountEmail, prefix = "/auth", endpoint = "account_email", input = server_fn::codec::GetUrl, client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_login))]
+async fn get_account_email() -> Result<AccountEmail, ServerFnError> {
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let identity = auth_session.user.unwrap().identity;
+
+    let user = supabase
+        .fetch_user(&identity.auth_token)
+        .await
+        .map_err(crate::supabase::map_err)?;
+
+    Ok(AccountEmail {
+        email: user.email.unwrap_or(identity.email),
+        pending: user.new_email,
+    })
+}
+
+#[server(name = ChangeEmail, prefix = "/auth", endpoint = "change_email", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_aal2))]
+async fn change_email(email: String) -> Result<String, ServerFnError> {
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let identity = auth_session.user.unwrap().identity;
+
+    let email = email.trim().to_lowercase();
+    if !email.contains('@') || email == identity.email {
+        expect_context::<leptos_axum::ResponseOptions>().set_status(http::StatusCode::BAD_REQUEST);
+        return Err(ServerFnError::Args("email".to_string()));
+    }
+
+    let redirect_to = format!("{}/user", supabase.site_url());
+    supabase
+        .update_user(
+            &identity.auth_token,
+            serde_json::json!({ "email": email }),
+            Some(&redirect_to),
+        )
+        .await
+        .map_err(crate::supabase::map_err)?;
+
+    Ok(email)
+}
+
+#[server(name = ChangePassword, prefix = "/auth", endpoint = "change_password", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_aal2, rate_limit(&PASSWORD_LIMIT)))]
+async fn change_password(
+    current_password: String,
+    password: String,
+    confirm: String,
+) -> Result<(), ServerFnError> {
+    use crate::components::auth::verify_password;
+    use crate::middlewares::RateLimitGuard;
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let Extension(rate_limit) = leptos_axum::extract::<Extension<RateLimitGuard>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let identity = auth_session.user.clone().unwrap().identity;
+
+    if password.chars().count() < 8 || password != confirm {
+        // The current password wasn't tried, so this doesn't count as an attempt
+        rate_limit.release().await;
+        expect_context::<leptos_axum::ResponseOptions>().set_status(http::StatusCode::BAD_REQUEST);
+        return Err(ServerFnError::Args("password".to_string()));
+    }
+
+    // The session's copy of the email is stale until it's refetched after an email change
+    let email = supabase
+        .fetch_user(&identity.auth_token)
+        .await
+        .map_err(crate::supabase::map_err)?
+        .email
+        .unwrap_or(identity.email.clone());
+    verify_password(&email, &current_password).await?;
+    rate_limit.record_success().await;
+
+    supabase
+        .update_user(
+            &identity.auth_token,
+            serde_json::json!({ "password": password }),
+            None,
+        )
+        .await
+        .map_err(crate::supabase::map_err)?;
+
+    let current_id = auth_session.session.id().map(|id| id.to_string());
+    supabase
+        .revoke_user_sessions(&identity.user_id, None, current_id.as_deref())
+        .await
+        .map_err(crate::supabase::map_err)?;
+    Ok(())
+}
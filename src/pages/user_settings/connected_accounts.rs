@@ -0,0 +1,326 @@
+use leptos::*;
+
+use crate::components::auth::{use_step_up, OAuthProvider};
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct ConnectedAccount {
+    pub identity_id: String,
+    pub provider: String,
+    pub label: String,
+    pub email: Option<String>,
+    pub created_at: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct ConnectedAccounts {
+    pub linked: Vec<ConnectedAccount>,
+    /// Configured providers not linked yet
+    pub available: Vec<OAuthProvider>,
+}
+
+#[component]
+pub fn ConnectedAccountSettings() -> impl IntoView {
+    let accounts = create_resource(|| (), |_| list_connected_accounts());
+
+    view! {
+      <h4 class="uk-heading-line uk-text-center">
+        <span>Connected Accounts</span>
+      </h4>
+
+      <section class="uk-flex uk-flex-column uk-flex-middle uk-text-left">
+        <Transition fallback=|| {
+            view! { <div uk-spinner="ratio: 2"></div> }
+        }>
+          {move || {
+              accounts()
+                  .map(|accounts| {
+                      let ConnectedAccounts { linked, available } = accounts.unwrap_or_default();
+                      // The last way to sign in can't be removed
+                      let can_unlink = linked.len() > 1;
+                      view! {
+                        <ul class="uk-list uk-list-divider uk-width-1-2">
+                          {linked
+                              .into_iter()
+                              .map(|account| {
+                                  view! { <ConnectedAccountRow account=account can_unlink=can_unlink/> }
+                              })
+                              .collect_view()}
+                        </ul>
+                        <div class="uk-width-1-2">
+                          {available
+                              .into_iter()
+                              .map(|provider| view! { <LinkIdentityButton provider=provider/> })
+                              .collect_view()}
+                        </div>
+                      }
+                  })
+          }}
+
+        </Transition>
+      </section>
+    }
+}
+
+#[component]
+fn ConnectedAccountRow(account: ConnectedAccount, can_unlink: bool) -> impl IntoView {
+    view! {
+      <li>
+        <div class="uk-grid-small uk-flex-middle" uk-grid>
+          <div class="uk-width-expand">
+            <h4 class="uk-text-default uk-margin-remove-bottom">{account.label}</h4>
+            <p class="uk-text-meta uk-margin-remove-top">
+              {account.email.unwrap_or_default()} " · linked "
+              {account.created_at.unwrap_or_default()}
+            </p>
+          </div>
+          <div class="uk-width-auto">
+            {can_unlink
+                .then(|| view! { <UnlinkIdentityButton identity_id=account.identity_id/> })}
+          </div>
+        </div>
+      </li>
+    }
+}
+
+#[island]
+fn LinkIdentityButton(provider: OAuthProvider) -> impl IntoView {
+    let link_identity_action = create_server_action::<LinkIdentity>();
+    use_step_up(link_identity_action);
+    let id = provider.id.clone();
+
+    view! {
+      <button
+        type="button"
+        class="uk-button uk-button-default uk-button-small uk-margin-small-right"
+        on:click=move |_| link_identity_action.dispatch(LinkIdentity { provider: id.clone() })
+      >
+        <span uk-icon=provider.icon class="uk-margin-small-right"></span>
+        "Connect " {provider.label}
+      </button>
+      {move || {
+          link_identity_action
+              .value()
+              .get()
+              .and_then(|result| result.err())
+              .map(|e| view! { <p class="uk-text-danger">{e.to_string()}</p> })
+      }}
+    }
+}
+
+#[island]
+fn UnlinkIdentityButton(identity_id: String) -> impl IntoView {
+    let prefers_dark = RwSignal::new(crate::PrefersDark::check());
+    let unlink_identity_action = create_server_action::<UnlinkIdentity>();
+    use_step_up(unlink_identity_action);
+    let id = identity_id.clone();
+
+    view! {
+      {move || {
+          if let Some(Ok(())) = unlink_identity_action.value().get() {
+              window().location().reload().unwrap();
+          }
+      }}
+
+      <div id=&format!("confirm-unlink_{identity_id}") class="uk-flex-top" uk-modal>
+        <div class=move || {
+            format!(
+                "uk-modal-dialog uk-modal-body uk-margin-auto-vertical uk-background-{0} uk-{1} bg-toggle",
+                if prefers_dark() { "secondary" } else { "default" },
+                if prefers_dark() { "light" } else { "dark" },
+            )
+        }>
+          <h4 class="uk-modal-title uk-text-center">"Disconnect this account?"</h4>
+          <p class="uk-text-center">
+            <button class="uk-button uk-button-default uk-modal-close" type="button">
+              "Cancel"
+            </button>
+            <button
+              type="button"
+              class="uk-button uk-button-danger uk-modal-close"
+              on:click=move |_| {
+                  unlink_identity_action
+                      .dispatch(UnlinkIdentity {
+                          identity_id: id.clone(),
+                      })
+              }
+            >
+              "Disconnect"
+            </button>
+          </p>
+        </div>
+      </div>
+
+      <button
+        uk-toggle=&format!("target: #confirm-unlink_{identity_id}")
+        class="uk-button uk-button-default"
+      >
+        Disconnect
+      </button>
+      {move || {
+          unlink_identity_action
+              .value()
+              .get()
+              .and_then(|result| result.err())
+              .map(|e| view! { <p class="uk-text-danger">{e.to_string()}</p> })
+      }}
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[path = ""]
+mod ssr {
+    pub use crate::compose_from_fn;
+    pub use crate::middlewares::{require_aal2, require_login};
+}
+#[cfg(feature = "ssr")]
+pub use ssr::*;
+
+#[server(name = ListConnectedAccounts, prefix = "/auth", endpoint = "connected_accounts", input = server_fn::codec::GetUrl, client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_login))]
+async fn list_connected_accounts() -> Result<ConnectedAccounts, ServerFnError> {
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let identity = auth_session.user.unwrap().identity;
+
+    let user = supabase
+        .fetch_user(&identity.auth_token)
+        .await
+        .map_err(crate::supabase::map_err)?;
+
+    let linked: Vec<ConnectedAccount> = user
+        .identities
+        .iter()
+        .map(|linked| ConnectedAccount {
+            identity_id: linked.identity_id.clone(),
+            provider: linked.provider.clone(),
+            label: match supabase.oauth_provider(&linked.provider) {
+                Some(provider) => provider.label.clone(),
+                None if linked.provider == "email" => "Email and password".to_string(),
+                None => linked.provider.clone(),
+            },
+            email: linked.email(),
+            created_at: linked.created_at.clone(),
+        })
+        .collect();
+
+    let available = supabase
+        .oauth_providers()
+        .iter()
+        .filter(|provider| !linked.iter().any(|l| l.provider == provider.id))
+        .cloned()
+        .collect();
+
+    Ok(ConnectedAccounts { linked, available })
+}
+
+#[server(name = LinkIdentity, prefix = "/auth", endpoint = "link_identity", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_aal2))]
+async fn link_identity(provider: String) -> Result<(), ServerFnError> {
+    use crate::components::auth::pkce;
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let identity = auth_session.user.unwrap().identity;
+
+    let Some(provider) = supabase.oauth_provider(&provider) else {
+        expect_context::<leptos_axum::ResponseOptions>().set_status(http::StatusCode::BAD_REQUEST);
+        return Err(ServerFnError::Args("provider".to_string()));
+    };
+
+    let redirect_url = supabase
+        .identity_link_url(
+            &identity.auth_token,
+            &provider.id,
+            &provider.scopes,
+            &format!("{}/auth/callback_link", supabase.site_url()),
+            &pkce::start_flow(),
+        )
+        .await
+        .map_err(crate::supabase::map_err)?;
+
+    leptos_axum::redirect(&redirect_url);
+    Ok(())
+}
+
+#[server(prefix = "/auth", endpoint = "callback_link", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_login))]
+async fn callback_link() -> Result<(), ServerFnError> {
+    use crate::components::auth::pkce;
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::extract::Query;
+    use axum::Extension;
+
+    #[derive(serde::Deserialize, Debug)]
+    struct LinkCode {
+        // Missing when the identity belongs to another user, Supabase sends an error instead
+        code: Option<String>,
+    }
+
+    let Query(LinkCode { code }) = leptos_axum::extract::<Query<LinkCode>>().await?;
+    let (Some(code), Some(pkce_code_verifier)) = (code, pkce::take_verifier().await?) else {
+        leptos_axum::redirect("/user");
+        return Ok(());
+    };
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let identity = auth_session.user.unwrap().identity;
+
+    // Linking happened on Supabase's side, the session the code opens isn't needed
+    let access_token = supabase
+        .client
+        .exchange_code_for_session(&code, &pkce_code_verifier)
+        .await
+        .map_err(crate::supabase::map_err)?;
+    let _ = supabase.logout_session(&access_token.access_token).await;
+
+    if access_token.user.id != identity.user_id {
+        tracing::warn!(
+            "\ncallback_link - code for {} used by {}",
+            access_token.user.id,
+            identity.user_id
+        );
+    }
+
+    leptos_axum::redirect("/user");
+    Ok(())
+}
+
+#[server(name = UnlinkIdentity, prefix = "/auth", endpoint = "unlink_identity", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_aal2))]
+async fn unlink_identity(identity_id: String) -> Result<(), ServerFnError> {
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let res_options = expect_context::<leptos_axum::ResponseOptions>();
+    let identity = auth_session.user.unwrap().identity;
+
+    let user = supabase
+        .fetch_user(&identity.auth_token)
+        .await
+        .map_err(crate::supabase::map_err)?;
+
+    if !user.identities.iter().any(|i| i.identity_id == identity_id) {
+        res_options.set_status(http::StatusCode::NOT_FOUND);
+        return Err(ServerFnError::Args("identity_id".to_string()));
+    }
+    if user.identities.len() < 2 {
+        res_options.set_status(http::StatusCode::CONFLICT);
+        return Err(ServerFnError::new(
+            "This is your only way to sign in, connect another account first",
+        ));
+    }
+
+    supabase
+        .unlink_identity(&identity.auth_token, &identity_id)
+        .await
+        .map_err(crate::supabase::map_err)?;
+    Ok(())
+}
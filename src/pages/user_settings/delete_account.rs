@@ -0,0 +1,269 @@
+use leptos::*;
+use leptos_router::ActionForm;
+
+use super::sessions::Device;
+use crate::components::auth::follow_step_up;
+use crate::components::csrf::CsrfField;
+use crate::components::todo::TaskSchema;
+
+const EXPORT_FILE_NAME: &str = "todo-account-export.json";
+
+/// Everything kept about the user, handed over on request and before deleting the account
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct AccountExport {
+    pub exported_at: String,
+    pub user_id: String,
+    pub email: String,
+    pub tasks: Vec<TaskSchema>,
+    pub sessions: Vec<Device>,
+    pub mfa_factors: Vec<String>,
+}
+
+/// `data:` URL of the export so it can be saved once the account, and the session, are gone
+fn export_data_url(export: &AccountExport) -> String {
+    let json = serde_json::to_string_pretty(export).unwrap_or_default();
+    let encoded: String = json
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect();
+    format!("data:application/json;charset=utf-8,{encoded}")
+}
+
+#[component]
+pub fn DeleteAccountSettings() -> impl IntoView {
+    view! {
+      <h4 class="uk-heading-line uk-text-center">
+        <span>Your Data</span>
+      </h4>
+
+      <section class="uk-flex uk-flex-column uk-flex-middle uk-text-left">
+        <div class="uk-grid-small uk-flex-middle uk-margin-bottom uk-width-1-2" uk-grid>
+          <div class="uk-width-expand">
+            <h4 class="uk-margin-remove-bottom">Export</h4>
+            <p class="uk-text-meta uk-margin-remove-top">
+              "Your tasks, sessions and authenticator names, as a single JSON file."
+            </p>
+          </div>
+          <div class="uk-width-auto">
+            <a
+              href="/auth/export_data"
+              download=EXPORT_FILE_NAME
+              class="uk-button uk-button-small uk-button-default"
+            >
+              "Download"
+            </a>
+          </div>
+        </div>
+
+        <div class="uk-grid-small uk-flex-middle uk-margin-bottom uk-width-1-2" uk-grid>
+          <div class="uk-width-expand">
+            <h4 class="uk-margin-remove-bottom uk-text-danger">Delete account</h4>
+            <p class="uk-text-meta uk-margin-remove-top">
+              "Removes your account and everything in it for good. "
+              "You'll be asked to confirm it's you and handed the JSON export first."
+            </p>
+          </div>
+        </div>
+        <DeleteAccountForm/>
+      </section>
+    }
+}
+
+#[island]
+fn DeleteAccountForm() -> impl IntoView {
+    let delete_account_action = create_server_action::<DeleteAccount>();
+    follow_step_up(delete_account_action);
+
+    view! {
+      {move || match delete_account_action.value().get() {
+          Some(Ok(export)) => {
+              view! {
+                <div class="uk-alert-primary uk-width-1-2" uk-alert>
+                  <p>"Your account was deleted. Save your data, it isn't kept anywhere else."</p>
+                  <p class="uk-text-right">
+                    <a
+                      href=export_data_url(&export)
+                      download=EXPORT_FILE_NAME
+                      class="uk-button uk-button-small uk-button-primary uk-margin-small-right"
+                    >
+                      "Download export"
+                    </a>
+                    <a href="/signin" class="uk-button uk-button-small uk-button-default">
+                      "Done"
+                    </a>
+                  </p>
+                </div>
+              }
+                  .into_view()
+          }
+          Some(Err(e)) => view! { <p class="uk-text-danger">{e.to_string()}</p> }.into_view(),
+          _ => ().into_view(),
+      }}
+
+      <ActionForm action=delete_account_action class="uk-width-1-2 uk-margin-bottom">
+        <CsrfField/>
+        <div class="uk-grid-small uk-child-width-1-1" uk-grid>
+          <div>
+            <input
+              type="email"
+              name="confirmation"
+              placeholder="Type your email to confirm"
+              aria-label="Type your email to confirm"
+              class="uk-input"
+              required
+            />
+          </div>
+          <div class="uk-text-right">
+            <button type="submit" class="uk-button uk-button-small uk-button-danger">
+              "Delete my account"
+            </button>
+          </div>
+        </div>
+      </ActionForm>
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[path = ""]
+mod ssr {
+    pub use crate::compose_from_fn;
+    pub use crate::middlewares::{
+        rate_limit, require_aal2, require_fresh_auth, ACCOUNT_DELETION_LIMIT, FRESH_AUTH_MAX_AGE,
+    };
+
+    use leptos::{expect_context, ServerFnError};
+    use time::format_description::well_known::Iso8601;
+    use time::OffsetDateTime;
+
+    use super::{AccountExport, Device, TaskSchema};
+    use crate::supabase::{IdentityData, Supabase};
+
+    pub async fn build_export(identity: &IdentityData) -> Result<AccountExport, ServerFnError> {
+        let supabase = expect_context::<Supabase>();
+
+        let query_response = supabase
+            .client
+            .query()
+            .from("tasks")
+            .select(
+                "id,title,description,completed,due_at,tags,priority,reminder:reminders(remind_at)",
+            )
+            .eq("author_id", identity.user_id.clone())
+            .order("id.asc")
+            .auth(&identity.auth_token)
+            .execute()
+            .await;
+        let tasks = supabase_rust::parse_response::<TaskSchema>(query_response)
+            .await
+            .map_err(crate::supabase::map_err)?;
+
+        let sessions = supabase
+            .list_user_sessions(&identity.user_id)
+            .await
+            .map_err(crate::supabase::map_err)?
+            .into_iter()
+            .map(|s| Device {
+                current: false,
+                sb_id: s.sb_id,
+                user_agent: s.user_agent,
+                ip_address: s.ip_address,
+                created_at: s.created_at,
+                refreshed_at: s.refreshed_at,
+                expiry_date: s.expiry_date,
+            })
+            .collect();
+
+        let mfa_factors = supabase
+            .client
+            .mfa_list_factors(identity.user_id.clone(), supabase.admin_token())
+            .await
+            .map_err(crate::supabase::map_err)?
+            .into_iter()
+            .filter_map(|factor| factor.friendly_name)
+            .collect();
+
+        Ok(AccountExport {
+            exported_at: OffsetDateTime::now_utc().format(&Iso8601::DEFAULT).unwrap(),
+            user_id: identity.user_id.clone(),
+            email: identity.email.clone(),
+            tasks,
+            sessions,
+            mfa_factors,
+        })
+    }
+}
+#[cfg(feature = "ssr")]
+pub use ssr::*;
+
+#[server(name = ExportAccountData, prefix = "/auth", endpoint = "export_data", input = server_fn::codec::GetUrl, client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_aal2))]
+async fn export_account_data() -> Result<AccountExport, ServerFnError> {
+    use crate::supabase::AuthSession;
+    use axum::Extension;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let identity = auth_session.user.unwrap().identity;
+
+    let export = build_export(&identity).await?;
+    expect_context::<leptos_axum::ResponseOptions>().insert_header(
+        http::header::CONTENT_DISPOSITION,
+        format!("attachment; filename=\"{EXPORT_FILE_NAME}\"")
+            .parse()
+            .unwrap(),
+    );
+    Ok(export)
+}
+
+/// Returns the export taken right before the deletion. The user has to have signed in or
+/// verified again recently, a session left open somewhere isn't enough.
+#[server(name = DeleteAccount, prefix = "/auth", endpoint = "delete_account", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_aal2, require_fresh_auth(FRESH_AUTH_MAX_AGE), rate_limit(&ACCOUNT_DELETION_LIMIT)))]
+async fn delete_account(confirmation: String) -> Result<AccountExport, ServerFnError> {
+    use crate::middlewares::RateLimitGuard;
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+
+    let Extension(mut auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let Extension(rate_limit) = leptos_axum::extract::<Extension<RateLimitGuard>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let mut identity = auth_session.user.clone().unwrap().identity;
+    // The session's copy of the email is stale after an email change, the export gets it too
+    if let Some(email) = supabase
+        .fetch_user(&identity.auth_token)
+        .await
+        .map_err(crate::supabase::map_err)?
+        .email
+    {
+        identity.email = email;
+    }
+
+    if !confirmation.trim().eq_ignore_ascii_case(&identity.email) {
+        expect_context::<leptos_axum::ResponseOptions>().set_status(http::StatusCode::BAD_REQUEST);
+        return Err(ServerFnError::new("The email doesn't match your account"));
+    }
+
+    rate_limit.record_success().await;
+
+    let export = build_export(&identity).await?;
+
+    // Clears the sessions cache, the table rows would only go with the auth user
+    supabase
+        .revoke_user_sessions(&identity.user_id, None, None)
+        .await
+        .map_err(crate::supabase::map_err)?;
+    supabase
+        .delete_auth_user(&identity.user_id)
+        .await
+        .map_err(crate::supabase::map_err)?;
+
+    if let Err(e) = auth_session.logout().await {
+        tracing::error!("\ndelete_account logout - {e:?}");
+    }
+    tracing::info!("Deleted account {}", identity.user_id);
+    Ok(export)
+}
@@ -1,7 +1,30 @@
+mod account;
+mod connected_accounts;
+mod delete_account;
+mod passkeys;
+mod recovery_codes;
+mod reminders;
+mod sessions;
+mod tokens;
+mod trusted_devices;
+mod webhooks;
+
 use leptos::*;
 use leptos_meta::Title;
 
-use crate::components::auth::{AuthProvider, MFAFactor};
+use crate::components::auth::{use_step_up, AuthProvider, MFAFactor};
+use account::AccountSettings;
+use connected_accounts::ConnectedAccountSettings;
+use delete_account::DeleteAccountSettings;
+use passkeys::PasskeySettings;
+use recovery_codes::RecoveryCodeSettings;
+use reminders::ReminderSettings;
+use sessions::SessionSettings;
+use tokens::AccessTokenSettings;
+use trusted_devices::TrustedDeviceSettings;
+use webhooks::WebhookSettings;
+
+pub(crate) use sessions::describe_user_agent;
 
 #[component]
 pub fn UserSettings() -> impl IntoView {
@@ -17,6 +40,9 @@ pub fn UserSettings() -> impl IntoView {
       <AuthProvider>
         <hr class="uk-divider-small"/>
         <div class="uk-container uk-margin-top">
+          <AccountSettings/>
+          <ConnectedAccountSettings/>
+
           <h4 class="uk-heading-line uk-text-center">
             <span>Two-Factor Authentication</span>
           </h4>
@@ -91,6 +117,15 @@ pub fn UserSettings() -> impl IntoView {
               </ul>
             </Transition>
           </section>
+          <RecoveryCodeSettings/>
+          <TrustedDeviceSettings/>
+
+          <PasskeySettings/>
+          <SessionSettings/>
+          <ReminderSettings/>
+          <WebhookSettings/>
+          <AccessTokenSettings/>
+          <DeleteAccountSettings/>
         </div>
       </AuthProvider>
     }
@@ -101,6 +136,7 @@ fn RemoveAuthenticatorButton(factor_id: String) -> impl IntoView {
     let prefers_dark = RwSignal::new(crate::PrefersDark::check());
     let id = factor_id.clone();
     let remove_mfa_action = create_server_action::<RemoveMFA>();
+    use_step_up(remove_mfa_action);
 
     let delete_factor = move |_| {
         remove_mfa_action.dispatch(RemoveMFA {
@@ -110,7 +146,7 @@ fn RemoveAuthenticatorButton(factor_id: String) -> impl IntoView {
 
     view! {
       {move || {
-          if remove_mfa_action.version().get() > 0 {
+          if let Some(Ok(_)) = remove_mfa_action.value().get() {
               window().location().reload().unwrap();
           }
       }}
@@ -152,12 +188,12 @@ fn RemoveAuthenticatorButton(factor_id: String) -> impl IntoView {
 #[path = ""]
 mod ssr {
     pub use crate::compose_from_fn;
-    pub use crate::middlewares::require_login;
+    pub use crate::middlewares::{require_aal2, require_login};
 }
 #[cfg(feature = "ssr")]
 pub use ssr::*;
 
-#[server(name = ListUserMFA, prefix = "/auth", endpoint = "list_factors", input = server_fn::codec::GetUrl)]
+#[server(name = ListUserMFA, prefix = "/auth", endpoint = "list_factors", input = server_fn::codec::GetUrl, client = crate::components::csrf::CsrfClient)]
 #[middleware(compose_from_fn!(require_login))]
 pub async fn list_user_factors() -> Result<Vec<MFAFactor>, ServerFnError> {
     use crate::components::auth::filter_verified_factors;
@@ -181,8 +217,8 @@ pub async fn list_user_factors() -> Result<Vec<MFAFactor>, ServerFnError> {
     Ok(filter_verified_factors(factors, &auth_token).await)
 }
 
-#[server(name = RemoveMFA, prefix = "/auth", endpoint = "remove_factor")]
-#[middleware(compose_from_fn!(require_login))]
+#[server(name = RemoveMFA, prefix = "/auth", endpoint = "remove_factor", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_aal2))]
 async fn remove_factor(factor_id: String) -> Result<(), ServerFnError> {
     use crate::components::auth::filter_verified_factors;
     use crate::supabase::{AuthSession, Supabase};
@@ -194,11 +230,6 @@ async fn remove_factor(factor_id: String) -> Result<(), ServerFnError> {
 
     let mut user_identity = auth_session.user.unwrap().identity;
 
-    if user_identity.has_mfa && user_identity.aal == "aal1" {
-        leptos_axum::redirect("/user/authenticate?cb=/user");
-        return Ok(());
-    }
-
     supabase
         .client
         .mfa_delete_factor(factor_id, &user_identity.auth_token)
@@ -224,7 +255,8 @@ async fn remove_factor(factor_id: String) -> Result<(), ServerFnError> {
         .await
         .map_err(crate::supabase::map_err)?;
 
-    user_identity.has_mfa = !factors.is_empty();
+    user_identity.has_mfa =
+        !factors.is_empty() || supabase.has_passkeys(&user_identity.user_id).await;
     supabase
         .update_assurance_level(session_id, user_identity.clone(), refreshed_token)
         .await;
@@ -0,0 +1,311 @@
+use leptos::*;
+use webauthn_rs_proto::{CreationChallengeResponse, RegisterPublicKeyCredential};
+
+use crate::components::auth::passkey::{create_credential, PasskeyChallenge};
+use crate::components::auth::{follow_step_up, use_step_up, PasskeyInfo};
+
+#[component]
+pub fn PasskeySettings() -> impl IntoView {
+    let passkeys = create_resource(|| (), |_| list_passkeys());
+
+    view! {
+      <h4 class="uk-heading-line uk-text-center">
+        <span>Passkeys</span>
+      </h4>
+
+      <section class="uk-flex uk-flex-column uk-flex-middle uk-text-left">
+        <div class="uk-grid-small uk-flex-middle uk-margin-bottom uk-width-1-2" uk-grid>
+          <div class="uk-width-expand">
+            <h4 class="uk-margin-remove-bottom">Passwordless sign in</h4>
+            <p class="uk-text-meta uk-margin-remove-top">
+              "Sign in with your device's fingerprint, face or PIN. "
+              "Passkeys also stand in for the authenticator code."
+            </p>
+          </div>
+        </div>
+
+        <AddPasskeyForm/>
+
+        <Transition fallback=|| {
+            view! { <div uk-spinner="ratio: 2"></div> }
+        }>
+          <ul class="uk-list uk-list-divider uk-width-1-2">
+            {move || {
+                passkeys()
+                    .map(|passkeys| {
+                        let passkeys = passkeys.unwrap_or_default();
+                        if passkeys.is_empty() {
+                            view! {
+                              <li class="uk-text-center">
+                                <h4 class="uk-text-default uk-margin-remove-bottom">
+                                  "No passkey added yet"
+                                </h4>
+                              </li>
+                            }
+                                .into_view()
+                        } else {
+                            passkeys
+                                .into_iter()
+                                .map(|passkey| view! { <PasskeyRow passkey=passkey/> })
+                                .collect_view()
+                        }
+                    })
+            }}
+
+          </ul>
+        </Transition>
+      </section>
+    }
+}
+
+#[component]
+fn PasskeyRow(passkey: PasskeyInfo) -> impl IntoView {
+    view! {
+      <li>
+        <div class="uk-grid-small uk-flex-middle" uk-grid>
+          <div class="uk-width-expand">
+            <h4 class="uk-text-default uk-margin-remove-bottom">{passkey.name}</h4>
+            <p class="uk-text-meta uk-margin-remove-top">
+              "Added " {passkey.created_at} " · last used "
+              {passkey.last_used_at.unwrap_or("never".to_string())}
+            </p>
+          </div>
+          <div class="uk-width-auto">
+            <RemovePasskeyButton passkey_id=passkey.id/>
+          </div>
+        </div>
+      </li>
+    }
+}
+
+#[island]
+fn AddPasskeyForm() -> impl IntoView {
+    let name = RwSignal::new(String::default());
+    let add_passkey_action = create_action(|name: &String| {
+        let name = name.clone();
+        async move {
+            let challenge = start_passkey_registration()
+                .await
+                .map_err(|e| e.to_string())?;
+            let credential = create_credential(challenge.options).await?;
+            finish_passkey_registration(challenge.ceremony_id, name, credential)
+                .await
+                .map_err(|e| e.to_string())
+        }
+    });
+    // The browser won't start a passkey ceremony without the user asking for it
+    follow_step_up(add_passkey_action);
+
+    let on_submit = move |ev: ev::SubmitEvent| {
+        ev.prevent_default();
+        add_passkey_action.dispatch(name());
+    };
+
+    view! {
+      {move || match add_passkey_action.value().get() {
+          Some(Ok(())) => {
+              window().location().reload().unwrap();
+              ().into_view()
+          }
+          Some(Err(e)) => view! { <p class="uk-text-danger">{e}</p> }.into_view(),
+          None => ().into_view(),
+      }}
+
+      <form on:submit=on_submit class="uk-width-1-2 uk-margin-bottom">
+        <div class="uk-grid-small uk-flex-middle" uk-grid>
+          <div class="uk-width-expand">
+            <input
+              type="text"
+              maxlength="60"
+              placeholder="Passkey name"
+              aria-label="Passkey name"
+              class="uk-input"
+              on:input=move |ev| name.set(event_target_value(&ev))
+              prop:value=name
+              required
+            />
+          </div>
+          <div class="uk-width-auto">
+            <button
+              type="submit"
+              class="uk-button uk-button-small uk-button-primary"
+              disabled=add_passkey_action.pending()
+            >
+              "Add Passkey"
+            </button>
+          </div>
+        </div>
+      </form>
+    }
+}
+
+#[island]
+fn RemovePasskeyButton(passkey_id: u64) -> impl IntoView {
+    let prefers_dark = RwSignal::new(crate::PrefersDark::check());
+    let remove_passkey_action = create_server_action::<RemovePasskey>();
+    use_step_up(remove_passkey_action);
+
+    view! {
+      {move || {
+          if let Some(Ok(_)) = remove_passkey_action.value().get() {
+              window().location().reload().unwrap();
+          }
+      }}
+
+      <div id=&format!("confirm-remove-passkey_{passkey_id}") class="uk-flex-top" uk-modal>
+        <div class=move || {
+            format!(
+                "uk-modal-dialog uk-modal-body uk-margin-auto-vertical uk-background-{0} uk-{1} bg-toggle",
+                if prefers_dark() { "secondary" } else { "default" },
+                if prefers_dark() { "light" } else { "dark" },
+            )
+        }>
+          <h4 class="uk-modal-title uk-text-center">"Remove passkey?"</h4>
+          <p class="uk-text-center">
+            <button class="uk-button uk-button-default uk-modal-close" type="button">
+              "Cancel"
+            </button>
+            <button
+              type="button"
+              class="uk-button uk-button-danger uk-modal-close"
+              on:click=move |_| remove_passkey_action.dispatch(RemovePasskey { passkey_id })
+            >
+              "Remove"
+            </button>
+          </p>
+        </div>
+      </div>
+
+      <button
+        uk-toggle=&format!("target: #confirm-remove-passkey_{passkey_id}")
+        class="uk-button uk-button-default"
+      >
+        Remove
+      </button>
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[path = ""]
+mod ssr {
+    pub use crate::compose_from_fn;
+    pub use crate::middlewares::{require_aal2, require_login};
+}
+#[cfg(feature = "ssr")]
+pub use ssr::*;
+
+#[server(name = ListPasskeys, prefix = "/auth", endpoint = "passkeys", input = server_fn::codec::GetUrl, client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_login))]
+async fn list_passkeys() -> Result<Vec<PasskeyInfo>, ServerFnError> {
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let identity = auth_session.user.unwrap().identity;
+
+    let passkeys = supabase
+        .list_passkeys(&identity.user_id)
+        .await
+        .map_err(crate::supabase::map_err)?;
+
+    Ok(passkeys
+        .into_iter()
+        .map(|p| PasskeyInfo {
+            id: p.id.unwrap_or_default(),
+            name: p.name.unwrap_or_default(),
+            created_at: p.created_at.unwrap_or_default(),
+            last_used_at: p.last_used_at,
+        })
+        .collect())
+}
+
+#[server(name = StartPasskeyRegistration, prefix = "/auth", endpoint = "passkey_register_start", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_aal2))]
+async fn start_passkey_registration(
+) -> Result<PasskeyChallenge<CreationChallengeResponse>, ServerFnError> {
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let identity = auth_session.user.unwrap().identity;
+
+    let (ceremony_id, options) = supabase
+        .start_passkey_registration(&identity.user_id, &identity.email)
+        .await
+        .map_err(crate::supabase::map_err)?;
+    Ok(PasskeyChallenge {
+        ceremony_id,
+        options,
+    })
+}
+
+/// The session counts as verified with both factors from now on
+#[server(name = FinishPasskeyRegistration, prefix = "/auth", endpoint = "passkey_register", input = server_fn::codec::Json, client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_login))]
+async fn finish_passkey_registration(
+    ceremony_id: String,
+    name: String,
+    credential: RegisterPublicKeyCredential,
+) -> Result<(), ServerFnError> {
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+    use axum_extra::extract::CookieJar;
+
+    let name = name.trim().to_string();
+    if name.is_empty() || name.chars().count() > 60 {
+        expect_context::<leptos_axum::ResponseOptions>().set_status(http::StatusCode::BAD_REQUEST);
+        return Err(ServerFnError::Args("name".to_string()));
+    }
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let mut identity = auth_session.user.unwrap().identity;
+
+    supabase
+        .finish_passkey_registration(&identity.user_id, &ceremony_id, name, &credential)
+        .await
+        .map_err(crate::supabase::map_err)?;
+
+    let headers = leptos_axum::extract::<http::HeaderMap>().await?;
+    let cookies = CookieJar::from_headers(&headers);
+    let session_id = cookies.get("auth").unwrap().value();
+    identity.has_mfa = true;
+    identity.verify_second_factor();
+    supabase.update_identity(session_id, identity).await;
+    Ok(())
+}
+
+#[server(name = RemovePasskey, prefix = "/auth", endpoint = "remove_passkey", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_aal2))]
+async fn remove_passkey(passkey_id: u64) -> Result<(), ServerFnError> {
+    use crate::components::auth::filter_verified_factors;
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+    use axum_extra::extract::CookieJar;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let mut identity = auth_session.user.unwrap().identity;
+
+    supabase
+        .remove_passkey(&identity.user_id, passkey_id)
+        .await
+        .map_err(crate::supabase::map_err)?;
+
+    // Check if the user still has any MFA associated
+    let factors = supabase
+        .client
+        .mfa_list_factors(identity.user_id.clone(), supabase.admin_token())
+        .await
+        .map_err(crate::supabase::map_err)?;
+    let factors = filter_verified_factors(factors, &identity.auth_token).await;
+
+    let headers = leptos_axum::extract::<http::HeaderMap>().await?;
+    let cookies = CookieJar::from_headers(&headers);
+    let session_id = cookies.get("auth").unwrap().value();
+    identity.has_mfa = !factors.is_empty() || supabase.has_passkeys(&identity.user_id).await;
+    supabase.update_identity(session_id, identity).await;
+    Ok(())
+}
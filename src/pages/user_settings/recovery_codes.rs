@@ -0,0 +1,131 @@
+use leptos::*;
+
+use crate::components::auth::{use_step_up, RecoveryCodeList};
+
+#[component]
+pub fn RecoveryCodeSettings() -> impl IntoView {
+    let remaining = create_resource(|| (), |_| count_recovery_codes());
+
+    view! {
+      <section class="uk-flex uk-flex-column uk-flex-middle uk-text-left">
+        <Transition fallback=|| {
+            view! { <div uk-spinner="ratio: 2"></div> }
+        }>
+          {move || {
+              remaining()
+                  .map(|remaining| {
+                      view! { <RecoveryCodesPanel remaining=remaining.unwrap_or_default()/> }
+                  })
+          }}
+
+        </Transition>
+      </section>
+    }
+}
+
+#[island]
+fn RecoveryCodesPanel(remaining: usize) -> impl IntoView {
+    let prefers_dark = RwSignal::new(crate::PrefersDark::check());
+    let regenerate_action = create_server_action::<RegenerateRecoveryCodes>();
+    use_step_up(regenerate_action);
+
+    view! {
+      <div class="uk-grid-small uk-flex-middle uk-margin-bottom uk-width-1-2" uk-grid>
+        <div class="uk-width-expand">
+          <h4 class="uk-margin-remove-bottom">Recovery Codes</h4>
+          <p class="uk-text-meta uk-margin-remove-top">
+            "Single-use codes to get in when your authenticator is lost, "
+            {remaining} " left."
+          </p>
+        </div>
+        <div class="uk-width-auto">
+          <button
+            uk-toggle="target: #confirm-regenerate-recovery-codes"
+            class="uk-button uk-button-small uk-button-default"
+          >
+            "Regenerate"
+          </button>
+        </div>
+      </div>
+
+      {move || match regenerate_action.value().get() {
+          Some(Ok(codes)) if !codes.is_empty() => {
+              view! { <RecoveryCodeList codes=codes/> }.into_view()
+          }
+          Some(Err(e)) => view! { <p class="uk-text-danger">{e.to_string()}</p> }.into_view(),
+          _ => ().into_view(),
+      }}
+
+      <div id="confirm-regenerate-recovery-codes" class="uk-flex-top" uk-modal>
+        <div class=move || {
+            format!(
+                "uk-modal-dialog uk-modal-body uk-margin-auto-vertical uk-background-{0} uk-{1} bg-toggle",
+                if prefers_dark() { "secondary" } else { "default" },
+                if prefers_dark() { "light" } else { "dark" },
+            )
+        }>
+          <h4 class="uk-modal-title uk-text-center">"Replace your recovery codes?"</h4>
+          <p class="uk-text-center">"The codes you have now will stop working."</p>
+          <p class="uk-text-center">
+            <button class="uk-button uk-button-default uk-modal-close" type="button">
+              "Cancel"
+            </button>
+            <button
+              type="button"
+              class="uk-button uk-button-danger uk-modal-close"
+              on:click=move |_| regenerate_action.dispatch(RegenerateRecoveryCodes {})
+            >
+              "Regenerate"
+            </button>
+          </p>
+        </div>
+      </div>
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[path = ""]
+mod ssr {
+    pub use crate::compose_from_fn;
+    pub use crate::middlewares::{require_aal2, require_login};
+}
+#[cfg(feature = "ssr")]
+pub use ssr::*;
+
+#[server(name = CountRecoveryCodes, prefix = "/auth", endpoint = "recovery_codes", input = server_fn::codec::GetUrl, client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_login))]
+async fn count_recovery_codes() -> Result<usize, ServerFnError> {
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let identity = auth_session.user.unwrap().identity;
+
+    supabase
+        .count_recovery_codes(&identity.user_id)
+        .await
+        .map_err(crate::supabase::map_err)
+}
+
+/// Returns the new codes, the previous ones are discarded
+#[server(name = RegenerateRecoveryCodes, prefix = "/auth", endpoint = "regenerate_recovery_codes", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_aal2))]
+async fn regenerate_recovery_codes() -> Result<Vec<String>, ServerFnError> {
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let identity = auth_session.user.unwrap().identity;
+
+    if !identity.has_mfa {
+        expect_context::<leptos_axum::ResponseOptions>().set_status(http::StatusCode::CONFLICT);
+        return Err(ServerFnError::new("Add an authenticator first"));
+    }
+
+    supabase
+        .generate_recovery_codes(&identity.user_id)
+        .await
+        .map_err(crate::supabase::map_err)
+}
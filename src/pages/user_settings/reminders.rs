@@ -0,0 +1,120 @@
+use leptos::*;
+use leptos_router::ActionForm;
+
+use crate::components::csrf::CsrfField;
+
+#[component]
+pub fn ReminderSettings() -> impl IntoView {
+    let lead_time = create_resource(|| (), |_| get_reminder_lead_time());
+
+    view! {
+      <h4 class="uk-heading-line uk-text-center">
+        <span>Reminders</span>
+      </h4>
+
+      <section class="uk-flex uk-flex-column uk-flex-middle uk-text-left">
+        <Transition fallback=|| {
+            view! { <div uk-spinner="ratio: 2"></div> }
+        }>
+          {move || {
+              lead_time()
+                  .map(|minutes| {
+                      view! { <ReminderLeadTime minutes=minutes.unwrap_or_default()/> }
+                  })
+          }}
+
+        </Transition>
+      </section>
+    }
+}
+
+#[island]
+fn ReminderLeadTime(minutes: i64) -> impl IntoView {
+    let set_lead_time_action = create_server_action::<SetReminderLeadTime>();
+    let saved = move || {
+        set_lead_time_action
+            .value()
+            .get()
+            .is_some_and(|r| r.is_ok())
+    };
+
+    view! {
+      <ActionForm action=set_lead_time_action>
+        <CsrfField/>
+        <div class="uk-grid-small uk-flex-middle" uk-grid>
+          <div class="uk-width-expand">
+            <h4 class="uk-margin-remove-bottom">Default lead time</h4>
+            <p class="uk-text-meta uk-margin-remove-top">
+              "Minutes before a task's reminder time to notify you."
+            </p>
+          </div>
+          <div class="uk-width-auto">
+            <input
+              name="minutes"
+              type="number"
+              min="0"
+              max="10080"
+              aria-label="Lead time in minutes"
+              class="uk-input uk-form-width-small"
+              value=minutes
+              required
+            />
+          </div>
+          <div class="uk-width-auto">
+            <button type="submit" class="uk-button uk-button-small uk-button-primary">
+              "Save"
+            </button>
+          </div>
+          <div
+            class="uk-width-1-1 uk-text-success"
+            style:display=move || if saved() { "block" } else { "none" }
+          >
+            "Saved"
+          </div>
+        </div>
+      </ActionForm>
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[path = ""]
+mod ssr {
+    pub use crate::compose_from_fn;
+    pub use crate::middlewares::require_login;
+}
+#[cfg(feature = "ssr")]
+pub use ssr::*;
+
+#[server(name = GetReminderLeadTime, prefix = "/todo", endpoint = "lead_time", input = server_fn::codec::GetUrl, client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_login))]
+async fn get_reminder_lead_time() -> Result<i64, ServerFnError> {
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let identity = auth_session.user.unwrap().identity;
+
+    Ok(crate::reminders::lead_minutes(&supabase, &identity).await)
+}
+
+#[server(name = SetReminderLeadTime, prefix = "/todo", endpoint = "set_lead_time", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_login))]
+async fn set_reminder_lead_time(minutes: i64) -> Result<(), ServerFnError> {
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+
+    // Up to a week ahead
+    if !(0..=10080).contains(&minutes) {
+        expect_context::<leptos_axum::ResponseOptions>().set_status(http::StatusCode::BAD_REQUEST);
+        return Err(ServerFnError::Args("minutes".to_string()));
+    }
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let identity = auth_session.user.unwrap().identity;
+
+    crate::reminders::set_lead_minutes(&supabase, &identity, minutes)
+        .await
+        .map_err(crate::supabase::map_err)
+}
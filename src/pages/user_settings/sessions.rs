@@ -0,0 +1,309 @@
+use leptos::*;
+
+/// A signed in session of the user, identified by its Supabase session id
+/// so the app session id never reaches the browser
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct Device {
+    pub sb_id: String,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: Option<String>,
+    pub refreshed_at: Option<String>,
+    pub expiry_date: Option<String>,
+    pub current: bool,
+}
+
+/// `Firefox on Linux` out of a user agent string, good enough to tell devices apart
+pub(crate) fn describe_user_agent(user_agent: Option<&str>) -> String {
+    let Some(ua) = user_agent else {
+        return "Unknown device".to_string();
+    };
+
+    let browser = [
+        ("Edg/", "Edge"),
+        ("OPR/", "Opera"),
+        ("Firefox/", "Firefox"),
+        ("Chrome/", "Chrome"),
+        ("Safari/", "Safari"),
+        ("curl/", "curl"),
+    ]
+    .into_iter()
+    .find(|(token, _)| ua.contains(token))
+    .map_or("Unknown browser", |(_, name)| name);
+
+    let os = [
+        ("Android", "Android"),
+        ("iPhone", "iOS"),
+        ("iPad", "iPadOS"),
+        ("Windows", "Windows"),
+        ("Mac OS X", "macOS"),
+        ("CrOS", "ChromeOS"),
+        ("Linux", "Linux"),
+    ]
+    .into_iter()
+    .find(|(token, _)| ua.contains(token))
+    .map(|(_, name)| name);
+
+    match os {
+        Some(os) => format!("{browser} on {os}"),
+        None => browser.to_string(),
+    }
+}
+
+#[component]
+pub fn SessionSettings() -> impl IntoView {
+    let devices = create_resource(|| (), |_| list_devices());
+
+    view! {
+      <h4 class="uk-heading-line uk-text-center">
+        <span>Devices and Sessions</span>
+      </h4>
+
+      <section class="uk-flex uk-flex-column uk-flex-middle uk-text-left">
+        <div class="uk-grid-small uk-flex-middle uk-margin-bottom uk-width-1-2" uk-grid>
+          <div class="uk-width-expand">
+            <h4 class="uk-margin-remove-bottom">Signed in devices</h4>
+            <p class="uk-text-meta uk-margin-remove-top">
+              "Revoke a session you don't recognise, it will be signed out on its next request."
+            </p>
+          </div>
+          <div class="uk-width-auto">
+            <RevokeOtherDevicesButton/>
+          </div>
+        </div>
+
+        <Transition fallback=|| {
+            view! { <div uk-spinner="ratio: 2"></div> }
+        }>
+          <ul class="uk-list uk-list-divider uk-width-1-2">
+            {move || {
+                devices()
+                    .map(|devices| {
+                        devices
+                            .unwrap_or_default()
+                            .into_iter()
+                            .map(|device| view! { <DeviceRow device=device/> })
+                            .collect_view()
+                    })
+            }}
+
+          </ul>
+        </Transition>
+      </section>
+    }
+}
+
+#[component]
+fn DeviceRow(device: Device) -> impl IntoView {
+    let this_device = device.current.then(|| {
+        view! { <span class="uk-label uk-label-success uk-margin-small-left">"This device"</span> }
+    });
+
+    view! {
+      <li>
+        <div class="uk-grid-small uk-flex-middle" uk-grid>
+          <div class="uk-width-expand">
+            <h4 class="uk-text-default uk-margin-remove-bottom">
+              {describe_user_agent(device.user_agent.as_deref())}
+              {this_device}
+            </h4>
+            <p class="uk-text-meta uk-margin-remove-top">
+              {device.ip_address.unwrap_or("Unknown address".to_string())} " · signed in "
+              {device.created_at.unwrap_or_default()} " · last active "
+              {device.refreshed_at.unwrap_or("never".to_string())} " · expires "
+              {device.expiry_date.unwrap_or_default()}
+            </p>
+          </div>
+          <div class="uk-width-auto">
+            {(!device.current).then(|| view! { <RevokeDeviceButton sb_id=device.sb_id/> })}
+          </div>
+        </div>
+      </li>
+    }
+}
+
+#[island]
+fn RevokeDeviceButton(sb_id: String) -> impl IntoView {
+    let prefers_dark = RwSignal::new(crate::PrefersDark::check());
+    let revoke_device_action = create_server_action::<RevokeDevice>();
+    let id = sb_id.clone();
+
+    view! {
+      {move || {
+          if revoke_device_action.version().get() > 0 {
+              window().location().reload().unwrap();
+          }
+      }}
+
+      <div id=&format!("confirm-revoke-device_{sb_id}") class="uk-flex-top" uk-modal>
+        <div class=move || {
+            format!(
+                "uk-modal-dialog uk-modal-body uk-margin-auto-vertical uk-background-{0} uk-{1} bg-toggle",
+                if prefers_dark() { "secondary" } else { "default" },
+                if prefers_dark() { "light" } else { "dark" },
+            )
+        }>
+          <h4 class="uk-modal-title uk-text-center">"Sign out this device?"</h4>
+          <p class="uk-text-center">
+            <button class="uk-button uk-button-default uk-modal-close" type="button">
+              "Cancel"
+            </button>
+            <button
+              type="button"
+              class="uk-button uk-button-danger uk-modal-close"
+              on:click=move |_| revoke_device_action.dispatch(RevokeDevice { sb_id: id.clone() })
+            >
+              "Revoke"
+            </button>
+          </p>
+        </div>
+      </div>
+
+      <button
+        uk-toggle=&format!("target: #confirm-revoke-device_{sb_id}")
+        class="uk-button uk-button-default"
+      >
+        Revoke
+      </button>
+    }
+}
+
+#[island]
+fn RevokeOtherDevicesButton() -> impl IntoView {
+    let prefers_dark = RwSignal::new(crate::PrefersDark::check());
+    let revoke_others_action = create_server_action::<RevokeOtherDevices>();
+
+    view! {
+      {move || {
+          if revoke_others_action.version().get() > 0 {
+              window().location().reload().unwrap();
+          }
+      }}
+
+      <div id="confirm-revoke-other-devices" class="uk-flex-top" uk-modal>
+        <div class=move || {
+            format!(
+                "uk-modal-dialog uk-modal-body uk-margin-auto-vertical uk-background-{0} uk-{1} bg-toggle",
+                if prefers_dark() { "secondary" } else { "default" },
+                if prefers_dark() { "light" } else { "dark" },
+            )
+        }>
+          <h4 class="uk-modal-title uk-text-center">"Sign out every other device?"</h4>
+          <p class="uk-text-center">
+            <button class="uk-button uk-button-default uk-modal-close" type="button">
+              "Cancel"
+            </button>
+            <button
+              type="button"
+              class="uk-button uk-button-danger uk-modal-close"
+              on:click=move |_| revoke_others_action.dispatch(RevokeOtherDevices {})
+            >
+              "Sign out"
+            </button>
+          </p>
+        </div>
+      </div>
+
+      <button
+        uk-toggle="target: #confirm-revoke-other-devices"
+        class="uk-button uk-button-small uk-button-danger"
+      >
+        "Sign out others"
+      </button>
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[path = ""]
+mod ssr {
+    pub use crate::compose_from_fn;
+    pub use crate::middlewares::require_login;
+
+    /// Id of the app session making the request, from the `auth` cookie
+    pub async fn current_session_id() -> Result<String, leptos::ServerFnError> {
+        use axum_extra::extract::CookieJar;
+
+        let headers = leptos_axum::extract::<http::HeaderMap>().await?;
+        let cookies = CookieJar::from_headers(&headers);
+        Ok(cookies
+            .get("auth")
+            .map(|cookie| cookie.value().to_string())
+            .unwrap_or_default())
+    }
+}
+#[cfg(feature = "ssr")]
+pub use ssr::*;
+
+#[server(name = ListDevices, prefix = "/auth", endpoint = "devices", input = server_fn::codec::GetUrl, client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_login))]
+async fn list_devices() -> Result<Vec<Device>, ServerFnError> {
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let identity = auth_session.user.unwrap().identity;
+    let current_id = current_session_id().await?;
+
+    let sessions = supabase
+        .list_user_sessions(&identity.user_id)
+        .await
+        .map_err(crate::supabase::map_err)?;
+
+    Ok(sessions
+        .into_iter()
+        .map(|s| Device {
+            current: s.id == current_id,
+            sb_id: s.sb_id,
+            user_agent: s.user_agent,
+            ip_address: s.ip_address,
+            created_at: s.created_at,
+            refreshed_at: s.refreshed_at,
+            expiry_date: s.expiry_date,
+        })
+        .collect())
+}
+
+#[server(name = RevokeDevice, prefix = "/auth", endpoint = "revoke_device", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_login))]
+async fn revoke_device(sb_id: String) -> Result<(), ServerFnError> {
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let identity = auth_session.user.unwrap().identity;
+    let current_id = current_session_id().await?;
+
+    // Signing out is how the current session ends
+    let revoked = supabase
+        .revoke_user_sessions(&identity.user_id, Some(&sb_id), Some(&current_id))
+        .await
+        .map_err(crate::supabase::map_err)?;
+
+    if revoked == 0 {
+        expect_context::<leptos_axum::ResponseOptions>().set_status(http::StatusCode::NOT_FOUND);
+        return Err(ServerFnError::Args("sb_id".to_string()));
+    }
+    Ok(())
+}
+
+#[server(name = RevokeOtherDevices, prefix = "/auth", endpoint = "revoke_other_devices", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_login))]
+async fn revoke_other_devices() -> Result<(), ServerFnError> {
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let identity = auth_session.user.unwrap().identity;
+    let current_id = current_session_id().await?;
+
+    let revoked = supabase
+        .revoke_user_sessions(&identity.user_id, None, Some(&current_id))
+        .await
+        .map_err(crate::supabase::map_err)?;
+
+    tracing::info!("Revoked {revoked} other sessions of {}", identity.user_id);
+    Ok(())
+}
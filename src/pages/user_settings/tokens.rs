@@ -0,0 +1,339 @@
+use leptos::*;
+
+use crate::components::auth::use_step_up;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct AccessToken {
+    pub id: u64,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+}
+
+#[component]
+pub fn AccessTokenSettings() -> impl IntoView {
+    let tokens = create_resource(|| (), |_| list_access_tokens());
+
+    view! {
+      <h4 class="uk-heading-line uk-text-center">
+        <span>Personal Access Tokens</span>
+      </h4>
+
+      <section class="uk-flex uk-flex-column uk-flex-middle uk-text-left">
+        <div class="uk-grid-small uk-flex-middle uk-margin-bottom uk-width-1-2" uk-grid>
+          <div class="uk-width-expand">
+            <h4 class="uk-margin-remove-bottom">REST API</h4>
+            <p class="uk-text-meta uk-margin-remove-top">
+              "Send tokens as "
+              <code>"Authorization: Bearer <token>"</code>
+              " to "
+              <a href="/api/v1/openapi.json">"/api/v1"</a>
+              "."
+            </p>
+          </div>
+        </div>
+
+        <NewAccessTokenForm/>
+
+        <Transition fallback=|| {
+            view! { <div uk-spinner="ratio: 2"></div> }
+        }>
+          <ul class="uk-list uk-list-divider uk-width-1-2">
+            {move || {
+                tokens()
+                    .map(|tokens| {
+                        let tokens = tokens.unwrap_or_default();
+                        if tokens.is_empty() {
+                            view! {
+                              <li class="uk-text-center">
+                                <h4 class="uk-text-default uk-margin-remove-bottom">
+                                  "No access token created yet"
+                                </h4>
+                              </li>
+                            }
+                                .into_view()
+                        } else {
+                            tokens
+                                .into_iter()
+                                .map(|token| {
+                                    view! {
+                                      <li>
+                                        <div class="uk-grid-small uk-flex-middle" uk-grid>
+                                          <div class="uk-width-expand">
+                                            <h4 class="uk-text-default uk-margin-remove-bottom">
+                                              {token.name}
+                                            </h4>
+                                            <p class="uk-text-meta uk-margin-remove-top">
+                                              {token.scopes.join(", ")} " · created "
+                                              {token.created_at} " · last used "
+                                              {token.last_used_at.unwrap_or("never".to_string())}
+                                            </p>
+                                          </div>
+                                          <div class="uk-width-auto">
+                                            <RevokeAccessTokenButton token_id=token.id/>
+                                          </div>
+                                        </div>
+                                      </li>
+                                    }
+                                })
+                                .collect_view()
+                        }
+                    })
+            }}
+
+          </ul>
+        </Transition>
+      </section>
+    }
+}
+
+#[island]
+fn NewAccessTokenForm() -> impl IntoView {
+    let create_token_action = create_server_action::<CreateAccessToken>();
+    use_step_up(create_token_action);
+    let name = RwSignal::new(String::default());
+    let write = RwSignal::new(false);
+
+    let on_submit = move |ev: ev::SubmitEvent| {
+        ev.prevent_default();
+        create_token_action.dispatch(CreateAccessToken {
+            name: name(),
+            write: write(),
+        });
+        name.set(String::default());
+    };
+
+    view! {
+      {move || match create_token_action.value().get() {
+          Some(Ok(token)) => {
+              view! {
+                <div class="uk-alert-success uk-width-1-2" uk-alert>
+                  <p>"Copy your new token now, it won't be shown again:"</p>
+                  <code class="uk-text-break">{token}</code>
+                  <p class="uk-text-right">
+                    <button
+                      type="button"
+                      class="uk-button uk-button-small uk-button-default"
+                      on:click=move |_| window().location().reload().unwrap()
+                    >
+                      "Done"
+                    </button>
+                  </p>
+                </div>
+              }
+                  .into_view()
+          }
+          Some(Err(e)) => view! { <p class="uk-text-danger">{e.to_string()}</p> }.into_view(),
+          None => ().into_view(),
+      }}
+
+      <form on:submit=on_submit class="uk-width-1-2 uk-margin-bottom">
+        <div class="uk-grid-small uk-flex-middle" uk-grid>
+          <div class="uk-width-expand">
+            <input
+              type="text"
+              maxlength="60"
+              placeholder="Token name"
+              aria-label="Token name"
+              class="uk-input"
+              on:input=move |ev| name.set(event_target_value(&ev))
+              prop:value=name
+              required
+            />
+          </div>
+          <div class="uk-width-auto">
+            <label>
+              <input
+                type="checkbox"
+                class="uk-checkbox uk-margin-small-right"
+                prop:checked=write
+                on:change=move |ev| write.set(event_target_checked(&ev))
+              />
+              "Write access"
+            </label>
+          </div>
+          <div class="uk-width-auto">
+            <button type="submit" class="uk-button uk-button-small uk-button-primary">
+              "Create"
+            </button>
+          </div>
+        </div>
+      </form>
+    }
+}
+
+#[island]
+fn RevokeAccessTokenButton(token_id: u64) -> impl IntoView {
+    let prefers_dark = RwSignal::new(crate::PrefersDark::check());
+    let revoke_token_action = create_server_action::<RevokeAccessToken>();
+
+    view! {
+      {move || {
+          if revoke_token_action.version().get() > 0 {
+              window().location().reload().unwrap();
+          }
+      }}
+
+      <div id=&format!("confirm-revoke-token_{token_id}") class="uk-flex-top" uk-modal>
+        <div class=move || {
+            format!(
+                "uk-modal-dialog uk-modal-body uk-margin-auto-vertical uk-background-{0} uk-{1} bg-toggle",
+                if prefers_dark() { "secondary" } else { "default" },
+                if prefers_dark() { "light" } else { "dark" },
+            )
+        }>
+          <h4 class="uk-modal-title uk-text-center">"Revoke token?"</h4>
+          <p class="uk-text-center">
+            <button class="uk-button uk-button-default uk-modal-close" type="button">
+              "Cancel"
+            </button>
+            <button
+              type="button"
+              class="uk-button uk-button-danger uk-modal-close"
+              on:click=move |_| revoke_token_action.dispatch(RevokeAccessToken { token_id })
+            >
+              "Revoke"
+            </button>
+          </p>
+        </div>
+      </div>
+
+      <button
+        uk-toggle=&format!("target: #confirm-revoke-token_{token_id}")
+        class="uk-button uk-button-default"
+      >
+        Revoke
+      </button>
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[path = ""]
+mod ssr {
+    pub use crate::compose_from_fn;
+    pub use crate::middlewares::{
+        require_aal2, require_fresh_auth, require_login, FRESH_AUTH_MAX_AGE,
+    };
+}
+#[cfg(feature = "ssr")]
+pub use ssr::*;
+
+#[server(name = ListAccessTokens, prefix = "/auth", endpoint = "access_tokens", input = server_fn::codec::GetUrl, client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_login))]
+async fn list_access_tokens() -> Result<Vec<AccessToken>, ServerFnError> {
+    use crate::api::tokens::TokenSchema;
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let identity = auth_session.user.unwrap().identity;
+
+    let query_response = supabase
+        .client
+        .query()
+        .from("personal_access_tokens")
+        .select("id,name,scopes,created_at,last_used_at")
+        .eq("owner_id", identity.user_id.clone())
+        .order("created_at.desc")
+        .auth(&identity.auth_token)
+        .execute()
+        .await;
+
+    let tokens = supabase_rust::parse_response::<TokenSchema>(query_response)
+        .await
+        .map_err(crate::supabase::map_err)?;
+
+    Ok(tokens
+        .into_iter()
+        .map(|t| AccessToken {
+            id: t.id.unwrap_or_default(),
+            name: t.name.unwrap_or_default(),
+            scopes: t
+                .scopes
+                .unwrap_or_default()
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            created_at: t.created_at.unwrap_or_default(),
+            last_used_at: t.last_used_at,
+        })
+        .collect())
+}
+
+/// Returns the plaintext token, only its hash is stored
+#[server(name = CreateAccessToken, prefix = "/auth", endpoint = "create_access_token", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_aal2, require_fresh_auth(FRESH_AUTH_MAX_AGE)))]
+async fn create_access_token(name: String, write: bool) -> Result<String, ServerFnError> {
+    use crate::api::tokens::{self, Scope, TokenSchema};
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+
+    let name = name.trim().to_string();
+    if name.is_empty() || name.chars().count() > 60 {
+        expect_context::<leptos_axum::ResponseOptions>().set_status(http::StatusCode::BAD_REQUEST);
+        return Err(ServerFnError::Args("name".to_string()));
+    }
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let identity = auth_session.user.unwrap().identity;
+
+    let token = tokens::generate();
+    let scopes = if write {
+        vec![Scope::Read, Scope::Write]
+    } else {
+        vec![Scope::Read]
+    };
+
+    let row = serde_json::to_string(&TokenSchema {
+        owner_id: Some(identity.user_id.clone()),
+        name: Some(name),
+        token_hash: Some(tokens::hash(&token)),
+        scopes: Some(scopes),
+        ..Default::default()
+    })
+    .unwrap();
+
+    let query_response = supabase
+        .client
+        .query()
+        .from("personal_access_tokens")
+        .insert(row)
+        .auth(&identity.auth_token)
+        .execute()
+        .await;
+
+    supabase_rust::parse_response::<TokenSchema>(query_response)
+        .await
+        .map_err(crate::supabase::map_err)?;
+    Ok(token)
+}
+
+#[server(name = RevokeAccessToken, prefix = "/auth", endpoint = "revoke_access_token", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_login))]
+async fn revoke_access_token(token_id: u64) -> Result<(), ServerFnError> {
+    use crate::api::tokens::TokenSchema;
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let identity = auth_session.user.unwrap().identity;
+
+    let query_response = supabase
+        .client
+        .query()
+        .from("personal_access_tokens")
+        .delete()
+        .eq("id", token_id.to_string())
+        .auth(&identity.auth_token)
+        .execute()
+        .await;
+
+    supabase_rust::parse_response::<TokenSchema>(query_response)
+        .await
+        .map_err(crate::supabase::map_err)?;
+    Ok(())
+}
@@ -0,0 +1,205 @@
+use leptos::*;
+
+use super::sessions::describe_user_agent;
+
+/// A browser that skips the 2FA challenge at sign in
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct TrustedDevice {
+    pub id: u64,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: Option<String>,
+    pub last_used_at: Option<String>,
+    pub expires_at: Option<String>,
+    pub current: bool,
+}
+
+#[component]
+pub fn TrustedDeviceSettings() -> impl IntoView {
+    let devices = create_resource(|| (), |_| list_trusted_devices());
+
+    view! {
+      <section class="uk-flex uk-flex-column uk-flex-middle uk-text-left">
+        <div class="uk-grid-small uk-flex-middle uk-margin-bottom uk-width-1-2" uk-grid>
+          <div class="uk-width-expand">
+            <h4 class="uk-margin-remove-bottom">Trusted Browsers</h4>
+            <p class="uk-text-meta uk-margin-remove-top">
+              "These skip the authenticator code when signing in. "
+              "Removing an authenticator or similar still asks for it."
+            </p>
+          </div>
+        </div>
+
+        <Transition fallback=|| {
+            view! { <div uk-spinner="ratio: 2"></div> }
+        }>
+          <ul class="uk-list uk-list-divider uk-width-1-2">
+            {move || {
+                devices()
+                    .map(|devices| {
+                        let devices = devices.unwrap_or_default();
+                        if devices.is_empty() {
+                            view! {
+                              <li class="uk-text-center">
+                                <h4 class="uk-text-default uk-margin-remove-bottom">
+                                  "No trusted browser"
+                                </h4>
+                              </li>
+                            }
+                                .into_view()
+                        } else {
+                            devices
+                                .into_iter()
+                                .map(|device| view! { <TrustedDeviceRow device=device/> })
+                                .collect_view()
+                        }
+                    })
+            }}
+
+          </ul>
+        </Transition>
+      </section>
+    }
+}
+
+#[component]
+fn TrustedDeviceRow(device: TrustedDevice) -> impl IntoView {
+    let this_browser = device.current.then(|| {
+        view! { <span class="uk-label uk-label-success uk-margin-small-left">"This browser"</span> }
+    });
+
+    view! {
+      <li>
+        <div class="uk-grid-small uk-flex-middle" uk-grid>
+          <div class="uk-width-expand">
+            <h4 class="uk-text-default uk-margin-remove-bottom">
+              {describe_user_agent(device.user_agent.as_deref())}
+              {this_browser}
+            </h4>
+            <p class="uk-text-meta uk-margin-remove-top">
+              {device.ip_address.unwrap_or("Unknown address".to_string())} " · trusted "
+              {device.created_at.unwrap_or_default()} " · last used "
+              {device.last_used_at.unwrap_or("never".to_string())} " · expires "
+              {device.expires_at.unwrap_or_default()}
+            </p>
+          </div>
+          <div class="uk-width-auto">
+            <RevokeTrustedDeviceButton device_id=device.id/>
+          </div>
+        </div>
+      </li>
+    }
+}
+
+#[island]
+fn RevokeTrustedDeviceButton(device_id: u64) -> impl IntoView {
+    let prefers_dark = RwSignal::new(crate::PrefersDark::check());
+    let revoke_action = create_server_action::<RevokeTrustedDevice>();
+
+    view! {
+      {move || {
+          if revoke_action.version().get() > 0 {
+              window().location().reload().unwrap();
+          }
+      }}
+
+      <div id=&format!("confirm-revoke-trusted_{device_id}") class="uk-flex-top" uk-modal>
+        <div class=move || {
+            format!(
+                "uk-modal-dialog uk-modal-body uk-margin-auto-vertical uk-background-{0} uk-{1} bg-toggle",
+                if prefers_dark() { "secondary" } else { "default" },
+                if prefers_dark() { "light" } else { "dark" },
+            )
+        }>
+          <h4 class="uk-modal-title uk-text-center">"Stop trusting this browser?"</h4>
+          <p class="uk-text-center">
+            <button class="uk-button uk-button-default uk-modal-close" type="button">
+              "Cancel"
+            </button>
+            <button
+              type="button"
+              class="uk-button uk-button-danger uk-modal-close"
+              on:click=move |_| revoke_action.dispatch(RevokeTrustedDevice { device_id })
+            >
+              "Revoke"
+            </button>
+          </p>
+        </div>
+      </div>
+
+      <button
+        uk-toggle=&format!("target: #confirm-revoke-trusted_{device_id}")
+        class="uk-button uk-button-default"
+      >
+        Revoke
+      </button>
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[path = ""]
+mod ssr {
+    pub use crate::compose_from_fn;
+    pub use crate::middlewares::require_login;
+}
+#[cfg(feature = "ssr")]
+pub use ssr::*;
+
+#[server(name = ListTrustedDevices, prefix = "/auth", endpoint = "trusted_devices", input = server_fn::codec::GetUrl, client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_login))]
+async fn list_trusted_devices() -> Result<Vec<TrustedDevice>, ServerFnError> {
+    use crate::components::auth::trusted_device;
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let identity = auth_session.user.unwrap().identity;
+    let current_id = trusted_device::current_id().await;
+
+    let devices = supabase
+        .list_trusted_devices(&identity.user_id)
+        .await
+        .map_err(crate::supabase::map_err)?;
+
+    Ok(devices
+        .into_iter()
+        .map(|d| TrustedDevice {
+            id: d.id.unwrap_or_default(),
+            current: d.id.is_some() && d.id == current_id,
+            user_agent: d.user_agent,
+            ip_address: d.ip_address,
+            created_at: d.created_at,
+            last_used_at: d.last_used_at,
+            expires_at: d.expires_at,
+        })
+        .collect())
+}
+
+/// Sessions that already skipped the challenge through the device keep going,
+/// except the current one
+#[server(name = RevokeTrustedDevice, prefix = "/auth", endpoint = "revoke_trusted_device", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_login))]
+async fn revoke_trusted_device(device_id: u64) -> Result<(), ServerFnError> {
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+    use axum_extra::extract::CookieJar;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let mut identity = auth_session.user.unwrap().identity;
+
+    supabase
+        .revoke_trusted_devices(&identity.user_id, Some(device_id))
+        .await
+        .map_err(crate::supabase::map_err)?;
+
+    if identity.trusted_device == Some(device_id) {
+        let headers = leptos_axum::extract::<http::HeaderMap>().await?;
+        let cookies = CookieJar::from_headers(&headers);
+        let session_id = cookies.get("auth").unwrap().value();
+        identity.trusted_device = None;
+        supabase.update_identity(session_id, identity).await;
+    }
+    Ok(())
+}
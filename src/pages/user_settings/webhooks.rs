@@ -0,0 +1,476 @@
+use leptos::*;
+
+const EVENTS: [&str; 4] = ["created", "updated", "completed", "deleted"];
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct Webhook {
+    pub id: u64,
+    pub url: String,
+    pub secret: String,
+    pub events: Vec<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct WebhookDelivery {
+    pub id: u64,
+    pub event: String,
+    pub status: String,
+    pub attempts: u32,
+    pub last_status_code: Option<u16>,
+    pub last_error: Option<String>,
+    pub created_at: String,
+}
+
+#[component]
+pub fn WebhookSettings() -> impl IntoView {
+    let webhooks = create_resource(|| (), |_| list_webhooks());
+
+    view! {
+      <h4 class="uk-heading-line uk-text-center">
+        <span>Webhooks</span>
+      </h4>
+
+      <section class="uk-flex uk-flex-column uk-flex-middle uk-text-left">
+        <div class="uk-grid-small uk-flex-middle uk-margin-bottom uk-width-1-2" uk-grid>
+          <div class="uk-width-expand">
+            <h4 class="uk-margin-remove-bottom">Task events</h4>
+            <p class="uk-text-meta uk-margin-remove-top">
+              "Each request is signed with an HMAC-SHA256 of "
+              <code>"<X-Webhook-Timestamp>.<body>"</code>
+              " in the X-Webhook-Signature header."
+            </p>
+          </div>
+        </div>
+
+        <NewWebhookForm/>
+
+        <Transition fallback=|| {
+            view! { <div uk-spinner="ratio: 2"></div> }
+        }>
+          <ul class="uk-list uk-list-divider uk-width-1-2">
+            {move || {
+                webhooks()
+                    .map(|hooks| {
+                        let hooks = hooks.unwrap_or_default();
+                        if hooks.is_empty() {
+                            view! {
+                              <li class="uk-text-center">
+                                <h4 class="uk-text-default uk-margin-remove-bottom">
+                                  "No webhook added yet"
+                                </h4>
+                              </li>
+                            }
+                                .into_view()
+                        } else {
+                            hooks
+                                .into_iter()
+                                .map(|hook| {
+                                    view! {
+                                      <li>
+                                        <div class="uk-grid-small uk-flex-middle" uk-grid>
+                                          <div class="uk-width-expand">
+                                            <h4 class="uk-text-default uk-margin-remove-bottom uk-text-break">
+                                              {hook.url}
+                                            </h4>
+                                            <p class="uk-text-meta uk-margin-remove-top">
+                                              {hook.events.join(", ")} " · secret: "
+                                              <code>{hook.secret}</code>
+                                            </p>
+                                          </div>
+                                          <div class="uk-width-auto">
+                                            <RemoveWebhookButton webhook_id=hook.id/>
+                                          </div>
+                                        </div>
+                                        <WebhookDeliveryLog webhook_id=hook.id/>
+                                      </li>
+                                    }
+                                })
+                                .collect_view()
+                        }
+                    })
+            }}
+
+          </ul>
+        </Transition>
+      </section>
+    }
+}
+
+#[island]
+fn NewWebhookForm() -> impl IntoView {
+    let add_webhook_action = create_server_action::<AddWebhook>();
+    let url = RwSignal::new(String::default());
+    let secret = RwSignal::new(String::default());
+    let events = RwSignal::new(EVENTS.map(|_| true));
+
+    let on_submit = move |ev: ev::SubmitEvent| {
+        ev.prevent_default();
+        add_webhook_action.dispatch(AddWebhook {
+            url: url(),
+            secret: secret(),
+            events: EVENTS
+                .iter()
+                .zip(events())
+                .filter(|(_, checked)| *checked)
+                .map(|(event, _)| event.to_string())
+                .collect(),
+        });
+    };
+
+    view! {
+      {move || {
+          if let Some(Ok(())) = add_webhook_action.value().get() {
+              window().location().reload().unwrap();
+          }
+      }}
+
+      <form on:submit=on_submit class="uk-width-1-2 uk-margin-bottom">
+        <div class="uk-grid-small" uk-grid>
+          <div class="uk-width-1-1">
+            <input
+              type="url"
+              placeholder="https://example.com/hooks/tasks"
+              aria-label="Webhook URL"
+              class="uk-input"
+              on:input=move |ev| url.set(event_target_value(&ev))
+              prop:value=url
+              required
+            />
+          </div>
+          <div class="uk-width-expand">
+            <input
+              type="text"
+              placeholder="Signing secret (leave empty to generate one)"
+              aria-label="Signing secret"
+              class="uk-input"
+              on:input=move |ev| secret.set(event_target_value(&ev))
+              prop:value=secret
+            />
+          </div>
+          <div class="uk-width-1-1">
+            {EVENTS
+                .iter()
+                .enumerate()
+                .map(|(i, event)| {
+                    view! {
+                      <label class="uk-margin-right">
+                        <input
+                          type="checkbox"
+                          class="uk-checkbox uk-margin-small-right"
+                          prop:checked=move || events()[i]
+                          on:change=move |ev| {
+                              events.update(|e| e[i] = event_target_checked(&ev))
+                          }
+                        />
+                        {*event}
+                      </label>
+                    }
+                })
+                .collect_view()}
+          </div>
+          <div class="uk-width-1-1 uk-text-right">
+            <button type="submit" class="uk-button uk-button-small uk-button-primary">
+              "Add webhook"
+            </button>
+          </div>
+          {move || {
+              if let Some(Err(e)) = add_webhook_action.value().get() {
+                  view! { <p class="uk-text-danger uk-width-1-1">{e.to_string()}</p> }.into_view()
+              } else {
+                  ().into_view()
+              }
+          }}
+
+        </div>
+      </form>
+    }
+}
+
+#[island]
+fn WebhookDeliveryLog(webhook_id: u64) -> impl IntoView {
+    let show = RwSignal::new(false);
+    let deliveries = create_local_resource(show, move |show| async move {
+        if show {
+            list_webhook_deliveries(webhook_id).await
+        } else {
+            Ok(vec![])
+        }
+    });
+
+    view! {
+      <button
+        type="button"
+        class="uk-button uk-button-text uk-margin-small-top"
+        on:click=move |_| show.update(|s| *s = !*s)
+      >
+        {move || if show() { "Hide deliveries" } else { "Show deliveries" }}
+      </button>
+      <Transition fallback=|| ()>
+        {move || {
+            deliveries()
+                .and_then(|d| d.ok())
+                .filter(|d| !d.is_empty())
+                .map(|d| {
+                    view! {
+                      <table class="uk-table uk-table-small uk-table-divider">
+                        <thead>
+                          <tr>
+                            <th>Event</th>
+                            <th>Status</th>
+                            <th>Attempts</th>
+                            <th>Response</th>
+                            <th>Queued at</th>
+                          </tr>
+                        </thead>
+                        <tbody>
+                          {d
+                              .into_iter()
+                              .map(|delivery| {
+                                  view! {
+                                    <tr>
+                                      <td>{delivery.event}</td>
+                                      <td>{delivery.status}</td>
+                                      <td>{delivery.attempts}</td>
+                                      <td class="uk-text-break">
+                                        {delivery
+                                            .last_error
+                                            .or(delivery.last_status_code.map(|c| c.to_string()))}
+                                      </td>
+                                      <td>{delivery.created_at}</td>
+                                    </tr>
+                                  }
+                              })
+                              .collect_view()}
+                        </tbody>
+                      </table>
+                    }
+                })
+        }}
+
+      </Transition>
+    }
+}
+
+#[island]
+fn RemoveWebhookButton(webhook_id: u64) -> impl IntoView {
+    let prefers_dark = RwSignal::new(crate::PrefersDark::check());
+    let remove_webhook_action = create_server_action::<RemoveWebhook>();
+
+    view! {
+      {move || {
+          if remove_webhook_action.version().get() > 0 {
+              window().location().reload().unwrap();
+          }
+      }}
+
+      <div id=&format!("confirm-delete-webhook_{webhook_id}") class="uk-flex-top" uk-modal>
+        <div class=move || {
+            format!(
+                "uk-modal-dialog uk-modal-body uk-margin-auto-vertical uk-background-{0} uk-{1} bg-toggle",
+                if prefers_dark() { "secondary" } else { "default" },
+                if prefers_dark() { "light" } else { "dark" },
+            )
+        }>
+          <h4 class="uk-modal-title uk-text-center">"Remove webhook?"</h4>
+          <p class="uk-text-center">
+            <button class="uk-button uk-button-default uk-modal-close" type="button">
+              "Cancel"
+            </button>
+            <button
+              type="button"
+              class="uk-button uk-button-danger uk-modal-close"
+              on:click=move |_| remove_webhook_action.dispatch(RemoveWebhook { webhook_id })
+            >
+              "Remove"
+            </button>
+          </p>
+        </div>
+      </div>
+
+      <button
+        uk-toggle=&format!("target: #confirm-delete-webhook_{webhook_id}")
+        class="uk-button uk-button-default"
+      >
+        Remove
+      </button>
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[path = ""]
+mod ssr {
+    pub use crate::compose_from_fn;
+    pub use crate::middlewares::require_login;
+}
+#[cfg(feature = "ssr")]
+pub use ssr::*;
+
+#[server(name = ListWebhooks, prefix = "/todo", endpoint = "webhooks", input = server_fn::codec::GetUrl, client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_login))]
+async fn list_webhooks() -> Result<Vec<Webhook>, ServerFnError> {
+    use crate::supabase::{AuthSession, Supabase};
+    use crate::webhooks::WebhookSchema;
+    use axum::Extension;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let identity = auth_session.user.unwrap().identity;
+
+    let query_response = supabase
+        .client
+        .query()
+        .from("webhooks")
+        .select("id,url,secret,events")
+        .eq("owner_id", identity.user_id.clone())
+        .order("created_at.asc")
+        .auth(&identity.auth_token)
+        .execute()
+        .await;
+
+    let hooks = supabase_rust::parse_response::<WebhookSchema>(query_response)
+        .await
+        .map_err(crate::supabase::map_err)?;
+
+    Ok(hooks
+        .into_iter()
+        .map(|hook| Webhook {
+            id: hook.id.unwrap_or_default(),
+            url: hook.url.unwrap_or_default(),
+            secret: hook.secret.unwrap_or_default(),
+            events: hook.events.unwrap_or_default(),
+        })
+        .collect())
+}
+
+#[server(name = AddWebhook, prefix = "/todo", endpoint = "add_webhook", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_login))]
+async fn add_webhook(
+    url: String,
+    mut secret: String,
+    events: Vec<String>,
+) -> Result<(), ServerFnError> {
+    use crate::supabase::{AuthSession, Supabase};
+    use crate::webhooks::{TaskEvent, WebhookSchema};
+    use axum::Extension;
+    use base64::prelude::{Engine as _, BASE64_URL_SAFE_NO_PAD};
+    use rand::RngCore;
+
+    let res_options = expect_context::<leptos_axum::ResponseOptions>();
+
+    // Deliveries come from the server, hooks can't point into its network
+    let valid_url = match crate::webhooks::check_url(&url) {
+        Ok(parsed) => crate::webhooks::resolve(&parsed).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = valid_url {
+        tracing::warn!("add_webhook rejected {url} - {e}");
+        res_options.set_status(http::StatusCode::BAD_REQUEST);
+        return Err(ServerFnError::Args("url".to_string()));
+    }
+    let known_events = TaskEvent::ALL.map(|e| e.as_str());
+    if events.is_empty() || events.iter().any(|e| !known_events.contains(&e.as_str())) {
+        res_options.set_status(http::StatusCode::BAD_REQUEST);
+        return Err(ServerFnError::Args("events".to_string()));
+    }
+
+    if secret.is_empty() {
+        let mut buf = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut buf);
+        secret = BASE64_URL_SAFE_NO_PAD.encode(buf);
+    }
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let identity = auth_session.user.unwrap().identity;
+
+    let webhook = serde_json::to_string(&WebhookSchema {
+        owner_id: Some(identity.user_id.clone()),
+        url: Some(url),
+        secret: Some(secret),
+        events: Some(events),
+        ..Default::default()
+    })
+    .unwrap();
+
+    let query_response = supabase
+        .client
+        .query()
+        .from("webhooks")
+        .insert(webhook)
+        .auth(&identity.auth_token)
+        .execute()
+        .await;
+
+    supabase_rust::parse_response::<WebhookSchema>(query_response)
+        .await
+        .map_err(crate::supabase::map_err)?;
+    Ok(())
+}
+
+#[server(name = RemoveWebhook, prefix = "/todo", endpoint = "remove_webhook", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_login))]
+async fn remove_webhook(webhook_id: u64) -> Result<(), ServerFnError> {
+    use crate::supabase::{AuthSession, Supabase};
+    use crate::webhooks::WebhookSchema;
+    use axum::Extension;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let identity = auth_session.user.unwrap().identity;
+
+    let query_response = supabase
+        .client
+        .query()
+        .from("webhooks")
+        .delete()
+        .eq("id", webhook_id.to_string())
+        .auth(&identity.auth_token)
+        .execute()
+        .await;
+
+    supabase_rust::parse_response::<WebhookSchema>(query_response)
+        .await
+        .map_err(crate::supabase::map_err)?;
+    Ok(())
+}
+
+#[server(name = ListWebhookDeliveries, prefix = "/todo", endpoint = "webhook_deliveries", input = server_fn::codec::GetUrl, client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_login))]
+async fn list_webhook_deliveries(webhook_id: u64) -> Result<Vec<WebhookDelivery>, ServerFnError> {
+    use crate::supabase::{AuthSession, Supabase};
+    use crate::webhooks::DeliverySchema;
+    use axum::Extension;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let supabase = expect_context::<Supabase>();
+    let identity = auth_session.user.unwrap().identity;
+
+    let query_response = supabase
+        .client
+        .query()
+        .from("webhook_deliveries")
+        .select("id,event,status,attempts,last_status_code,last_error,created_at")
+        .eq("webhook_id", webhook_id.to_string())
+        .order("created_at.desc")
+        .limit(20)
+        .auth(&identity.auth_token)
+        .execute()
+        .await;
+
+    let deliveries = supabase_rust::parse_response::<DeliverySchema>(query_response)
+        .await
+        .map_err(crate::supabase::map_err)?;
+
+    Ok(deliveries
+        .into_iter()
+        .map(|d| WebhookDelivery {
+            id: d.id.unwrap_or_default(),
+            event: d.event.unwrap_or_default(),
+            status: d.status.unwrap_or_default(),
+            attempts: d.attempts.unwrap_or_default(),
+            last_status_code: d.last_status_code,
+            last_error: d.last_error,
+            created_at: d.created_at.unwrap_or_default(),
+        })
+        .collect())
+}
@@ -2,32 +2,49 @@ use leptos::*;
 use leptos_meta::Title;
 use leptos_router::ActionForm;
 
-use crate::components::auth::AuthProvider;
+use crate::components::auth::{AuthProvider, PasskeyVerify};
+use crate::components::csrf::CsrfField;
 
 #[component]
 pub fn VerifyMultiFactorAuth() -> impl IntoView {
     let callback = RwSignal::new(String::default());
+    let has_mfa = RwSignal::new(true);
     #[cfg(feature = "ssr")]
     {
+        use crate::supabase::AuthSession;
         use axum::extract::RawQuery;
         if let Some(rawquery) = use_context::<std::sync::Arc<RawQuery>>() {
             let queries = crate::parse_query_string(&rawquery);
             if let Some(cb) = queries.get("cb") {
-                callback.set(cb.to_string());
+                callback.set(crate::components::auth::redirect::safe_callback(cb));
             }
         }
+        if let Some(user) = use_context::<AuthSession>().and_then(|s| s.user) {
+            has_mfa.set(user.identity.has_mfa);
+        }
     }
+
+    // Without a second factor the password is what a sensitive action asks for again
+    let verification = if has_mfa() {
+        view! {
+          <VerifyMFAInput callback=callback()/>
+          <hr class="uk-divider-small"/>
+          <PasskeyVerify callback=callback()/>
+        }
+        .into_view()
+    } else {
+        view! { <ConfirmPasswordInput callback=callback()/> }.into_view()
+    };
+
     view! {
       <AuthProvider>
         <Title text="Verify Authenticator - Supabase Leptos"/>
         <hr class="uk-divider-small"/>
         <div class="uk-container uk-margin-top">
           <h4 class="uk-heading-line uk-text-center">
-            <span>"Two-Factor Verification"</span>
+            <span>{if has_mfa() { "Two-Factor Verification" } else { "Confirm It's You" }}</span>
           </h4>
-          <div class="uk-card uk-card-body ">
-            <VerifyMFAInput callback=callback()/>
-          </div>
+          <div class="uk-card uk-card-body ">{verification}</div>
         </div>
       </AuthProvider>
     }
@@ -40,6 +57,7 @@ fn VerifyMFAInput(callback: String) -> impl IntoView {
 
     view! {
       <ActionForm action=verify_mfa_action>
+        <CsrfField/>
         <div class="uk-flex uk-flex-column uk-flex-around uk-flex-middle">
           <input
             id="callback"
@@ -51,7 +69,7 @@ fn VerifyMFAInput(callback: String) -> impl IntoView {
           />
           <div class="uk-form-stacked">
             <label class="uk-form-label" for="verification-code">
-              "Authenticator Verification Code"
+              "Authenticator or Recovery Code"
             </label>
             <input
               id="verification-code"
@@ -65,6 +83,14 @@ fn VerifyMFAInput(callback: String) -> impl IntoView {
               required
             />
           </div>
+          <label class="uk-margin">
+            <input
+              type="checkbox"
+              class="uk-checkbox uk-margin-small-right"
+              name="remember_device"
+            />
+            "Trust this browser for 30 days"
+          </label>
           <div class="uk-flex uk-flex-between uk-width-2-3">
             <button
               type="button"
@@ -85,28 +111,115 @@ fn VerifyMFAInput(callback: String) -> impl IntoView {
     }
 }
 
+#[island]
+fn ConfirmPasswordInput(callback: String) -> impl IntoView {
+    let confirm_password_action = create_server_action::<ConfirmPassword>();
+
+    view! {
+      <ActionForm action=confirm_password_action>
+        <CsrfField/>
+        <div class="uk-flex uk-flex-column uk-flex-around uk-flex-middle">
+          <input type="hidden" name="callback" value=callback/>
+          <div class="uk-form-stacked uk-margin-bottom">
+            <label class="uk-form-label" for="confirm-password">
+              "Password"
+            </label>
+            <input
+              id="confirm-password"
+              type="password"
+              placeholder="Current password"
+              aria-label="Current password"
+              class="uk-input uk-form-width-medium"
+              name="password"
+              required
+            />
+          </div>
+          {move || {
+              confirm_password_action
+                  .value()
+                  .get()
+                  .and_then(Result::err)
+                  .map(|e| view! { <p class="uk-text-danger">{e.to_string()}</p> })
+          }}
+          <div class="uk-flex uk-flex-between uk-width-2-3">
+            <button
+              type="button"
+              class="uk-button uk-button-default"
+              on:click=move |_| {
+                  window().history().unwrap().back().expect("THE TIME MACHINE BROKEN!")
+              }
+            >
+
+              Cancel
+            </button>
+            <button type="submit" class="uk-button uk-button-primary">
+              "Confirm"
+            </button>
+          </div>
+        </div>
+      </ActionForm>
+    }
+}
+
 #[cfg(feature = "ssr")]
 #[path = ""]
 mod ssr {
     pub use crate::compose_from_fn;
-    pub use crate::middlewares::require_login;
+    pub use crate::middlewares::{rate_limit, require_login, MFA_LIMIT, PASSWORD_LIMIT};
 }
 #[cfg(feature = "ssr")]
 pub use ssr::*;
 
-#[server(name = VerifyMFA, prefix = "/auth", endpoint = "verify_mfa")]
-#[middleware(compose_from_fn!(require_login))]
-async fn verify_mfa(mut code: String, callback: String) -> Result<(), ServerFnError> {
+#[server(name = VerifyMFA, prefix = "/auth", endpoint = "verify_mfa", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_login, rate_limit(&MFA_LIMIT)))]
+async fn verify_mfa(
+    mut code: String,
+    callback: String,
+    remember_device: Option<String>,
+) -> Result<(), ServerFnError> {
     use crate::components::auth::filter_verified_factors;
+    use crate::components::auth::{redirect, trusted_device};
+    use crate::middlewares::RateLimitGuard;
     use crate::supabase::{AuthSession, Supabase};
     use axum::Extension;
     use axum_extra::extract::CookieJar;
 
     let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
-    let identity = auth_session.user.unwrap().identity;
+    let Extension(rate_limit) = leptos_axum::extract::<Extension<RateLimitGuard>>().await?;
+    let mut identity = auth_session.user.unwrap().identity;
     let supabase = expect_context::<Supabase>();
 
+    let headers = leptos_axum::extract::<http::HeaderMap>().await?;
+    let cookies = CookieJar::from_headers(&headers);
+    let session_id = cookies.get("auth").unwrap().value();
+
     let code: String = code.split_whitespace().collect();
+
+    // Authenticator codes are 6 digits, anything else is taken for a recovery code
+    if code.len() != 6 || !code.chars().all(|c| c.is_ascii_digit()) {
+        let redeemed = supabase
+            .redeem_recovery_code(&identity.user_id, &code)
+            .await
+            .map_err(crate::supabase::map_err)?;
+        if !redeemed {
+            expect_context::<leptos_axum::ResponseOptions>()
+                .set_status(http::StatusCode::UNAUTHORIZED);
+            return Err(ServerFnError::ServerError(
+                "The recovery code is invalid or was already used".to_string(),
+            ));
+        }
+
+        // Recovery codes aren't known to Supabase, the session is raised in the app only
+        rate_limit.record_success().await;
+        if remember_device.is_some() {
+            trusted_device::remember(&identity.user_id).await?;
+        }
+        identity.verify_second_factor();
+        supabase.update_identity(session_id, identity).await;
+        redirect::redirect(&callback);
+        return Ok(());
+    }
+
     let user_factors = supabase
         .client
         .mfa_list_factors(identity.user_id.clone(), supabase.admin_token())
@@ -115,39 +228,73 @@ async fn verify_mfa(mut code: String, callback: String) -> Result<(), ServerFnEr
 
     let factors = filter_verified_factors(user_factors, &identity.auth_token).await;
 
-    let access_token = {
-        let mut token = None;
-        for factor in &factors {
-            match supabase
-                .client
-                .mfa_challenge_and_verify(factor.id.clone(), code.clone(), &identity.auth_token)
-                .await
-            {
-                Ok(t) => {
-                    token = Some(t);
-                    break;
-                }
-                Err(_) => continue,
-            };
+    let mut access_token = None;
+    for factor in &factors {
+        if let Ok(token) = supabase
+            .client
+            .mfa_challenge_and_verify(factor.id.clone(), code.clone(), &identity.auth_token)
+            .await
+        {
+            access_token = Some(token);
+            break;
         }
-        token
     }
-    .ok_or_else::<ServerFnError, _>(|| {
-        ServerFnError::ServerError(
+
+    // One failure however many factors were tried with the code
+    let Some(access_token) = access_token else {
+        expect_context::<leptos_axum::ResponseOptions>().set_status(http::StatusCode::UNAUTHORIZED);
+        return Err(ServerFnError::ServerError(
             "One Time Password verification failed. \
                 The code didn't match any associated authenticator"
                 .to_string(),
-        )
-    })?;
+        ));
+    };
+    rate_limit.record_success().await;
+    if remember_device.is_some() {
+        trusted_device::remember(&identity.user_id).await?;
+    }
 
     // Update the user's session with the new access token that has an elevated AAL
-    let headers = leptos_axum::extract::<http::HeaderMap>().await?;
-    let cookies = CookieJar::from_headers(&headers);
-    let session_id = cookies.get("auth").unwrap().value();
     supabase
         .update_assurance_level(session_id, identity.clone(), access_token)
         .await;
 
-    leptos_axum::redirect(&callback);
+    redirect::redirect(&callback);
+    Ok(())
+}
+
+/// Counts as having just signed in for `require_fresh_auth`, users with a second factor verify
+/// that instead
+#[server(name = ConfirmPassword, prefix = "/auth", endpoint = "confirm_password", client = crate::components::csrf::CsrfClient)]
+#[middleware(compose_from_fn!(require_login, rate_limit(&PASSWORD_LIMIT)))]
+async fn confirm_password(password: String, callback: String) -> Result<(), ServerFnError> {
+    use crate::components::auth::{redirect, verify_password};
+    use crate::middlewares::RateLimitGuard;
+    use crate::supabase::{AuthSession, Supabase};
+    use axum::Extension;
+    use axum_extra::extract::CookieJar;
+
+    let Extension(auth_session) = leptos_axum::extract::<Extension<AuthSession>>().await?;
+    let Extension(rate_limit) = leptos_axum::extract::<Extension<RateLimitGuard>>().await?;
+    let mut identity = auth_session.user.unwrap().identity;
+    let supabase = expect_context::<Supabase>();
+
+    // The session's email is stale after an email change
+    let email = supabase
+        .fetch_user(&identity.auth_token)
+        .await
+        .map_err(crate::supabase::map_err)?
+        .email
+        .unwrap_or(identity.email.clone());
+    verify_password(&email, &password).await?;
+    rate_limit.record_success().await;
+
+    let headers = leptos_axum::extract::<http::HeaderMap>().await?;
+    let cookies = CookieJar::from_headers(&headers);
+    let session_id = cookies.get("auth").unwrap().value();
+    identity.verified_at = Some(time::OffsetDateTime::now_utc());
+    supabase.update_identity(session_id, identity).await;
+
+    redirect::redirect(&callback);
     Ok(())
 }
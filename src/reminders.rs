@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use leptos::serde_json;
+use time::format_description::well_known::Iso8601;
+use time::OffsetDateTime;
+
+use crate::notifications::{Notifier, Reminder};
+use crate::supabase::{IdentityData, Supabase};
+
+/// Lead time applied to new reminders until the user picks their own in `UserSettings`
+pub const DEFAULT_LEAD_MINUTES: i64 = 15;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default)]
+pub struct ReminderSchema {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remind_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notify_at: Option<String>,
+    pub sent_at: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default)]
+pub struct UserSettingsSchema {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reminder_lead_minutes: Option<i64>,
+}
+
+/// The user's reminder lead time, falls back to [`DEFAULT_LEAD_MINUTES`] if it was never set
+pub async fn lead_minutes(supabase: &Supabase, identity: &IdentityData) -> i64 {
+    let query = supabase
+        .client
+        .query()
+        .from("user_settings")
+        .select("reminder_lead_minutes")
+        .eq("user_id", identity.user_id.clone())
+        .auth(&identity.auth_token)
+        .execute()
+        .await;
+
+    supabase_rust::parse_response::<UserSettingsSchema>(query)
+        .await
+        .ok()
+        .and_then(|settings| settings.into_iter().next())
+        .and_then(|settings| settings.reminder_lead_minutes)
+        .unwrap_or(DEFAULT_LEAD_MINUTES)
+}
+
+pub async fn set_lead_minutes(
+    supabase: &Supabase,
+    identity: &IdentityData,
+    minutes: i64,
+) -> Result<(), supabase_rust::errors::Error> {
+    let settings = serde_json::to_string(&UserSettingsSchema {
+        user_id: Some(identity.user_id.clone()),
+        reminder_lead_minutes: Some(minutes),
+    })
+    .unwrap();
+
+    let query = supabase
+        .client
+        .query()
+        .from("user_settings")
+        .upsert(settings)
+        .on_conflict("user_id")
+        .auth(&identity.auth_token)
+        .execute()
+        .await;
+
+    supabase_rust::parse_response::<UserSettingsSchema>(query).await?;
+    Ok(())
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct DueReminderSchema {
+    id: u64,
+    task_id: u32,
+    author_id: String,
+    remind_at: String,
+    task: Option<DueTaskSchema>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct DueTaskSchema {
+    title: String,
+    description: Option<String>,
+}
+
+/// Scans the `reminders` table every `every` and hands due reminders to the notifiers.
+/// A reminder is marked as sent once at least one notifier delivered it,
+/// otherwise it's picked up again on the next scan. Reminders only ever go to the account
+/// email of their author, looked up when they're due.
+pub async fn run_scheduler(supabase: Supabase, notifiers: Vec<Arc<dyn Notifier>>, every: Duration) {
+    let mut interval = tokio::time::interval(every);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        interval.tick().await;
+        if notifiers.is_empty() {
+            continue;
+        }
+        if let Err(e) = dispatch_due(&supabase, &notifiers).await {
+            tracing::error!("\nReminder scheduler - {e:?}");
+        }
+    }
+}
+
+async fn dispatch_due(
+    supabase: &Supabase,
+    notifiers: &[Arc<dyn Notifier>],
+) -> Result<(), supabase_rust::errors::Error> {
+    let now = OffsetDateTime::now_utc();
+
+    let query = supabase
+        .client
+        .query()
+        .from("reminders")
+        .select("id,task_id,author_id,remind_at,task:tasks(title,description)")
+        .is("sent_at", "null")
+        .lte("notify_at", now.format(&Iso8601::DEFAULT).unwrap())
+        .auth(supabase.admin_token())
+        .execute()
+        .await;
+
+    let due = supabase_rust::parse_response::<DueReminderSchema>(query).await?;
+    let mut recipients: HashMap<String, Option<String>> = HashMap::new();
+
+    for row in due {
+        let Some(task) = row.task else {
+            continue;
+        };
+        let Ok(remind_at) = OffsetDateTime::parse(&row.remind_at, &Iso8601::DEFAULT) else {
+            tracing::error!(
+                "\nReminder {} has an invalid remind_at: {}",
+                row.id,
+                row.remind_at
+            );
+            continue;
+        };
+        let email = match recipients.get(&row.author_id) {
+            Some(email) => email.clone(),
+            None => {
+                let email = match supabase.admin_get_user(&row.author_id).await {
+                    Ok(user) => user.email,
+                    Err(e) => {
+                        tracing::error!("\nReminder {} - author lookup failed: {e:?}", row.id);
+                        continue;
+                    }
+                };
+                recipients.insert(row.author_id.clone(), email.clone());
+                email
+            }
+        };
+        let Some(email) = email else {
+            tracing::warn!("\nReminder {} - the author has no email", row.id);
+            continue;
+        };
+        let reminder = Reminder {
+            task_id: row.task_id,
+            email,
+            title: task.title,
+            description: task.description.unwrap_or_default(),
+            remind_at,
+        };
+
+        let mut delivered = false;
+        for notifier in notifiers {
+            match notifier.notify(&reminder).await {
+                Ok(()) => delivered = true,
+                Err(e) => tracing::error!("\nReminder {} via {} - {e}", row.id, notifier.name()),
+            }
+        }
+
+        if delivered {
+            let _ = supabase
+                .client
+                .query()
+                .from("reminders")
+                .update(
+                    serde_json::json!({
+                        "sent_at": OffsetDateTime::now_utc().format(&Iso8601::DEFAULT).unwrap()
+                    })
+                    .to_string(),
+                )
+                .eq("id", row.id.to_string())
+                .auth(supabase.admin_token())
+                .execute()
+                .await;
+        }
+    }
+    Ok(())
+}
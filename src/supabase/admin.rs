@@ -0,0 +1,265 @@
+//! Operator actions on other users' accounts, all with the service key and all audited
+
+use leptos::serde_json::{self, json, Value};
+use reqwest::Method;
+use supabase_rust::errors::{AuthError, Error, ErrorKind};
+use supabase_rust::schema::MFAFactorStatus;
+use time::format_description::well_known::Iso8601;
+use time::OffsetDateTime;
+use webauthn_rs::prelude::Uuid;
+
+use super::SupabaseBackend;
+
+/// How long a disabled account stays banned, GoTrue takes no "forever"
+const DISABLED_BAN_DURATION: &str = "876000h";
+
+/// The parts of the GoTrue admin user object the dashboard shows
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct AdminUser {
+    pub id: String,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub last_sign_in_at: Option<String>,
+    /// Set while the account is disabled
+    #[serde(default)]
+    pub banned_until: Option<String>,
+    #[serde(default)]
+    pub app_metadata: Value,
+}
+
+impl AdminUser {
+    pub fn role(&self) -> Option<String> {
+        self.app_metadata.get("role")?.as_str().map(str::to_string)
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.banned_until.as_deref().is_some_and(|until| {
+            OffsetDateTime::parse(until, &Iso8601::DEFAULT)
+                .is_ok_and(|until| until > OffsetDateTime::now_utc())
+        })
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct AdminAuditSchema {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub admin_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_user_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+}
+
+/// Ids end up in GoTrue paths, anything but a UUID is turned away before that. Only the
+/// lowercase hyphenated form GoTrue hands out is taken, so ids compare as plain strings.
+pub(crate) fn user_uuid(user_id: &str) -> Result<Uuid, Error> {
+    Uuid::parse_str(user_id)
+        .ok()
+        .filter(|uuid| uuid.to_string() == user_id)
+        .ok_or_else(|| Error {
+            http_status: 400,
+            kind: ErrorKind::Auth(AuthError {
+                msg: Some("Invalid user id".to_string()),
+                ..Default::default()
+            }),
+        })
+}
+
+impl SupabaseBackend {
+    /// One page of users, `page` counting from 1. `search` matches the email.
+    pub async fn admin_list_users(
+        &self,
+        page: u32,
+        per_page: u32,
+        search: Option<&str>,
+    ) -> Result<Vec<AdminUser>, Error> {
+        let page = page.max(1).to_string();
+        let per_page = per_page.to_string();
+        let mut query = vec![("page", page.as_str()), ("per_page", per_page.as_str())];
+        if let Some(search) = search {
+            query.push(("filter", search));
+        }
+
+        let users = self
+            .gotrue(
+                Method::GET,
+                "/admin/users",
+                &query,
+                Some(self.admin_token()),
+                None,
+            )
+            .await?;
+        Ok(users
+            .get("users")
+            .cloned()
+            .and_then(|users| serde_json::from_value(users).ok())
+            .unwrap_or_default())
+    }
+
+    pub async fn admin_get_user(&self, user_id: &str) -> Result<AdminUser, Error> {
+        let user_id = user_uuid(user_id)?;
+        let user = self
+            .gotrue(
+                Method::GET,
+                &format!("/admin/users/{user_id}"),
+                &[],
+                Some(self.admin_token()),
+                None,
+            )
+            .await?;
+        Ok(serde_json::from_value(user).unwrap_or_default())
+    }
+
+    /// Bans the account so it can't sign in or refresh, or lifts the ban
+    pub async fn admin_set_disabled(&self, user_id: &str, disabled: bool) -> Result<(), Error> {
+        let user_id = user_uuid(user_id)?;
+        let ban_duration = if disabled {
+            DISABLED_BAN_DURATION
+        } else {
+            "none"
+        };
+        self.gotrue(
+            Method::PUT,
+            &format!("/admin/users/{user_id}"),
+            &[],
+            Some(self.admin_token()),
+            Some(json!({ "ban_duration": ban_duration })),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// Names of the verified authenticators of `user_id`
+    pub async fn admin_list_factors(&self, user_id: &str) -> Result<Vec<String>, Error> {
+        let factors = self
+            .client
+            .mfa_list_factors(user_id.to_string(), self.admin_token())
+            .await?;
+        Ok(factors
+            .into_iter()
+            .filter(|factor| factor.status != MFAFactorStatus::Unverified)
+            .map(|factor| factor.friendly_name.unwrap_or(factor.id))
+            .collect())
+    }
+
+    /// Removes every second factor of `user_id`: authenticators, passkeys, recovery codes
+    /// and trusted browsers
+    pub async fn admin_reset_mfa(&self, user_id: &str) -> Result<(), Error> {
+        let user_id = &user_uuid(user_id)?.to_string();
+        let factors = self
+            .client
+            .mfa_list_factors(user_id.to_string(), self.admin_token())
+            .await?;
+        for factor in factors {
+            self.gotrue(
+                Method::DELETE,
+                &format!("/admin/users/{user_id}/factors/{}", factor.id),
+                &[],
+                Some(self.admin_token()),
+                None,
+            )
+            .await?;
+        }
+
+        for table in ["passkeys", "mfa_recovery_codes"] {
+            let query = self
+                .client
+                .query()
+                .from(table)
+                .delete()
+                .eq("user_id", user_id)
+                .auth(self.admin_token())
+                .execute()
+                .await;
+            supabase_rust::parse_response::<Value>(query).await?;
+        }
+        self.revoke_trusted_devices(user_id, None).await
+    }
+
+    /// Writes an operator action to the audit table, `target_user_id` being the account acted on
+    pub async fn record_admin_action(
+        &self,
+        admin_id: &str,
+        action: &str,
+        target_user_id: &str,
+        details: Value,
+    ) -> Result<(), Error> {
+        let entry = serde_json::to_string(&AdminAuditSchema {
+            admin_id: Some(admin_id.to_string()),
+            action: Some(action.to_string()),
+            target_user_id: Some(target_user_id.to_string()),
+            details: Some(details),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let query = self
+            .client
+            .query()
+            .from("admin_audit_log")
+            .insert(entry)
+            .auth(self.admin_token())
+            .execute()
+            .await;
+        supabase_rust::parse_response::<AdminAuditSchema>(query)
+            .await
+            .map(|_| ())
+    }
+
+    /// Latest audit entries, only those about `target_user_id` when given
+    pub async fn list_admin_actions(
+        &self,
+        target_user_id: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<AdminAuditSchema>, Error> {
+        let mut query = self
+            .client
+            .query()
+            .from("admin_audit_log")
+            .select("id,admin_id,action,target_user_id,details,created_at");
+        if let Some(target_user_id) = target_user_id {
+            query = query.eq("target_user_id", target_user_id);
+        }
+
+        let query = query
+            .order("created_at.desc")
+            .limit(limit)
+            .auth(self.admin_token())
+            .execute()
+            .await;
+        supabase_rust::parse_response::<AdminAuditSchema>(query).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_ids_have_to_be_uuids() {
+        let id = "2d4bd2a4-4b6f-4b47-8f2e-0d5fb0a5a4c1";
+        assert_eq!(user_uuid(id).unwrap().to_string(), id);
+
+        for id in [
+            "",
+            "me",
+            "../settings",
+            "2d4bd2a4-4b6f-4b47-8f2e-0d5fb0a5a4c1/factors",
+            "2D4BD2A4-4B6F-4B47-8F2E-0D5FB0A5A4C1",
+            "2d4bd2a44b6f4b478f2e0d5fb0a5a4c1",
+            "{2d4bd2a4-4b6f-4b47-8f2e-0d5fb0a5a4c1}",
+        ] {
+            let e = user_uuid(id).unwrap_err();
+            assert_eq!(e.http_status, 400, "{id}");
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use std::net::SocketAddr;
+
+use http::HeaderMap;
+
+/// Client details captured at sign in, shown in the user's list of devices
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Default)]
+pub struct DeviceInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+impl DeviceInfo {
+    /// Key of the device in the session data, read back by `SessionStore::create`
+    pub const SESSION_KEY: &'static str = "device";
+
+    /// Forwarding headers are taken at face value, the address is only ever displayed
+    pub fn from_request(headers: &HeaderMap, peer: Option<SocketAddr>) -> Self {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+        };
+
+        let forwarded_for = header("x-forwarded-for")
+            .and_then(|value| value.split(',').next())
+            .map(str::trim);
+        let ip_address = forwarded_for
+            .or_else(|| header("x-real-ip"))
+            .map(str::to_string)
+            .or_else(|| peer.map(|addr| addr.ip().to_string()));
+
+        Self {
+            user_agent: header("user-agent").map(|ua| ua.chars().take(512).collect()),
+            ip_address,
+        }
+    }
+}
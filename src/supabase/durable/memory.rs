@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use axum::async_trait;
+use supabase_rust::errors::Error;
+use time::OffsetDateTime;
+
+use super::{format_date, DurableSessions, StoredSession};
+use crate::supabase::UserSession;
+
+#[derive(Debug)]
+struct MemorySession {
+    session: StoredSession,
+    created_at: OffsetDateTime,
+    refreshed_at: Option<OffsetDateTime>,
+}
+
+/// Sessions in a map, lost on restart. For running without a database and for tests.
+#[derive(Debug, Default)]
+pub struct MemorySessions {
+    /// Ordered by id for `page`
+    sessions: Mutex<BTreeMap<String, MemorySession>>,
+}
+
+#[async_trait]
+impl DurableSessions for MemorySessions {
+    async fn auth_session_expiry(&self, _sb_id: &str) -> Result<Option<OffsetDateTime>, Error> {
+        Ok(None)
+    }
+
+    async fn create(&self, session: StoredSession) -> Result<(), Error> {
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.insert(
+            session.id.clone(),
+            MemorySession {
+                session,
+                created_at: OffsetDateTime::now_utc(),
+                refreshed_at: None,
+            },
+        );
+        Ok(())
+    }
+
+    async fn save(&self, id: &str, data: &str, expiry_date: OffsetDateTime) -> Result<(), Error> {
+        if let Some(stored) = self.sessions.lock().unwrap().get_mut(id) {
+            stored.session.data = data.to_string();
+            stored.session.expiry_date = expiry_date;
+            stored.refreshed_at = Some(OffsetDateTime::now_utc());
+        }
+        Ok(())
+    }
+
+    async fn load(&self, id: &str) -> Result<Option<(String, Option<OffsetDateTime>)>, Error> {
+        let sessions = self.sessions.lock().unwrap();
+        Ok(sessions.get(id).map(|stored| {
+            (
+                stored.session.data.clone(),
+                Some(stored.session.expiry_date),
+            )
+        }))
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), Error> {
+        self.sessions.lock().unwrap().remove(id);
+        Ok(())
+    }
+
+    async fn list_user(&self, user_id: &str) -> Result<Vec<UserSession>, Error> {
+        let sessions = self.sessions.lock().unwrap();
+        let mut listed: Vec<_> = sessions
+            .values()
+            .filter(|stored| stored.session.user_id == user_id)
+            .collect();
+        listed.sort_by_key(|stored| std::cmp::Reverse(stored.refreshed_at));
+
+        Ok(listed
+            .into_iter()
+            .map(|stored| UserSession {
+                id: stored.session.id.clone(),
+                sb_id: stored.session.sb_id.clone(),
+                created_at: format_date(Some(stored.created_at)),
+                refreshed_at: format_date(stored.refreshed_at),
+                expiry_date: format_date(Some(stored.session.expiry_date)),
+                user_agent: stored.session.user_agent.clone(),
+                ip_address: stored.session.ip_address.clone(),
+            })
+            .collect())
+    }
+
+    async fn page(&self, after: &str, limit: usize) -> Result<Vec<(String, String)>, Error> {
+        use std::ops::Bound;
+
+        let sessions = self.sessions.lock().unwrap();
+        Ok(sessions
+            .range::<str, _>((Bound::Excluded(after), Bound::Unbounded))
+            .take(limit)
+            .map(|(id, stored)| (id.clone(), stored.session.data.clone()))
+            .collect())
+    }
+
+    async fn replace_data(&self, id: &str, data: &str) -> Result<(), Error> {
+        if let Some(stored) = self.sessions.lock().unwrap().get_mut(id) {
+            stored.session.data = data.to_string();
+        }
+        Ok(())
+    }
+
+    /// Sessions here always have an expiry
+    async fn delete_expired(
+        &self,
+        now: OffsetDateTime,
+        _created_before: OffsetDateTime,
+    ) -> Result<Vec<String>, Error> {
+        let mut expired = Vec::new();
+        self.sessions.lock().unwrap().retain(|id, stored| {
+            let live = stored.session.expiry_date > now;
+            if !live {
+                expired.push(id.clone());
+            }
+            live
+        });
+        Ok(expired)
+    }
+}
@@ -0,0 +1,106 @@
+//! Where sessions outlive the in-memory cache. `SESSION_STORE` picks the tier: the Supabase
+//! `sessions` table through PostgREST by default, the same table straight from Postgres,
+//! Redis, or memory for local development and tests.
+
+mod memory;
+#[cfg(feature = "session-postgres")]
+mod postgres;
+#[cfg(feature = "session-redis")]
+mod redis;
+mod supabase;
+
+use std::sync::Arc;
+
+use axum::async_trait;
+use supabase_rust::errors::Error;
+use time::OffsetDateTime;
+
+pub use self::memory::MemorySessions;
+#[cfg(feature = "session-postgres")]
+pub use self::postgres::PostgresSessions;
+#[cfg(feature = "session-redis")]
+pub use self::redis::RedisSessions;
+pub use self::supabase::SupabaseSessions;
+use super::UserSession;
+
+/// A session as the durable tier keeps it, `data` already sealed by the session cipher
+#[derive(Debug, Clone)]
+pub struct StoredSession {
+    pub id: String,
+    /// Id of the matching Supabase auth session
+    pub sb_id: String,
+    pub user_id: String,
+    pub data: String,
+    pub expiry_date: OffsetDateTime,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+#[async_trait]
+pub trait DurableSessions: std::fmt::Debug + Send + Sync {
+    /// Expiry Supabase gave the auth session `sb_id` when it created it. Tiers that are told
+    /// about sessions only once signed in have nothing to look up and return `None`.
+    async fn auth_session_expiry(&self, sb_id: &str) -> Result<Option<OffsetDateTime>, Error>;
+    async fn create(&self, session: StoredSession) -> Result<(), Error>;
+    /// Replaces the data and expiry of `id`, marking it refreshed
+    async fn save(&self, id: &str, data: &str, expiry_date: OffsetDateTime) -> Result<(), Error>;
+    /// The data and expiry of `id`
+    async fn load(&self, id: &str) -> Result<Option<(String, Option<OffsetDateTime>)>, Error>;
+    async fn delete(&self, id: &str) -> Result<(), Error>;
+    /// Sessions of `user_id`, most recently refreshed first
+    async fn list_user(&self, user_id: &str) -> Result<Vec<UserSession>, Error>;
+    /// Up to `limit` sessions with an id past `after` in id order, as `(id, data)`
+    async fn page(&self, after: &str, limit: usize) -> Result<Vec<(String, String)>, Error>;
+    /// Replaces the data of `id` and nothing else, for re-encryption
+    async fn replace_data(&self, id: &str, data: &str) -> Result<(), Error>;
+    /// Deletes the sessions that expired before `now`, and those without an expiry created
+    /// before `created_before`, returning their ids. Supabase gives auth sessions without a
+    /// time-box no `not_after`, their rows only get an expiry once the app saves them.
+    async fn delete_expired(
+        &self,
+        now: OffsetDateTime,
+        created_before: OffsetDateTime,
+    ) -> Result<Vec<String>, Error>;
+}
+
+/// The tier named by `SESSION_STORE`: `supabase`, `postgres` with `SESSION_DATABASE_URL`,
+/// `redis` with `SESSION_REDIS_URL`, or `memory`
+pub async fn from_env() -> Arc<dyn DurableSessions> {
+    let tier = std::env::var("SESSION_STORE").unwrap_or("supabase".to_string());
+    match tier.as_str() {
+        "supabase" => Arc::new(SupabaseSessions::from_env()),
+        "memory" => {
+            tracing::warn!("Sessions are kept in memory, they end when the server stops");
+            Arc::new(MemorySessions::default())
+        }
+        #[cfg(feature = "session-postgres")]
+        "postgres" => Arc::new(PostgresSessions::from_env().await),
+        #[cfg(feature = "session-redis")]
+        "redis" => Arc::new(RedisSessions::from_env().await),
+        other => panic!(
+            "SESSION_STORE={other} isn't available, build with the session-{other} feature \
+                or use supabase or memory"
+        ),
+    }
+}
+
+/// Errors of the Postgres and Redis tiers, in the error type the Supabase calls use
+#[cfg(any(feature = "session-postgres", feature = "session-redis"))]
+fn storage_error(e: impl std::fmt::Display) -> Error {
+    use supabase_rust::errors::{ErrorKind, PostgrestError};
+
+    tracing::error!("Session storage - {e}");
+    Error {
+        http_status: 500,
+        kind: ErrorKind::Postgrest(PostgrestError {
+            message: e.to_string(),
+            ..Default::default()
+        }),
+    }
+}
+
+/// Dates of the session list as PostgREST hands them over
+fn format_date(date: Option<OffsetDateTime>) -> Option<String> {
+    use time::format_description::well_known::Iso8601;
+    date.and_then(|date| date.format(&Iso8601::DEFAULT).ok())
+}
@@ -0,0 +1,192 @@
+use axum::async_trait;
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use supabase_rust::errors::Error;
+use time::OffsetDateTime;
+
+use super::{format_date, storage_error, DurableSessions, StoredSession};
+use crate::supabase::UserSession;
+
+/// Connections of the pool, sessions are read and written on most requests
+const MAX_CONNECTIONS: u32 = 10;
+
+/// The `public.sessions` table of `supabase.sql` straight from Postgres, either the
+/// Supabase database or a local one with the table minus its `auth.sessions` foreign key
+#[derive(Clone, Debug)]
+pub struct PostgresSessions {
+    pool: PgPool,
+}
+
+type SessionRow = (
+    String,
+    String,
+    Option<OffsetDateTime>,
+    Option<OffsetDateTime>,
+    Option<OffsetDateTime>,
+    Option<String>,
+    Option<String>,
+);
+
+impl PostgresSessions {
+    pub async fn from_env() -> Self {
+        let url =
+            std::env::var("SESSION_DATABASE_URL").expect("env var SESSION_DATABASE_URL not set");
+        let pool = PgPoolOptions::new()
+            .max_connections(MAX_CONNECTIONS)
+            .connect(&url)
+            .await
+            .expect("SESSION_DATABASE_URL can't be connected to");
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl DurableSessions for PostgresSessions {
+    async fn auth_session_expiry(&self, sb_id: &str) -> Result<Option<OffsetDateTime>, Error> {
+        // Only there when the database is Supabase's, whose trigger inserted the row
+        let expiry_date: Option<Option<OffsetDateTime>> = sqlx::query_scalar(
+            "select expiry_date from public.sessions where sb_id = $1::uuid limit 1",
+        )
+        .bind(sb_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(storage_error)?;
+        Ok(expiry_date.flatten())
+    }
+
+    async fn create(&self, session: StoredSession) -> Result<(), Error> {
+        let updated = sqlx::query(
+            "update public.sessions set id = $2, data = $3, expiry_date = $4, user_id = $5::uuid, \
+                user_agent = $6, ip_address = $7 where sb_id = $1::uuid",
+        )
+        .bind(&session.sb_id)
+        .bind(&session.id)
+        .bind(&session.data)
+        .bind(session.expiry_date)
+        .bind(&session.user_id)
+        .bind(&session.user_agent)
+        .bind(&session.ip_address)
+        .execute(&self.pool)
+        .await
+        .map_err(storage_error)?;
+        if updated.rows_affected() > 0 {
+            return Ok(());
+        }
+
+        // No trigger outside of Supabase, the row is new
+        sqlx::query(
+            "insert into public.sessions (id, sb_id, data, created_at, expiry_date, user_id, \
+                user_agent, ip_address) values ($1, $2::uuid, $3, now(), $4, $5::uuid, $6, $7)",
+        )
+        .bind(&session.id)
+        .bind(&session.sb_id)
+        .bind(&session.data)
+        .bind(session.expiry_date)
+        .bind(&session.user_id)
+        .bind(&session.user_agent)
+        .bind(&session.ip_address)
+        .execute(&self.pool)
+        .await
+        .map_err(storage_error)?;
+        Ok(())
+    }
+
+    async fn save(&self, id: &str, data: &str, expiry_date: OffsetDateTime) -> Result<(), Error> {
+        sqlx::query(
+            "update public.sessions set data = $2, expiry_date = $3, \
+                refreshed_at = now() at time zone 'utc' where id = $1",
+        )
+        .bind(id)
+        .bind(data)
+        .bind(expiry_date)
+        .execute(&self.pool)
+        .await
+        .map_err(storage_error)?;
+        Ok(())
+    }
+
+    async fn load(&self, id: &str) -> Result<Option<(String, Option<OffsetDateTime>)>, Error> {
+        let session: Option<(Option<String>, Option<OffsetDateTime>)> =
+            sqlx::query_as("select data, expiry_date from public.sessions where id = $1")
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(storage_error)?;
+        Ok(session.map(|(data, expiry_date)| (data.unwrap_or_default(), expiry_date)))
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), Error> {
+        sqlx::query("delete from public.sessions where id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(storage_error)?;
+        Ok(())
+    }
+
+    async fn list_user(&self, user_id: &str) -> Result<Vec<UserSession>, Error> {
+        let rows: Vec<SessionRow> = sqlx::query_as(
+            "select id, sb_id::text, created_at, refreshed_at at time zone 'utc', expiry_date, \
+                user_agent, ip_address from public.sessions where user_id = $1::uuid \
+                order by refreshed_at desc nulls last",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(storage_error)?;
+
+        Ok(rows
+            .into_iter()
+            .map(
+                |(id, sb_id, created_at, refreshed_at, expiry_date, user_agent, ip_address)| {
+                    UserSession {
+                        id,
+                        sb_id,
+                        created_at: format_date(created_at),
+                        refreshed_at: format_date(refreshed_at),
+                        expiry_date: format_date(expiry_date),
+                        user_agent,
+                        ip_address,
+                    }
+                },
+            )
+            .collect())
+    }
+
+    async fn page(&self, after: &str, limit: usize) -> Result<Vec<(String, String)>, Error> {
+        sqlx::query_as(
+            "select id, data from public.sessions where id > $1 and data is not null \
+                order by id limit $2",
+        )
+        .bind(after)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(storage_error)
+    }
+
+    async fn replace_data(&self, id: &str, data: &str) -> Result<(), Error> {
+        sqlx::query("update public.sessions set data = $2 where id = $1")
+            .bind(id)
+            .bind(data)
+            .execute(&self.pool)
+            .await
+            .map_err(storage_error)?;
+        Ok(())
+    }
+
+    async fn delete_expired(
+        &self,
+        now: OffsetDateTime,
+        created_before: OffsetDateTime,
+    ) -> Result<Vec<String>, Error> {
+        sqlx::query_scalar(
+            "delete from public.sessions where expiry_date < $1 \
+                or (expiry_date is null and created_at < $2) returning id",
+        )
+        .bind(now)
+        .bind(created_before)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(storage_error)
+    }
+}
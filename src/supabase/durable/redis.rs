@@ -0,0 +1,277 @@
+use std::sync::{Arc, Mutex};
+
+use axum::async_trait;
+use leptos::serde_json;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use supabase_rust::errors::Error;
+use time::OffsetDateTime;
+
+use super::{format_date, storage_error, DurableSessions, StoredSession};
+use crate::supabase::UserSession;
+
+/// One key per session expiring with it, and a set per user of the ids of their sessions
+#[derive(Clone)]
+pub struct RedisSessions {
+    connection: ConnectionManager,
+    /// Sorted session ids of the scan `page` took when starting from the first, the later
+    /// pages are read from it rather than scanning again
+    page_ids: Arc<Mutex<Vec<String>>>,
+}
+
+impl std::fmt::Debug for RedisSessions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisSessions").finish_non_exhaustive()
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct RedisSession {
+    id: String,
+    sb_id: String,
+    user_id: String,
+    data: String,
+    #[serde(with = "time::serde::rfc3339")]
+    created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    refreshed_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339")]
+    expiry_date: OffsetDateTime,
+    user_agent: Option<String>,
+    ip_address: Option<String>,
+}
+
+fn session_key(id: &str) -> String {
+    format!("session:{id}")
+}
+
+fn user_key(user_id: &str) -> String {
+    format!("user_sessions:{user_id}")
+}
+
+impl RedisSessions {
+    pub async fn from_env() -> Self {
+        let url = std::env::var("SESSION_REDIS_URL").expect("env var SESSION_REDIS_URL not set");
+        let client = redis::Client::open(url).expect("SESSION_REDIS_URL is not a valid URL");
+        let connection = ConnectionManager::new(client)
+            .await
+            .expect("SESSION_REDIS_URL can't be connected to");
+        Self {
+            connection,
+            page_ids: Arc::default(),
+        }
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<RedisSession>, Error> {
+        let value: Option<String> = self
+            .connection
+            .clone()
+            .get(session_key(id))
+            .await
+            .map_err(storage_error)?;
+        Ok(value.and_then(|value| serde_json::from_str(&value).ok()))
+    }
+
+    /// Writes `session` to expire with it, already expired sessions are dropped
+    async fn put(&self, session: &RedisSession) -> Result<(), Error> {
+        let ttl = (session.expiry_date - OffsetDateTime::now_utc()).whole_seconds();
+        if ttl <= 0 {
+            return self.delete(&session.id).await;
+        }
+
+        let mut connection = self.connection.clone();
+        let value = serde_json::to_string(session).map_err(storage_error)?;
+        connection
+            .set_ex::<_, _, ()>(session_key(&session.id), value, ttl as u64)
+            .await
+            .map_err(storage_error)?;
+        connection
+            .sadd::<_, _, ()>(user_key(&session.user_id), &session.id)
+            .await
+            .map_err(storage_error)
+    }
+}
+
+#[async_trait]
+impl DurableSessions for RedisSessions {
+    async fn auth_session_expiry(&self, _sb_id: &str) -> Result<Option<OffsetDateTime>, Error> {
+        Ok(None)
+    }
+
+    async fn create(&self, session: StoredSession) -> Result<(), Error> {
+        self.put(&RedisSession {
+            id: session.id,
+            sb_id: session.sb_id,
+            user_id: session.user_id,
+            data: session.data,
+            created_at: OffsetDateTime::now_utc(),
+            refreshed_at: None,
+            expiry_date: session.expiry_date,
+            user_agent: session.user_agent,
+            ip_address: session.ip_address,
+        })
+        .await
+    }
+
+    async fn save(&self, id: &str, data: &str, expiry_date: OffsetDateTime) -> Result<(), Error> {
+        let Some(mut session) = self.get(id).await? else {
+            return Ok(());
+        };
+        session.data = data.to_string();
+        session.expiry_date = expiry_date;
+        session.refreshed_at = Some(OffsetDateTime::now_utc());
+        self.put(&session).await
+    }
+
+    async fn load(&self, id: &str) -> Result<Option<(String, Option<OffsetDateTime>)>, Error> {
+        Ok(self
+            .get(id)
+            .await?
+            .map(|session| (session.data, Some(session.expiry_date))))
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), Error> {
+        let session = self.get(id).await?;
+        let mut connection = self.connection.clone();
+        connection
+            .del::<_, ()>(session_key(id))
+            .await
+            .map_err(storage_error)?;
+        if let Some(session) = session {
+            connection
+                .srem::<_, _, ()>(user_key(&session.user_id), id)
+                .await
+                .map_err(storage_error)?;
+        }
+        Ok(())
+    }
+
+    async fn list_user(&self, user_id: &str) -> Result<Vec<UserSession>, Error> {
+        let mut connection = self.connection.clone();
+        let ids: Vec<String> = connection
+            .smembers(user_key(user_id))
+            .await
+            .map_err(storage_error)?;
+
+        let mut sessions = Vec::new();
+        for id in ids {
+            match self.get(&id).await? {
+                Some(session) => sessions.push(session),
+                // Expired since, the key is gone but not the set member
+                None => connection
+                    .srem::<_, _, ()>(user_key(user_id), &id)
+                    .await
+                    .map_err(storage_error)?,
+            }
+        }
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.refreshed_at));
+
+        Ok(sessions
+            .into_iter()
+            .map(|session| UserSession {
+                id: session.id,
+                sb_id: session.sb_id,
+                created_at: format_date(Some(session.created_at)),
+                refreshed_at: format_date(session.refreshed_at),
+                expiry_date: format_date(Some(session.expiry_date)),
+                user_agent: session.user_agent,
+                ip_address: session.ip_address,
+            })
+            .collect())
+    }
+
+    async fn page(&self, after: &str, limit: usize) -> Result<Vec<(String, String)>, Error> {
+        // Keys come in no order, they're scanned once per pass and sorted. Sessions created
+        // since are already under the current key.
+        if after.is_empty() {
+            let mut connection = self.connection.clone();
+            let mut keys = connection
+                .scan_match::<_, String>(session_key("*"))
+                .await
+                .map_err(storage_error)?;
+            let mut ids = Vec::new();
+            while let Some(key) = keys.next_item().await {
+                if let Some(id) = key.strip_prefix("session:") {
+                    ids.push(id.to_string());
+                }
+            }
+            ids.sort();
+            ids.dedup();
+            *self.page_ids.lock().unwrap() = ids;
+        }
+
+        let mut page = Vec::new();
+        let mut next = after.to_string();
+        while page.len() < limit {
+            let batch: Vec<String> = {
+                let ids = self.page_ids.lock().unwrap();
+                let start = ids.partition_point(|id| id.as_str() <= next.as_str());
+                ids[start..]
+                    .iter()
+                    .take(limit - page.len())
+                    .cloned()
+                    .collect()
+            };
+            let Some(last) = batch.last() else {
+                // The pass is through, the ids aren't kept around until the next
+                self.page_ids.lock().unwrap().clear();
+                break;
+            };
+            next = last.clone();
+            // Expired ones are gone by now, the page is filled from the ids after them
+            for id in batch {
+                if let Some(session) = self.get(&id).await? {
+                    page.push((id, session.data));
+                }
+            }
+        }
+        Ok(page)
+    }
+
+    async fn replace_data(&self, id: &str, data: &str) -> Result<(), Error> {
+        let Some(mut session) = self.get(id).await? else {
+            return Ok(());
+        };
+        session.data = data.to_string();
+        self.put(&session).await
+    }
+
+    async fn delete_expired(
+        &self,
+        _now: OffsetDateTime,
+        _created_before: OffsetDateTime,
+    ) -> Result<Vec<String>, Error> {
+        // Session keys expire on their own, what's left are their members in the user sets
+        let mut connection = self.connection.clone();
+        let user_keys: Vec<String> = {
+            let mut keys = connection
+                .scan_match::<_, String>(user_key("*"))
+                .await
+                .map_err(storage_error)?;
+            let mut user_keys = Vec::new();
+            while let Some(key) = keys.next_item().await {
+                user_keys.push(key);
+            }
+            user_keys
+        };
+
+        let mut expired = Vec::new();
+        for key in user_keys {
+            let ids: Vec<String> = connection.smembers(&key).await.map_err(storage_error)?;
+            for id in ids {
+                let live: bool = connection
+                    .exists(session_key(&id))
+                    .await
+                    .map_err(storage_error)?;
+                if !live {
+                    connection
+                        .srem::<_, _, ()>(&key, &id)
+                        .await
+                        .map_err(storage_error)?;
+                    expired.push(id);
+                }
+            }
+        }
+        Ok(expired)
+    }
+}
@@ -0,0 +1,237 @@
+use axum::async_trait;
+use leptos::serde_json;
+use supabase_rust::errors::{Error, ErrorKind, PostgrestError};
+use time::format_description::well_known::Iso8601;
+use time::OffsetDateTime;
+
+use super::{DurableSessions, StoredSession};
+use crate::supabase::UserSession;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default)]
+struct SessionSchema {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sb_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refreshed_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiry_date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip_address: Option<String>,
+}
+
+/// The `public.sessions` table through PostgREST with the service key. Rows are inserted by
+/// a trigger on `auth.sessions`, sign in fills them in.
+#[derive(Clone, Debug)]
+pub struct SupabaseSessions {
+    client: supabase_rust::Supabase,
+    service_key: String,
+}
+
+impl SupabaseSessions {
+    pub fn from_env() -> Self {
+        Self {
+            client: supabase_rust::Supabase::new(None, None, None),
+            service_key: std::env::var("SUPABASE_SERVICE_KEY")
+                .expect("env var SUPABASE_SERVICE_KEY not set"),
+        }
+    }
+
+    async fn update(&self, column: &str, value: &str, session: SessionSchema) -> Result<(), Error> {
+        let query = self
+            .client
+            .query()
+            .from("sessions")
+            .update(serde_json::to_string(&session).unwrap())
+            .eq(column, value)
+            .auth(&self.service_key)
+            .execute()
+            .await;
+        supabase_rust::parse_response::<SessionSchema>(query)
+            .await
+            .map(|_| ())
+    }
+}
+
+fn parse_date(date: &Option<String>) -> Option<OffsetDateTime> {
+    OffsetDateTime::parse(date.as_deref()?, &Iso8601::DEFAULT).ok()
+}
+
+fn format_date(date: OffsetDateTime) -> Option<String> {
+    date.format(&Iso8601::DEFAULT).ok()
+}
+
+#[async_trait]
+impl DurableSessions for SupabaseSessions {
+    async fn auth_session_expiry(&self, sb_id: &str) -> Result<Option<OffsetDateTime>, Error> {
+        let query = self
+            .client
+            .query()
+            .from("sessions")
+            .eq("sb_id", sb_id)
+            .select("expiry_date")
+            .auth(&self.service_key)
+            .execute()
+            .await;
+
+        // The trigger inserted the row when Supabase created the session
+        let sessions = supabase_rust::parse_response::<SessionSchema>(query).await?;
+        let Some(session) = sessions.first() else {
+            return Err(Error {
+                http_status: 404,
+                kind: ErrorKind::Postgrest(PostgrestError {
+                    message: "Found {{0}} rows matching the query".to_string(),
+                    ..Default::default()
+                }),
+            });
+        };
+        Ok(parse_date(&session.expiry_date))
+    }
+
+    async fn create(&self, session: StoredSession) -> Result<(), Error> {
+        let sb_id = session.sb_id.clone();
+        let session = SessionSchema {
+            id: Some(session.id),
+            data: Some(session.data),
+            expiry_date: format_date(session.expiry_date),
+            user_id: Some(session.user_id),
+            user_agent: session.user_agent,
+            ip_address: session.ip_address,
+            ..Default::default()
+        };
+        // When authenticated a trigger inserts a new entry into the table so we UPDATE here
+        self.update("sb_id", &sb_id, session).await
+    }
+
+    async fn save(&self, id: &str, data: &str, expiry_date: OffsetDateTime) -> Result<(), Error> {
+        let session = SessionSchema {
+            id: Some(id.to_string()),
+            data: Some(data.to_string()),
+            refreshed_at: format_date(OffsetDateTime::now_utc()),
+            expiry_date: format_date(expiry_date),
+            ..Default::default()
+        };
+        self.update("id", id, session).await
+    }
+
+    async fn load(&self, id: &str) -> Result<Option<(String, Option<OffsetDateTime>)>, Error> {
+        let query = self
+            .client
+            .query()
+            .from("sessions")
+            .select("data,expiry_date")
+            .eq("id", id)
+            .single()
+            .auth(&self.service_key)
+            .execute()
+            .await;
+
+        let sessions = supabase_rust::parse_response::<SessionSchema>(query).await?;
+        Ok(sessions.into_iter().next().map(|session| {
+            let expiry_date = parse_date(&session.expiry_date);
+            (session.data.unwrap_or_default(), expiry_date)
+        }))
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), Error> {
+        let query = self
+            .client
+            .query()
+            .from("sessions")
+            .delete()
+            .eq("id", id)
+            .auth(&self.service_key)
+            .execute()
+            .await;
+        supabase_rust::parse_response::<SessionSchema>(query)
+            .await
+            .map(|_| ())
+    }
+
+    async fn list_user(&self, user_id: &str) -> Result<Vec<UserSession>, Error> {
+        let query = self
+            .client
+            .query()
+            .from("sessions")
+            .select("id,sb_id,created_at,refreshed_at,expiry_date,user_agent,ip_address")
+            .eq("user_id", user_id)
+            .order("refreshed_at.desc.nullslast")
+            .auth(&self.service_key)
+            .execute()
+            .await;
+
+        supabase_rust::parse_response::<UserSession>(query).await
+    }
+
+    async fn page(&self, after: &str, limit: usize) -> Result<Vec<(String, String)>, Error> {
+        let query = self
+            .client
+            .query()
+            .from("sessions")
+            .select("id,data")
+            .gt("id", after)
+            .order("id.asc")
+            .limit(limit)
+            .auth(&self.service_key)
+            .execute()
+            .await;
+
+        let sessions = supabase_rust::parse_response::<SessionSchema>(query).await?;
+        Ok(sessions
+            .into_iter()
+            .filter_map(|session| Some((session.id?, session.data?)))
+            .collect())
+    }
+
+    async fn replace_data(&self, id: &str, data: &str) -> Result<(), Error> {
+        let session = SessionSchema {
+            data: Some(data.to_string()),
+            ..Default::default()
+        };
+        self.update("id", id, session).await
+    }
+
+    async fn delete_expired(
+        &self,
+        now: OffsetDateTime,
+        created_before: OffsetDateTime,
+    ) -> Result<Vec<String>, Error> {
+        let query = self
+            .client
+            .query()
+            .from("sessions")
+            .delete()
+            .lt("expiry_date", now.format(&Iso8601::DEFAULT).unwrap())
+            .auth(&self.service_key)
+            .execute()
+            .await;
+        let mut sessions = supabase_rust::parse_response::<SessionSchema>(query).await?;
+
+        let query = self
+            .client
+            .query()
+            .from("sessions")
+            .delete()
+            .is("expiry_date", "null")
+            .lt(
+                "created_at",
+                created_before.format(&Iso8601::DEFAULT).unwrap(),
+            )
+            .auth(&self.service_key)
+            .execute()
+            .await;
+        sessions.extend(supabase_rust::parse_response::<SessionSchema>(query).await?);
+
+        Ok(sessions
+            .into_iter()
+            .filter_map(|session| session.id)
+            .collect())
+    }
+}
@@ -0,0 +1,313 @@
+//! GoTrue endpoints the client doesn't wrap, called over plain HTTP
+
+use leptos::serde_json::{self, json, Value};
+use reqwest::Method;
+use supabase_rust::errors::{AuthError, Error, ErrorKind};
+use supabase_rust::schema::AccessToken;
+
+use super::SupabaseBackend;
+
+#[derive(serde::Deserialize, Debug, Default)]
+struct GoTrueError {
+    msg: Option<String>,
+    message: Option<String>,
+    error_description: Option<String>,
+}
+
+/// The parts of the GoTrue user object the app reads
+#[derive(serde::Deserialize, Debug, Clone, Default)]
+pub struct UserAccount {
+    pub id: String,
+    #[serde(default)]
+    pub email: Option<String>,
+    /// Set while an email change waits for confirmation
+    #[serde(default)]
+    pub new_email: Option<String>,
+    #[serde(default)]
+    pub identities: Vec<UserIdentity>,
+}
+
+/// A sign in method attached to the user, `email` or an OAuth provider
+#[derive(serde::Deserialize, Debug, Clone, Default)]
+pub struct UserIdentity {
+    pub identity_id: String,
+    pub provider: String,
+    #[serde(default)]
+    pub identity_data: Option<Value>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+impl UserIdentity {
+    pub fn email(&self) -> Option<String> {
+        self.identity_data
+            .as_ref()?
+            .get("email")?
+            .as_str()
+            .map(str::to_string)
+    }
+}
+
+impl SupabaseBackend {
+    /// `token` authenticates as a user, the API key alone is sent otherwise
+    pub(crate) async fn gotrue(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, &str)],
+        token: Option<&str>,
+        body: Option<Value>,
+    ) -> Result<Value, Error> {
+        let mut request = self
+            .http
+            .request(method, format!("{}/auth/v1{path}", self.url))
+            .header("apikey", &self.api_key)
+            .bearer_auth(token.unwrap_or(self.api_key.as_str()))
+            .query(query);
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+
+        let response = request.send().await.map_err(|e| Error {
+            http_status: 502,
+            kind: ErrorKind::Auth(AuthError {
+                msg: Some(e.to_string()),
+                ..Default::default()
+            }),
+        })?;
+
+        let status = response.status().as_u16();
+        let text = response.text().await.unwrap_or_default();
+        if status < 300 {
+            return Ok(serde_json::from_str(&text).unwrap_or(Value::Null));
+        }
+
+        let error = serde_json::from_str::<GoTrueError>(&text).unwrap_or_default();
+        Err(Error {
+            http_status: status,
+            kind: ErrorKind::Auth(AuthError {
+                msg: error.msg.or(error.message),
+                error_description: error.error_description,
+                ..Default::default()
+            }),
+        })
+    }
+
+    /// Emails a password recovery link leading to `redirect_to` with a PKCE `code`
+    pub async fn send_password_recovery(
+        &self,
+        email: &str,
+        redirect_to: &str,
+        code_challenge: &str,
+    ) -> Result<(), Error> {
+        let body = json!({
+            "email": email,
+            "code_challenge": code_challenge,
+            "code_challenge_method": "s256",
+        });
+        self.gotrue(
+            Method::POST,
+            "/recover",
+            &[("redirect_to", redirect_to)],
+            None,
+            Some(body),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// Emails a sign in link to an existing user, leading to `redirect_to` with a PKCE `code`
+    pub async fn send_magic_link(
+        &self,
+        email: &str,
+        redirect_to: &str,
+        code_challenge: &str,
+    ) -> Result<(), Error> {
+        let body = json!({
+            "email": email,
+            "create_user": false,
+            "code_challenge": code_challenge,
+            "code_challenge_method": "s256",
+        });
+        self.gotrue(
+            Method::POST,
+            "/otp",
+            &[("redirect_to", redirect_to)],
+            None,
+            Some(body),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    pub async fn fetch_user(&self, access_token: &str) -> Result<UserAccount, Error> {
+        let user = self
+            .gotrue(Method::GET, "/user", &[], Some(access_token), None)
+            .await?;
+        Ok(serde_json::from_value(user).unwrap_or_default())
+    }
+
+    /// Updates the signed in user's `email`, `password` or `data`,
+    /// confirmation emails link back to `redirect_to`
+    pub async fn update_user(
+        &self,
+        access_token: &str,
+        attributes: Value,
+        redirect_to: Option<&str>,
+    ) -> Result<UserAccount, Error> {
+        let query: Vec<_> = redirect_to
+            .map(|to| ("redirect_to", to))
+            .into_iter()
+            .collect();
+        let user = self
+            .gotrue(
+                Method::PUT,
+                "/user",
+                &query,
+                Some(access_token),
+                Some(attributes),
+            )
+            .await?;
+        Ok(serde_json::from_value(user).unwrap_or_default())
+    }
+
+    /// Deletes the auth user with the service key, their rows follow through `on delete cascade`
+    pub async fn delete_auth_user(&self, user_id: &str) -> Result<(), Error> {
+        self.gotrue(
+            Method::DELETE,
+            &format!("/admin/users/{user_id}"),
+            &[],
+            Some(&self.service_key),
+            None,
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// Ends only the session of `access_token`, GoTrue's `/logout` ends all of the user's
+    /// sessions unless told otherwise
+    pub async fn logout_session(&self, access_token: &str) -> Result<(), Error> {
+        self.gotrue(
+            Method::POST,
+            "/logout",
+            &[("scope", "local")],
+            Some(access_token),
+            None,
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// Provider URL that attaches an identity to the signed in user, coming back to
+    /// `redirect_to` with a PKCE `code`
+    pub async fn identity_link_url(
+        &self,
+        access_token: &str,
+        provider: &str,
+        scopes: &[String],
+        redirect_to: &str,
+        code_challenge: &str,
+    ) -> Result<String, Error> {
+        let scopes = scopes.join(" ");
+        let response = self
+            .gotrue(
+                Method::GET,
+                "/user/identities/authorize",
+                &[
+                    ("provider", provider),
+                    ("scopes", &scopes),
+                    ("redirect_to", redirect_to),
+                    ("code_challenge", code_challenge),
+                    ("code_challenge_method", "s256"),
+                    ("skip_http_redirect", "true"),
+                ],
+                Some(access_token),
+                None,
+            )
+            .await?;
+
+        response
+            .get("url")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or(Error {
+                http_status: 502,
+                kind: ErrorKind::Auth(AuthError {
+                    msg: Some("Missing identity link url".to_string()),
+                    ..Default::default()
+                }),
+            })
+    }
+
+    pub async fn unlink_identity(
+        &self,
+        access_token: &str,
+        identity_id: &str,
+    ) -> Result<(), Error> {
+        self.gotrue(
+            Method::DELETE,
+            &format!("/user/identities/{identity_id}"),
+            &[],
+            Some(access_token),
+            None,
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// Starts a Supabase session for `user_id` without their password, once the app has
+    /// authenticated them another way. Goes through a magic link that is verified right away.
+    pub async fn sign_in_as(&self, user_id: &str) -> Result<AccessToken, Error> {
+        let user = self
+            .gotrue(
+                Method::GET,
+                &format!("/admin/users/{user_id}"),
+                &[],
+                Some(&self.service_key),
+                None,
+            )
+            .await?;
+        let user: UserAccount = serde_json::from_value(user).unwrap_or_default();
+        let email = user.email.ok_or(Error {
+            http_status: 404,
+            kind: ErrorKind::Auth(AuthError {
+                msg: Some("User not found".to_string()),
+                ..Default::default()
+            }),
+        })?;
+
+        let link = self
+            .gotrue(
+                Method::POST,
+                "/admin/generate_link",
+                &[],
+                Some(&self.service_key),
+                Some(json!({ "type": "magiclink", "email": email })),
+            )
+            .await?;
+        // Older GoTrue versions nest the link details under `properties`
+        let token_hash = link
+            .get("hashed_token")
+            .or_else(|| link.get("properties")?.get("hashed_token"))
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+
+        let session = self
+            .gotrue(
+                Method::POST,
+                "/verify",
+                &[],
+                None,
+                Some(json!({ "type": "magiclink", "token_hash": token_hash })),
+            )
+            .await?;
+        serde_json::from_value(session).map_err(|e| Error {
+            http_status: 502,
+            kind: ErrorKind::Auth(AuthError {
+                msg: Some(e.to_string()),
+                ..Default::default()
+            }),
+        })
+    }
+}
@@ -1,16 +1,36 @@
+mod admin;
 mod auth;
+mod device;
+pub mod durable;
 mod error;
+mod gotrue;
+mod passkeys;
+mod recovery_codes;
+mod session_cipher;
 mod session_store;
+mod trusted_devices;
 mod user_identity;
 mod wrappers;
 
 use std::sync::{Arc, Weak};
 
 use tower_sessions_moka_store::MokaStore;
-use user_identity::IdentityData;
+
+use crate::components::auth::OAuthProvider;
+use durable::DurableSessions;
+use session_cipher::SessionCipher;
+use session_store::{RefreshFlights, SessionTimeouts};
 use wrappers::{AuthWrapper, StoreWrapper};
 
+pub(crate) use admin::user_uuid;
+pub use admin::{AdminAuditSchema, AdminUser};
+pub use device::DeviceInfo;
 pub use error::{map_err, SupabaseError};
+pub use gotrue::{UserAccount, UserIdentity};
+pub use passkeys::{PasskeySchema, Passkeys};
+pub use session_store::{run_session_reaper, RefreshedTokens, TokenRefresher, UserSession};
+pub use trusted_devices::{TrustedDeviceSchema, TRUSTED_DEVICE_DAYS};
+pub use user_identity::IdentityData;
 
 pub type AuthSession = axum_login::AuthSession<AuthWrapper<SupabaseBackend>>;
 pub type Supabase = std::sync::Arc<SupabaseBackend>;
@@ -26,18 +46,64 @@ pub struct SupabaseBackend {
     weak: Weak<Self>,
     sessions_cache: MokaStore,
     service_key: String,
+    url: String,
+    api_key: String,
+    site_url: String,
+    oauth_providers: Vec<OAuthProvider>,
+    passkeys: Arc<Passkeys>,
+    /// Signs the trusted device cookies
+    device_secret: String,
+    /// Encrypts `sessions.data`
+    session_cipher: Arc<SessionCipher>,
+    /// Where sessions are kept past the cache, see `durable::from_env`
+    durable: Arc<dyn DurableSessions>,
+    session_timeouts: SessionTimeouts,
+    refresh_flights: Arc<RefreshFlights>,
+    /// Spends refresh tokens, the Supabase client outside of tests
+    token_refresher: Arc<dyn TokenRefresher>,
+    http: reqwest::Client,
 }
 
 impl SupabaseBackend {
-    pub fn new(sessions_cache: MokaStore) -> Arc<Self> {
+    pub fn new(sessions_cache: MokaStore, durable: Arc<dyn DurableSessions>) -> Arc<Self> {
+        let client = supabase_rust::Supabase::new(None, None, None);
+        Self::with_token_refresher(sessions_cache, durable, Arc::new(client))
+    }
+
+    /// Like `new`, with refresh tokens spent through `token_refresher`
+    pub fn with_token_refresher(
+        sessions_cache: MokaStore,
+        durable: Arc<dyn DurableSessions>,
+        token_refresher: Arc<dyn TokenRefresher>,
+    ) -> Arc<Self> {
         let client = supabase_rust::Supabase::new(None, None, None);
         let service_key =
             std::env::var("SUPABASE_SERVICE_KEY").expect("env var SUPABASE_SERVICE_KEY not set");
+        let url = std::env::var("SUPABASE_URL").expect("env var SUPABASE_URL not set");
+        let api_key = std::env::var("SUPABASE_API_KEY").expect("env var SUPABASE_API_KEY not set");
+        let site_url = std::env::var("SITE_URL").unwrap_or("http://localhost:3000".to_string());
+        let site_url = site_url.trim_end_matches('/').to_string();
+        let device_secret = std::env::var("TRUSTED_DEVICE_SECRET")
+            .ok()
+            .filter(|secret| !secret.is_empty())
+            .expect("env var TRUSTED_DEVICE_SECRET not set");
         Arc::new_cyclic(|this: &Weak<Self>| Self {
             client,
             weak: this.clone(),
             sessions_cache,
             service_key,
+            url: url.trim_end_matches('/').to_string(),
+            api_key,
+            oauth_providers: OAuthProvider::from_env(),
+            passkeys: Arc::new(Passkeys::new(&site_url)),
+            site_url,
+            device_secret,
+            session_cipher: Arc::new(SessionCipher::from_env()),
+            durable,
+            session_timeouts: SessionTimeouts::from_env(),
+            refresh_flights: Arc::default(),
+            token_refresher,
+            http: reqwest::Client::new(),
         })
     }
 
@@ -52,4 +118,46 @@ impl SupabaseBackend {
     pub fn admin_token(&self) -> &str {
         self.service_key.as_ref()
     }
+
+    /// Public origin of the app, links sent by email and OAuth callbacks point there
+    pub fn site_url(&self) -> &str {
+        self.site_url.as_ref()
+    }
+
+    pub fn oauth_providers(&self) -> &[OAuthProvider] {
+        &self.oauth_providers
+    }
+
+    pub fn oauth_provider(&self, id: &str) -> Option<&OAuthProvider> {
+        self.oauth_providers
+            .iter()
+            .find(|provider| provider.id == id)
+    }
+}
+
+/// Backend with nothing to reach, sessions are kept in memory. Refreshes go to
+/// `token_refresher`, or the unreachable Supabase client without one.
+#[cfg(test)]
+pub(crate) fn test_backend(token_refresher: Option<Arc<dyn TokenRefresher>>) -> Supabase {
+    for (key, value) in [
+        ("SUPABASE_URL", "http://127.0.0.1:9"),
+        ("SUPABASE_API_KEY", "api-key"),
+        ("SUPABASE_SERVICE_KEY", "service-key"),
+        ("TRUSTED_DEVICE_SECRET", "device-secret"),
+        (
+            "SESSION_ENCRYPTION_KEYS",
+            "test:AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=",
+        ),
+    ] {
+        std::env::set_var(key, value);
+    }
+    let token_refresher: Arc<dyn TokenRefresher> = match token_refresher {
+        Some(token_refresher) => token_refresher,
+        None => Arc::new(supabase_rust::Supabase::new(None, None, None)),
+    };
+    SupabaseBackend::with_token_refresher(
+        MokaStore::new(Some(100)),
+        Arc::new(durable::MemorySessions::default()),
+        token_refresher,
+    )
 }
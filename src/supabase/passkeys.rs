@@ -0,0 +1,385 @@
+//! WebAuthn ceremonies and the `passkeys` table. Passkeys sign in on their own or
+//! stand in for the 2FA challenge.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use leptos::serde_json;
+use supabase_rust::errors::{AuthError, Error, ErrorKind};
+use time::format_description::well_known::Iso8601;
+use time::OffsetDateTime;
+use webauthn_rs::prelude::{
+    CreationChallengeResponse, DiscoverableAuthentication, DiscoverableKey, Passkey,
+    PasskeyAuthentication, PasskeyRegistration, PublicKeyCredential, RegisterPublicKeyCredential,
+    RequestChallengeResponse, ResidentKeyRequirement, Url, Uuid, Webauthn, WebauthnBuilder,
+};
+
+use super::SupabaseBackend;
+
+/// Ceremonies not finished within this are dropped
+const CEREMONY_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Default)]
+pub struct PasskeySchema {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub passkey: Option<Passkey>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<String>,
+}
+
+enum CeremonyState {
+    Registration(PasskeyRegistration),
+    Authentication(PasskeyAuthentication),
+    Discoverable(DiscoverableAuthentication),
+}
+
+struct Ceremony {
+    started: Instant,
+    /// `None` for passwordless sign in, the user is only known once the passkey answers
+    user_id: Option<String>,
+    state: CeremonyState,
+}
+
+/// The relying party and the ceremonies in progress, kept in memory like the sessions cache
+pub struct Passkeys {
+    webauthn: Webauthn,
+    ceremonies: Mutex<HashMap<String, Ceremony>>,
+}
+
+impl std::fmt::Debug for Passkeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Passkeys").finish_non_exhaustive()
+    }
+}
+
+impl Passkeys {
+    /// The relying party is `site_url`, its id can be widened to a parent domain
+    /// with `WEBAUTHN_RP_ID`
+    pub fn new(site_url: &str) -> Self {
+        let origin = Url::parse(site_url).expect("SITE_URL is not a valid URL");
+        let rp_id = std::env::var("WEBAUTHN_RP_ID")
+            .ok()
+            .filter(|id| !id.is_empty())
+            .or_else(|| origin.host_str().map(str::to_string))
+            .expect("SITE_URL has no host");
+
+        let webauthn = WebauthnBuilder::new(&rp_id, &origin)
+            .and_then(|builder| builder.rp_name("Todo Supabase Leptos").build())
+            .expect("Invalid WebAuthn relying party");
+
+        Self {
+            webauthn,
+            ceremonies: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn begin(&self, user_id: Option<String>, state: CeremonyState) -> String {
+        use base64::prelude::{Engine as _, BASE64_URL_SAFE_NO_PAD};
+        use rand::RngCore;
+
+        let mut id = [0u8; 24];
+        rand::thread_rng().fill_bytes(&mut id);
+        let id = BASE64_URL_SAFE_NO_PAD.encode(id);
+
+        let mut ceremonies = self.ceremonies.lock().unwrap();
+        ceremonies.retain(|_, c| c.started.elapsed() < CEREMONY_TIMEOUT);
+        ceremonies.insert(
+            id.clone(),
+            Ceremony {
+                started: Instant::now(),
+                user_id,
+                state,
+            },
+        );
+        id
+    }
+
+    /// Ceremonies are single use, whether they succeed or not
+    fn take(&self, id: &str, user_id: Option<&str>) -> Result<CeremonyState, Error> {
+        let ceremony = self.ceremonies.lock().unwrap().remove(id);
+        match ceremony {
+            Some(c)
+                if c.started.elapsed() < CEREMONY_TIMEOUT && c.user_id.as_deref() == user_id =>
+            {
+                Ok(c.state)
+            }
+            _ => Err(passkey_error(400, "The passkey request expired, try again")),
+        }
+    }
+}
+
+fn passkey_error(http_status: u16, msg: &str) -> Error {
+    Error {
+        http_status,
+        kind: ErrorKind::Auth(AuthError {
+            msg: Some(msg.to_string()),
+            ..Default::default()
+        }),
+    }
+}
+
+fn user_uuid(user_id: &str) -> Result<Uuid, Error> {
+    Uuid::parse_str(user_id).map_err(|_| passkey_error(400, "Invalid user id"))
+}
+
+impl SupabaseBackend {
+    pub async fn list_passkeys(&self, user_id: &str) -> Result<Vec<PasskeySchema>, Error> {
+        let query = self
+            .client
+            .query()
+            .from("passkeys")
+            .select("id,name,passkey,created_at,last_used_at")
+            .eq("user_id", user_id)
+            .order("created_at.asc")
+            .auth(&self.service_key)
+            .execute()
+            .await;
+
+        supabase_rust::parse_response::<PasskeySchema>(query).await
+    }
+
+    /// Passkeys count as a second factor alongside the TOTP authenticators
+    pub async fn has_passkeys(&self, user_id: &str) -> bool {
+        self.list_passkeys(user_id)
+            .await
+            .is_ok_and(|passkeys| !passkeys.is_empty())
+    }
+
+    pub async fn remove_passkey(&self, user_id: &str, passkey_id: u64) -> Result<(), Error> {
+        let query = self
+            .client
+            .query()
+            .from("passkeys")
+            .delete()
+            .eq("id", passkey_id.to_string())
+            .eq("user_id", user_id)
+            .auth(&self.service_key)
+            .execute()
+            .await;
+
+        let removed = supabase_rust::parse_response::<PasskeySchema>(query).await?;
+        if removed.is_empty() {
+            return Err(passkey_error(404, "Passkey not found"));
+        }
+        Ok(())
+    }
+
+    /// Returns the ceremony id to finish with and the options for `navigator.credentials.create`
+    pub async fn start_passkey_registration(
+        &self,
+        user_id: &str,
+        email: &str,
+    ) -> Result<(String, CreationChallengeResponse), Error> {
+        let existing = self
+            .list_passkeys(user_id)
+            .await?
+            .into_iter()
+            .filter_map(|row| row.passkey.map(|p| p.cred_id().clone()))
+            .collect::<Vec<_>>();
+
+        let (mut challenge, state) = self
+            .passkeys
+            .webauthn
+            .start_passkey_registration(user_uuid(user_id)?, email, email, Some(existing))
+            .map_err(|e| passkey_error(400, &e.to_string()))?;
+
+        // Passwordless sign in needs the authenticator to remember the user
+        if let Some(selection) = challenge.public_key.authenticator_selection.as_mut() {
+            selection.resident_key = Some(ResidentKeyRequirement::Required);
+            selection.require_resident_key = true;
+        }
+
+        let ceremony_id = self.passkeys.begin(
+            Some(user_id.to_string()),
+            CeremonyState::Registration(state),
+        );
+        Ok((ceremony_id, challenge))
+    }
+
+    pub async fn finish_passkey_registration(
+        &self,
+        user_id: &str,
+        ceremony_id: &str,
+        name: String,
+        credential: &RegisterPublicKeyCredential,
+    ) -> Result<(), Error> {
+        let CeremonyState::Registration(state) = self.passkeys.take(ceremony_id, Some(user_id))?
+        else {
+            return Err(passkey_error(400, "Not a passkey registration"));
+        };
+
+        let passkey = self
+            .passkeys
+            .webauthn
+            .finish_passkey_registration(credential, &state)
+            .map_err(|e| passkey_error(400, &e.to_string()))?;
+
+        let row = serde_json::to_string(&PasskeySchema {
+            user_id: Some(user_id.to_string()),
+            name: Some(name),
+            passkey: Some(passkey),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let query = self
+            .client
+            .query()
+            .from("passkeys")
+            .insert(row)
+            .auth(&self.service_key)
+            .execute()
+            .await;
+
+        supabase_rust::parse_response::<PasskeySchema>(query)
+            .await
+            .map(|_| ())
+    }
+
+    /// Challenge for the signed in user's own passkeys, used as the second factor
+    pub async fn start_passkey_authentication(
+        &self,
+        user_id: &str,
+    ) -> Result<(String, RequestChallengeResponse), Error> {
+        let passkeys: Vec<Passkey> = self
+            .list_passkeys(user_id)
+            .await?
+            .into_iter()
+            .filter_map(|row| row.passkey)
+            .collect();
+        if passkeys.is_empty() {
+            return Err(passkey_error(404, "No passkey registered"));
+        }
+
+        let (challenge, state) = self
+            .passkeys
+            .webauthn
+            .start_passkey_authentication(&passkeys)
+            .map_err(|e| passkey_error(400, &e.to_string()))?;
+
+        let ceremony_id = self.passkeys.begin(
+            Some(user_id.to_string()),
+            CeremonyState::Authentication(state),
+        );
+        Ok((ceremony_id, challenge))
+    }
+
+    pub async fn finish_passkey_authentication(
+        &self,
+        user_id: &str,
+        ceremony_id: &str,
+        credential: &PublicKeyCredential,
+    ) -> Result<(), Error> {
+        let CeremonyState::Authentication(state) =
+            self.passkeys.take(ceremony_id, Some(user_id))?
+        else {
+            return Err(passkey_error(400, "Not a passkey authentication"));
+        };
+
+        let result = self
+            .passkeys
+            .webauthn
+            .finish_passkey_authentication(credential, &state)
+            .map_err(|_| passkey_error(401, "The passkey couldn't be verified"))?;
+
+        self.record_passkey_use(user_id, &result).await;
+        Ok(())
+    }
+
+    /// Challenge without a user, any passkey stored on the device may answer it
+    pub fn start_passkey_login(&self) -> Result<(String, RequestChallengeResponse), Error> {
+        let (challenge, state) = self
+            .passkeys
+            .webauthn
+            .start_discoverable_authentication()
+            .map_err(|e| passkey_error(400, &e.to_string()))?;
+
+        let ceremony_id = self
+            .passkeys
+            .begin(None, CeremonyState::Discoverable(state));
+        Ok((ceremony_id, challenge))
+    }
+
+    /// Returns the id of the user the passkey belongs to
+    pub async fn finish_passkey_login(
+        &self,
+        ceremony_id: &str,
+        credential: &PublicKeyCredential,
+    ) -> Result<String, Error> {
+        let CeremonyState::Discoverable(state) = self.passkeys.take(ceremony_id, None)? else {
+            return Err(passkey_error(400, "Not a passkey sign in"));
+        };
+
+        let (user_uuid, _) = self
+            .passkeys
+            .webauthn
+            .identify_discoverable_authentication(credential)
+            .map_err(|_| passkey_error(401, "Unknown passkey"))?;
+        let user_id = user_uuid.to_string();
+
+        let keys: Vec<DiscoverableKey> = self
+            .list_passkeys(&user_id)
+            .await?
+            .iter()
+            .filter_map(|row| row.passkey.as_ref().map(DiscoverableKey::from))
+            .collect();
+
+        let result = self
+            .passkeys
+            .webauthn
+            .finish_discoverable_authentication(credential, state, &keys)
+            .map_err(|_| passkey_error(401, "The passkey couldn't be verified"))?;
+
+        self.record_passkey_use(&user_id, &result).await;
+        Ok(user_id)
+    }
+
+    /// Stores the passkey's new signature counter and when it was last used
+    async fn record_passkey_use(
+        &self,
+        user_id: &str,
+        result: &webauthn_rs::prelude::AuthenticationResult,
+    ) {
+        let Ok(rows) = self.list_passkeys(user_id).await else {
+            return;
+        };
+
+        for row in rows {
+            let Some(mut passkey) = row.passkey else {
+                continue;
+            };
+            if passkey.update_credential(result).is_none() {
+                continue;
+            }
+
+            let update = serde_json::to_string(&PasskeySchema {
+                passkey: Some(passkey),
+                last_used_at: Some(OffsetDateTime::now_utc().format(&Iso8601::DEFAULT).unwrap()),
+                ..Default::default()
+            })
+            .unwrap();
+
+            let query = self
+                .client
+                .query()
+                .from("passkeys")
+                .update(update)
+                .eq("id", row.id.unwrap_or_default().to_string())
+                .auth(&self.service_key)
+                .execute()
+                .await;
+            if query.is_err() {
+                tracing::error!("\nPasskeys - failed to record use of {:?}", row.id);
+            }
+        }
+    }
+}
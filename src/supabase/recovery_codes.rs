@@ -0,0 +1,119 @@
+//! Single-use codes that stand in for the second factor when the authenticator is lost
+
+use leptos::serde_json;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use supabase_rust::errors::Error;
+
+use super::SupabaseBackend;
+
+const CODE_COUNT: usize = 10;
+/// No 0/o or 1/l, the codes are likely to be typed from a printout
+const CODE_ALPHABET: &[u8] = b"23456789abcdefghjkmnpqrstuvwxyz";
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Default)]
+struct RecoveryCodeSchema {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code_hash: Option<String>,
+}
+
+/// `xxxxx-xxxxx`
+fn generate() -> String {
+    let mut rng = rand::thread_rng();
+    let mut code: String = (0..10)
+        .map(|_| CODE_ALPHABET[rng.gen_range(0..CODE_ALPHABET.len())] as char)
+        .collect();
+    code.insert(5, '-');
+    code
+}
+
+/// Hashed with the user id so equal codes of different users don't share a hash
+fn hash(user_id: &str, code: &str) -> String {
+    let code: String = code
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    Sha256::digest(format!("{user_id}:{code}").as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+impl SupabaseBackend {
+    /// Replaces the user's recovery codes, the returned plaintext codes aren't stored
+    pub async fn generate_recovery_codes(&self, user_id: &str) -> Result<Vec<String>, Error> {
+        let codes: Vec<String> = (0..CODE_COUNT).map(|_| generate()).collect();
+
+        let query = self
+            .client
+            .query()
+            .from("mfa_recovery_codes")
+            .delete()
+            .eq("user_id", user_id)
+            .auth(&self.service_key)
+            .execute()
+            .await;
+        supabase_rust::parse_response::<RecoveryCodeSchema>(query).await?;
+
+        let rows: Vec<RecoveryCodeSchema> = codes
+            .iter()
+            .map(|code| RecoveryCodeSchema {
+                user_id: Some(user_id.to_string()),
+                code_hash: Some(hash(user_id, code)),
+                ..Default::default()
+            })
+            .collect();
+
+        let query = self
+            .client
+            .query()
+            .from("mfa_recovery_codes")
+            .insert(serde_json::to_string(&rows).unwrap())
+            .auth(&self.service_key)
+            .execute()
+            .await;
+        supabase_rust::parse_response::<RecoveryCodeSchema>(query).await?;
+
+        Ok(codes)
+    }
+
+    /// How many unused codes the user has left
+    pub async fn count_recovery_codes(&self, user_id: &str) -> Result<usize, Error> {
+        let query = self
+            .client
+            .query()
+            .from("mfa_recovery_codes")
+            .select("id")
+            .eq("user_id", user_id)
+            .auth(&self.service_key)
+            .execute()
+            .await;
+
+        supabase_rust::parse_response::<RecoveryCodeSchema>(query)
+            .await
+            .map(|codes| codes.len())
+    }
+
+    /// Uses up `code`, returns whether it was one of the user's unused codes
+    pub async fn redeem_recovery_code(&self, user_id: &str, code: &str) -> Result<bool, Error> {
+        let query = self
+            .client
+            .query()
+            .from("mfa_recovery_codes")
+            .delete()
+            .eq("user_id", user_id)
+            .eq("code_hash", hash(user_id, code))
+            .auth(&self.service_key)
+            .execute()
+            .await;
+
+        supabase_rust::parse_response::<RecoveryCodeSchema>(query)
+            .await
+            .map(|redeemed| !redeemed.is_empty())
+    }
+}
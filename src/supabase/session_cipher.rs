@@ -0,0 +1,217 @@
+//! Encryption of `sessions.data`, which holds the Supabase tokens of the session.
+//! Stored as `<key id>:<base64 of nonce and ciphertext>`, the session id is bound in as
+//! associated data so a blob can't be moved to another row. Without any key configured,
+//! which debug builds only allow with `SESSION_ENCRYPTION_DISABLED=true`, the data is
+//! stored as plain JSON.
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+
+/// AES-GCM nonces are 96 bits
+const NONCE_LEN: usize = 12;
+
+pub struct SessionCipher {
+    /// Id of the key new data is encrypted with, none when encryption isn't configured
+    current: Option<String>,
+    /// Every configured key, the older ones still decrypt until sessions are re-encrypted
+    keys: Vec<(String, Aes256Gcm)>,
+}
+
+impl std::fmt::Debug for SessionCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionCipher")
+            .field("current", &self.current)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SessionCipher {
+    /// Reads `SESSION_ENCRYPTION_KEYS`, `<key id>:<base64 32 byte key>` separated by commas,
+    /// the first one being the current key. Panics when unset or empty, unless a debug build
+    /// sets `SESSION_ENCRYPTION_DISABLED=true`.
+    pub fn from_env() -> Self {
+        let cipher =
+            Self::from_config(&std::env::var("SESSION_ENCRYPTION_KEYS").unwrap_or_default());
+        if cipher.current.is_none() {
+            let disabled = cfg!(debug_assertions)
+                && std::env::var("SESSION_ENCRYPTION_DISABLED").is_ok_and(|v| v == "true");
+            assert!(
+                disabled,
+                "env var SESSION_ENCRYPTION_KEYS not set, SESSION_ENCRYPTION_DISABLED=true \
+                 stores sessions unencrypted in debug builds only"
+            );
+            tracing::warn!(
+                "SESSION_ENCRYPTION_DISABLED is set, session tokens are stored unencrypted"
+            );
+        }
+        cipher
+    }
+
+    fn from_config(config: &str) -> Self {
+        let keys: Vec<(String, Aes256Gcm)> = config
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (id, key) = entry
+                    .split_once(':')
+                    .expect("SESSION_ENCRYPTION_KEYS entries are <key id>:<base64 key>");
+                let key = BASE64_STANDARD
+                    .decode(key)
+                    .ok()
+                    .filter(|key| key.len() == 32)
+                    .expect("SESSION_ENCRYPTION_KEYS keys are 32 bytes in base64");
+                (
+                    id.to_string(),
+                    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)),
+                )
+            })
+            .collect();
+
+        let current = keys.first().map(|(id, _)| id.clone());
+        Self { current, keys }
+    }
+
+    pub fn encrypt(&self, session_id: &str, data: &str) -> String {
+        let Some(current) = &self.current else {
+            return data.to_string();
+        };
+        let cipher = self.key(current).unwrap();
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let payload = Payload {
+            msg: data.as_bytes(),
+            aad: session_id.as_bytes(),
+        };
+        // Only fails past the message size limit of AES-GCM
+        let mut sealed = nonce.to_vec();
+        sealed.extend(cipher.encrypt(&nonce, payload).unwrap());
+        format!("{current}:{}", BASE64_STANDARD.encode(sealed))
+    }
+
+    /// The data stored for `session_id`. Plaintext is only read without a current key,
+    /// once keys are configured it's left to `decrypt_legacy`.
+    pub fn decrypt(&self, session_id: &str, stored: &str) -> Option<String> {
+        if stored.starts_with('{') {
+            return self.current.is_none().then(|| stored.to_string());
+        }
+        self.open(session_id, stored)
+    }
+
+    /// Like `decrypt`, but also reads the JSON of sessions saved before encryption, for
+    /// `reencrypt_sessions` to move them to the current key
+    pub fn decrypt_legacy(&self, session_id: &str, stored: &str) -> Option<String> {
+        if stored.starts_with('{') {
+            return Some(stored.to_string());
+        }
+        self.open(session_id, stored)
+    }
+
+    fn open(&self, session_id: &str, stored: &str) -> Option<String> {
+        let (id, sealed) = stored.split_once(':')?;
+        let Some(cipher) = self.key(id) else {
+            tracing::error!(key_id = id, "Session encrypted with an unknown key");
+            return None;
+        };
+        let sealed = BASE64_STANDARD.decode(sealed).ok()?;
+        if sealed.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let payload = Payload {
+            msg: ciphertext,
+            aad: session_id.as_bytes(),
+        };
+        let data = cipher
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| tracing::error!(key_id = id, "Session data failed to decrypt"))
+            .ok()?;
+        String::from_utf8(data).ok()
+    }
+
+    /// Whether `stored` is plaintext or under a key other than the current one. Nothing is
+    /// without a current key, there's no key to move it to.
+    pub fn is_stale(&self, stored: &str) -> bool {
+        let Some(current) = &self.current else {
+            return false;
+        };
+        stored
+            .strip_prefix(current.as_str())
+            .map_or(true, |rest| !rest.starts_with(':'))
+    }
+
+    fn key(&self, id: &str) -> Option<&Aes256Gcm> {
+        self.keys
+            .iter()
+            .find(|(key_id, _)| key_id == id)
+            .map(|(_, key)| key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY_1: &str = "k1:AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=";
+    const KEY_2: &str = "k2:HyAhIiMkJSYnKCkqKywtLi8wMTIzNDU2Nzg5Ojs8PT4=";
+
+    #[test]
+    fn round_trips_bound_to_the_session() {
+        let cipher = SessionCipher::from_config(KEY_1);
+        let sealed = cipher.encrypt("session", r#"{"token":"secret"}"#);
+        assert!(sealed.starts_with("k1:"));
+        assert!(!sealed.contains("secret"));
+        assert_eq!(
+            cipher.decrypt("session", &sealed).as_deref(),
+            Some(r#"{"token":"secret"}"#)
+        );
+        assert_eq!(cipher.decrypt("other", &sealed), None);
+        assert!(!cipher.is_stale(&sealed));
+    }
+
+    #[test]
+    fn older_keys_still_decrypt() {
+        let sealed = SessionCipher::from_config(KEY_1).encrypt("session", "{}");
+        let rotated = SessionCipher::from_config(&format!("{KEY_2}, {KEY_1}"));
+        assert_eq!(rotated.decrypt("session", &sealed).as_deref(), Some("{}"));
+        assert!(rotated.is_stale(&sealed));
+        assert!(rotated.is_stale("{}"));
+    }
+
+    #[test]
+    fn plaintext_only_reads_as_legacy_once_keyed() {
+        let cipher = SessionCipher::from_config(KEY_1);
+        assert_eq!(cipher.decrypt("session", r#"{"a":1}"#), None);
+        assert_eq!(
+            cipher.decrypt_legacy("session", r#"{"a":1}"#).as_deref(),
+            Some(r#"{"a":1}"#)
+        );
+        assert!(cipher.is_stale(r#"{"a":1}"#));
+    }
+
+    #[test]
+    fn empty_config_stores_plaintext() {
+        for config in ["", " ", ","] {
+            let cipher = SessionCipher::from_config(config);
+            assert_eq!(cipher.encrypt("session", r#"{"a":1}"#), r#"{"a":1}"#);
+            assert_eq!(
+                cipher.decrypt("session", r#"{"a":1}"#).as_deref(),
+                Some(r#"{"a":1}"#)
+            );
+            assert!(!cipher.is_stale(r#"{"a":1}"#));
+        }
+    }
+
+    #[test]
+    fn unknown_keys_dont_decrypt() {
+        let sealed = SessionCipher::from_config(KEY_1).encrypt("session", "{}");
+        assert_eq!(
+            SessionCipher::from_config(KEY_2).decrypt("session", &sealed),
+            None
+        );
+        assert_eq!(
+            SessionCipher::from_config("").decrypt("session", &sealed),
+            None
+        );
+    }
+}
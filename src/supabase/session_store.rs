@@ -1,27 +1,109 @@
 use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
 
 use axum::async_trait;
 use axum_login::tower_sessions::{session, session_store, SessionStore};
+use futures_util::future::{BoxFuture, FutureExt, Shared};
 use leptos::serde_json;
-use supabase_rust::errors::{AuthError, Error, ErrorKind, JwtErrorKind, PostgrestError};
+use supabase_rust::errors::{AuthError, Error, ErrorKind, JwtErrorKind};
 use supabase_rust::schema::AccessToken;
-use time::format_description::well_known::Iso8601;
 use time::OffsetDateTime;
 
-use super::{AppUser, IdentityData, SupabaseBackend};
-
-#[derive(serde::Serialize, serde::Deserialize, Debug, Default)]
-struct SessionSchema {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub sb_id: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub id: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub data: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+use super::durable::StoredSession;
+use super::{AppUser, DeviceInfo, IdentityData, Supabase, SupabaseBackend};
+
+/// How long sessions last, from `SESSION_IDLE_TIMEOUT_MINS` and
+/// `SESSION_ABSOLUTE_TIMEOUT_HOURS`
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SessionTimeouts {
+    /// Ends sessions unused for that long, none when unset
+    idle: Option<time::Duration>,
+    /// Ends sessions that long after signing in, sooner if Supabase ends the auth session first
+    absolute: time::Duration,
+}
+
+impl SessionTimeouts {
+    pub fn from_env() -> Self {
+        let idle = std::env::var("SESSION_IDLE_TIMEOUT_MINS")
+            .ok()
+            .and_then(|mins| mins.parse().ok())
+            .map(time::Duration::minutes);
+        let absolute = std::env::var("SESSION_ABSOLUTE_TIMEOUT_HOURS")
+            .ok()
+            .and_then(|hours| hours.parse().ok())
+            .unwrap_or(24);
+        Self {
+            idle,
+            absolute: time::Duration::hours(absolute),
+        }
+    }
+
+    fn is_live(&self, identity: &IdentityData, now: OffsetDateTime) -> bool {
+        identity.expiry_date > now
+            && self
+                .idle
+                .map_or(true, |idle| identity.last_accessed + idle > now)
+    }
+}
+
+/// A row of `public.sessions` as listed to its owner, without the session data
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct UserSession {
+    pub id: String,
+    /// Id of the matching Supabase auth session, safe to hand to the browser unlike `id`
+    pub sb_id: String,
+    pub created_at: Option<String>,
     pub refreshed_at: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub expiry_date: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+/// Tokens handed out for a refresh token, which Supabase rotates on use
+#[derive(Debug, Clone)]
+pub struct RefreshedTokens {
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+/// Spends refresh tokens for `RefreshFlights`
+#[async_trait]
+pub trait TokenRefresher: std::fmt::Debug + Send + Sync {
+    async fn refresh(&self, refresh_token: &str) -> Result<RefreshedTokens, Error>;
+}
+
+#[async_trait]
+impl TokenRefresher for supabase_rust::Supabase {
+    async fn refresh(&self, refresh_token: &str) -> Result<RefreshedTokens, Error> {
+        let access = self.refresh_token(refresh_token).await?;
+        Ok(RefreshedTokens {
+            access_token: access.access_token,
+            refresh_token: access.refresh_token,
+        })
+    }
+}
+
+/// Token refreshes under way by session id. Supabase rotates the refresh token on use, so
+/// concurrent requests of a session with an expired token wait on the same refresh instead
+/// of spending the token once each and signing out all but the first.
+#[derive(Default)]
+pub(crate) struct RefreshFlights(
+    Mutex<HashMap<session::Id, Shared<BoxFuture<'static, Option<session::Record>>>>>,
+);
+
+impl std::fmt::Debug for RefreshFlights {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let in_flight = self
+            .0
+            .lock()
+            .map(|flights| flights.len())
+            .unwrap_or_default();
+        f.debug_struct("RefreshFlights")
+            .field("in_flight", &in_flight)
+            .finish()
+    }
 }
 
 impl SupabaseBackend {
@@ -38,20 +120,12 @@ impl SupabaseBackend {
                 }),
             })?;
 
-        let query = self
-            .client
-            .query()
-            .from("sessions")
-            .eq("sb_id", claims.session_id.clone())
-            .select("expiry_date")
-            .auth(&self.service_key)
-            .execute()
-            .await;
-
-        match supabase_rust::parse_response::<SessionSchema>(query).await {
-            Ok(sessions) if !sessions.is_empty() => {
-                let expiry_date = IdentityData::parse_expiry_date(&sessions[0].expiry_date);
+        match self.durable.auth_session_expiry(&claims.session_id).await {
+            Ok(auth_expiry) => {
+                let expiry_date = OffsetDateTime::now_utc() + self.session_timeouts.absolute;
+                let expiry_date = auth_expiry.map_or(expiry_date, |auth| auth.min(expiry_date));
 
+                let role = IdentityData::role_from_token(&access_token.access_token);
                 let identity = IdentityData {
                     auth_token: access_token.access_token,
                     refresh_token: access_token.refresh_token.clone(),
@@ -61,19 +135,15 @@ impl SupabaseBackend {
                     email: access_token.user.email.clone(),
                     has_mfa: false,
                     aal: claims.aal,
+                    trusted_device: None,
+                    verified_at: Some(OffsetDateTime::now_utc()),
+                    role,
                 };
 
                 let user = AppUser { identity };
 
                 Ok(user)
             }
-            Ok(_) => Err(Error {
-                http_status: 404,
-                kind: ErrorKind::Postgrest(PostgrestError {
-                    message: "Found {{0}} rows matching the query".to_string(),
-                    ..Default::default()
-                }),
-            }),
             Err(e) => Err(e),
         }
     }
@@ -82,8 +152,15 @@ impl SupabaseBackend {
         &self,
         mut session_record: session::Record,
     ) -> Option<(session::Record, bool)> {
+        let now = OffsetDateTime::now_utc();
+        if session_record.expiry_date <= now {
+            return None;
+        }
         let mut dirty = false;
         let mut identity = IdentityData::from_record_data(&session_record.data)?;
+        if !self.session_timeouts.is_live(&identity, now) {
+            return None;
+        }
         let token_validation = self.client.jwt_valid(&identity.auth_token).await;
 
         match token_validation {
@@ -107,19 +184,146 @@ impl SupabaseBackend {
         }
     }
 
+    /// Joins the refresh of the session already under way or starts one. The refreshed record
+    /// is saved once by the refresh, so it's handed back clean to every caller.
     async fn refresh_session_token(
+        &self,
+        identity: IdentityData,
+        session_record: session::Record,
+    ) -> Option<(session::Record, bool)> {
+        let id = session_record.id;
+        let flight = self
+            .refresh_flights
+            .0
+            .lock()
+            .unwrap()
+            .entry(id)
+            .or_insert_with(|| {
+                let backend = self.weak.upgrade().unwrap();
+                async move {
+                    let refreshed = backend.refresh_and_save(identity, session_record).await;
+                    backend.refresh_flights.0.lock().unwrap().remove(&id);
+                    refreshed
+                }
+                .boxed()
+                .shared()
+            })
+            .clone();
+
+        flight.await.map(|record| (record, false))
+    }
+
+    async fn refresh_and_save(
         &self,
         mut identity: IdentityData,
         mut session_record: session::Record,
-    ) -> Option<(session::Record, bool)> {
-        if let Ok(access) = self.client.refresh_token(&identity.refresh_token).await {
-            identity.auth_token = access.access_token;
-            identity.refresh_token = access.refresh_token;
-            identity.last_accessed = OffsetDateTime::now_utc();
-            session_record.data = identity.into_record_data();
-            return Some((session_record, true));
+    ) -> Option<session::Record> {
+        // A refresh that finished just before this one started already spent the token
+        if let Ok(Some(cached)) = self.sessions_cache.load(&session_record.id).await {
+            let refreshed = IdentityData::from_record_data(&cached.data)
+                .is_some_and(|cached| cached.refresh_token != identity.refresh_token);
+            if refreshed {
+                return Some(cached);
+            }
+        }
+
+        let access = self
+            .token_refresher
+            .refresh(&identity.refresh_token)
+            .await
+            .ok()?;
+        identity.role = IdentityData::role_from_token(&access.access_token);
+        identity.auth_token = access.access_token;
+        identity.refresh_token = access.refresh_token;
+        identity.last_accessed = OffsetDateTime::now_utc();
+        session_record.data = identity.into_record_data();
+        if let Err(e) = self.save(&session_record).await {
+            tracing::error!("\nSessionStore.refresh_and_save - {e:?}");
+        }
+        Some(session_record)
+    }
+
+    fn seal_record_data(&self, record: &session::Record) -> String {
+        let data = serde_json::to_string(&record.data).unwrap_or_default();
+        self.session_cipher.encrypt(&record.id.to_string(), &data)
+    }
+
+    /// Re-encrypts the sessions stored in plaintext or with a key other than the current one,
+    /// to run after adding a key to `SESSION_ENCRYPTION_KEYS`. Returns how many were moved.
+    pub async fn reencrypt_sessions(&self) -> Result<usize, Error> {
+        const BATCH: usize = 500;
+
+        let mut last_id = String::default();
+        let mut reencrypted = 0;
+        loop {
+            let sessions = self.durable.page(&last_id, BATCH).await?;
+            let Some((id, _)) = sessions.last() else {
+                break;
+            };
+            last_id = id.clone();
+
+            for (id, stored) in &sessions {
+                if !self.session_cipher.is_stale(stored) {
+                    continue;
+                }
+                // Undecryptable sessions are left to expire, there's nothing to move
+                let Some(data) = self.session_cipher.decrypt_legacy(id, stored) else {
+                    continue;
+                };
+                let sealed = self.session_cipher.encrypt(id, &data);
+                self.durable.replace_data(id, &sealed).await?;
+                reencrypted += 1;
+            }
+        }
+        Ok(reencrypted)
+    }
+
+    /// Deletes the expired sessions from the durable tier and the cache. Returns how many.
+    /// Sessions never given an expiry go once past the absolute timeout.
+    pub async fn reap_expired_sessions(&self) -> Result<usize, Error> {
+        let now = OffsetDateTime::now_utc();
+        let expired = self
+            .durable
+            .delete_expired(now, now - self.session_timeouts.absolute)
+            .await?;
+        for id in &expired {
+            if let Ok(id) = session::Id::from_str(id) {
+                let _ = self.sessions_cache.delete(&id).await;
+            }
         }
-        None
+        Ok(expired.len())
+    }
+
+    /// Sessions of `user_id`, most recently refreshed first
+    pub async fn list_user_sessions(&self, user_id: &str) -> Result<Vec<UserSession>, Error> {
+        self.durable.list_user(user_id).await
+    }
+
+    /// Ends the sessions of `user_id` in the cache and the table, either the one matching
+    /// `sb_id` or all of them but `keep`. Returns how many were ended.
+    pub async fn revoke_user_sessions(
+        &self,
+        user_id: &str,
+        sb_id: Option<&str>,
+        keep: Option<&str>,
+    ) -> Result<usize, Error> {
+        let sessions = self.list_user_sessions(user_id).await?;
+
+        let mut revoked = 0;
+        for session in sessions {
+            if sb_id.is_some_and(|sb_id| sb_id != session.sb_id)
+                || keep.is_some_and(|keep| keep == session.id)
+            {
+                continue;
+            }
+            let Ok(id) = session::Id::from_str(&session.id) else {
+                continue;
+            };
+            if self.delete(&id).await.is_ok() {
+                revoked += 1;
+            }
+        }
+        Ok(revoked)
     }
 
     pub async fn update_assurance_level(
@@ -136,9 +340,23 @@ impl SupabaseBackend {
             .unwrap();
         identity.last_accessed = OffsetDateTime::now_utc();
         identity.refresh_token = token.refresh_token.clone();
+        identity.role = IdentityData::role_from_token(&token.access_token);
         identity.auth_token = token.access_token;
         identity.aal = claims.aal;
-        let _ = self.save(&identity.into_session_record(session_id)).await;
+        identity.verified_at = Some(OffsetDateTime::now_utc());
+        self.update_identity(session_id, identity).await;
+    }
+
+    /// Replaces the identity kept in the session, leaving the rest of its data as is
+    pub async fn update_identity(&self, session_id: &str, identity: IdentityData) {
+        let mut record = identity.into_session_record(session_id);
+        // Keep what else the session holds, like its device or a pending password recovery
+        if let Ok(Some(existing)) = self.sessions_cache.load(&record.id).await {
+            let mut data = existing.data;
+            data.extend(record.data);
+            record.data = data;
+        }
+        let _ = self.save(&record).await;
     }
 }
 
@@ -147,27 +365,10 @@ impl SessionStore for SupabaseBackend {
     async fn save(&self, record: &session::Record) -> session_store::Result<()> {
         self.sessions_cache.save(record).await?;
 
-        let session_schema = serde_json::to_string(&SessionSchema {
-            id: Some(record.id.to_string()),
-            data: Some(serde_json::to_string(&record.data).unwrap_or_default()),
-            refreshed_at: Some(OffsetDateTime::now_utc().format(&Iso8601::DEFAULT).unwrap()),
-            expiry_date: Some(record.expiry_date.format(&Iso8601::DEFAULT).unwrap()),
-            ..Default::default()
-        })
-        .unwrap();
-
-        let query = self
-            .client
-            .query()
-            .from("sessions")
-            .update(session_schema)
-            .eq("id", record.id.to_string())
-            .auth(&self.service_key)
-            .execute()
-            .await;
-
-        if query.is_err() {
-            tracing::error!("\nSessionStore.save is_err");
+        let data = self.seal_record_data(record);
+        let id = record.id.to_string();
+        if let Err(e) = self.durable.save(&id, &data, record.expiry_date).await {
+            tracing::error!("\nSessionStore.save - {e:?}");
         }
         Ok(())
     }
@@ -195,36 +396,28 @@ impl SessionStore for SupabaseBackend {
             return Ok(Some(record));
         }
 
-        let query = self
-            .client
-            .query()
-            .from("sessions")
-            .select("data,expiry_date")
-            .eq("id", id.to_string())
-            .single()
-            .auth(&self.service_key)
-            .execute()
-            .await;
-
-        let sessions = supabase_rust::parse_response::<SessionSchema>(query)
+        let stored = self
+            .durable
+            .load(&id.to_string())
             .await
             .map_err(|e| {
                 tracing::error!("\nSessionStore.load - {e:?}");
             })
             .ok()
-            .unwrap_or_default();
+            .flatten();
 
-        if sessions.is_empty() {
+        // Sessions are always stored with an expiry, one without never finished signing in
+        let Some((stored, Some(expiry_date))) = stored else {
             let _ = self.delete(id).await;
             return Ok(None);
-        }
+        };
 
-        let expiry_date = IdentityData::parse_expiry_date(&sessions[0].expiry_date);
-        let data = sessions[0]
-            .data
-            .as_ref()
+        let stale = self.session_cipher.is_stale(&stored);
+        let data = self
+            .session_cipher
+            .decrypt(&id.to_string(), &stored)
             .and_then(|data_str| {
-                serde_json::from_str::<HashMap<String, serde_json::Value>>(data_str)
+                serde_json::from_str::<HashMap<String, serde_json::Value>>(&data_str)
                     .map_err(|e| {
                         tracing::error!("\nSessionStore.load.parse_data - {e:?}");
                         e
@@ -241,7 +434,8 @@ impl SessionStore for SupabaseBackend {
 
         match self.validate_session(session_record).await {
             Some((record, dirty)) => {
-                if dirty {
+                // Saving again moves rotated out keys to the current key
+                if dirty || stale {
                     let _ = self.save(&record).await;
                 } else {
                     self.sessions_cache.save(&record).await?;
@@ -258,15 +452,7 @@ impl SessionStore for SupabaseBackend {
     async fn delete(&self, id: &session::Id) -> session_store::Result<()> {
         self.sessions_cache.delete(id).await?;
 
-        let _ = self
-            .client
-            .query()
-            .from("sessions")
-            .delete()
-            .eq("id", id.to_string())
-            .auth(&self.service_key)
-            .execute()
-            .await;
+        let _ = self.durable.delete(&id.to_string()).await;
         Ok(())
     }
 
@@ -278,28 +464,134 @@ impl SessionStore for SupabaseBackend {
 
         self.sessions_cache.create(record).await?;
 
-        let session_schema = serde_json::to_string(&SessionSchema {
-            id: Some(record.id.to_string()),
-            data: Some(serde_json::to_string(&record.data).unwrap_or_default()),
-            expiry_date: Some(record.expiry_date.format(&Iso8601::DEFAULT).unwrap()),
-            ..Default::default()
-        })
-        .unwrap();
+        let device = record
+            .data
+            .get(DeviceInfo::SESSION_KEY)
+            .and_then(|device| serde_json::from_value::<DeviceInfo>(device.clone()).ok())
+            .unwrap_or_default();
 
-        let query = self
-            .client
-            .query()
-            .from("sessions")
-            // When authenticated a trigger inserts a new entry into the table so we UPDATE here
-            .update(session_schema)
-            .eq("sb_id", claims.session_id)
-            .auth(&self.service_key)
-            .execute()
-            .await;
-
-        if query.is_err() {
-            tracing::error!("\nSessionStore.create is_err");
+        let session = StoredSession {
+            id: record.id.to_string(),
+            sb_id: claims.session_id,
+            user_id: identity.user_id,
+            data: self.seal_record_data(record),
+            expiry_date: record.expiry_date,
+            user_agent: device.user_agent,
+            ip_address: device.ip_address,
+        };
+
+        if let Err(e) = self.durable.create(session).await {
+            tracing::error!("\nSessionStore.create - {e:?}");
         }
         Ok(())
     }
 }
+
+/// Reaps the expired sessions every `every`, `load` would otherwise only find those that
+/// are asked for again
+pub async fn run_session_reaper(supabase: Supabase, every: Duration) {
+    let mut interval = tokio::time::interval(every);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        interval.tick().await;
+        match supabase.reap_expired_sessions().await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!(count, "Reaped expired sessions"),
+            Err(e) => tracing::error!("\nSession reaper - {e:?}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+
+    /// Hands out `access-<n>` and `refresh-<n>` on its n-th call
+    #[derive(Debug, Default)]
+    struct CountingRefresher {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl TokenRefresher for CountingRefresher {
+        async fn refresh(&self, _refresh_token: &str) -> Result<RefreshedTokens, Error> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            // Slow enough for the other requests to arrive while it runs
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Ok(RefreshedTokens {
+                access_token: format!("access-{call}"),
+                refresh_token: format!("refresh-{call}"),
+            })
+        }
+    }
+
+    fn expired_record() -> (IdentityData, session::Record) {
+        let now = OffsetDateTime::now_utc();
+        let identity = IdentityData {
+            auth_token: "access-0".to_string(),
+            refresh_token: "refresh-0".to_string(),
+            expiry_date: now + time::Duration::hours(1),
+            last_accessed: now,
+            user_id: "2d4bd2a4-4b6f-4b47-8f2e-0d5fb0a5a4c1".to_string(),
+            email: "user@example.com".to_string(),
+            has_mfa: false,
+            aal: "aal1".to_string(),
+            trusted_device: None,
+            verified_at: None,
+            role: None,
+        };
+        let record = session::Record {
+            id: session::Id::default(),
+            data: identity.clone().into_record_data(),
+            expiry_date: identity.expiry_date,
+        };
+        (identity, record)
+    }
+
+    #[tokio::test]
+    async fn concurrent_refreshes_spend_the_token_once() {
+        let refresher = Arc::new(CountingRefresher::default());
+        let backend = crate::supabase::test_backend(Some(refresher.clone()));
+        let (identity, record) = expired_record();
+
+        let refreshes = (0..16)
+            .map(|_| backend.refresh_session_token(identity.clone(), record.clone()))
+            .collect::<Vec<_>>();
+        let refreshed = futures_util::future::join_all(refreshes).await;
+
+        assert_eq!(refresher.calls.load(Ordering::SeqCst), 1);
+        for result in refreshed {
+            let (record, dirty) = result.expect("every caller gets the refreshed session");
+            let identity = IdentityData::from_record_data(&record.data).unwrap();
+            assert_eq!(identity.auth_token, "access-1");
+            assert_eq!(identity.refresh_token, "refresh-1");
+            assert!(!dirty);
+        }
+        assert!(backend.refresh_flights.0.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn late_refreshes_take_the_saved_tokens() {
+        let refresher = Arc::new(CountingRefresher::default());
+        let backend = crate::supabase::test_backend(Some(refresher.clone()));
+        let (identity, record) = expired_record();
+
+        backend
+            .refresh_session_token(identity.clone(), record.clone())
+            .await
+            .unwrap();
+        // Still holding the token spent by the first refresh
+        let (record, _) = backend
+            .refresh_session_token(identity, record)
+            .await
+            .unwrap();
+
+        assert_eq!(refresher.calls.load(Ordering::SeqCst), 1);
+        let identity = IdentityData::from_record_data(&record.data).unwrap();
+        assert_eq!(identity.refresh_token, "refresh-1");
+    }
+}
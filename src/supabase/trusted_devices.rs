@@ -0,0 +1,171 @@
+//! Browsers allowed to skip the 2FA challenge at sign in, remembered by a signed cookie
+
+use hmac::{Hmac, Mac};
+use leptos::serde_json;
+use sha2::Sha256;
+use supabase_rust::errors::{AuthError, Error, ErrorKind};
+use time::format_description::well_known::Iso8601;
+use time::OffsetDateTime;
+
+use super::{DeviceInfo, SupabaseBackend};
+
+/// How long a browser stays trusted
+pub const TRUSTED_DEVICE_DAYS: i64 = 30;
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Default)]
+pub struct TrustedDeviceSchema {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip_address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+}
+
+impl SupabaseBackend {
+    fn sign_device(&self, user_id: &str, device_id: u64, expires: i64) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.device_secret.as_bytes())
+            .expect("HMAC can take a key of any size");
+        mac.update(format!("{user_id}.{device_id}.{expires}").as_bytes());
+        mac.finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+
+    /// Trusts the browser for `TRUSTED_DEVICE_DAYS`, returns the value of its cookie
+    pub async fn trust_device(&self, user_id: &str, device: DeviceInfo) -> Result<String, Error> {
+        let expires_at = OffsetDateTime::now_utc() + time::Duration::days(TRUSTED_DEVICE_DAYS);
+
+        let row = serde_json::to_string(&TrustedDeviceSchema {
+            user_id: Some(user_id.to_string()),
+            user_agent: device.user_agent,
+            ip_address: device.ip_address,
+            expires_at: Some(expires_at.format(&Iso8601::DEFAULT).unwrap()),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let query = self
+            .client
+            .query()
+            .from("trusted_devices")
+            .insert(row)
+            .auth(&self.service_key)
+            .execute()
+            .await;
+
+        let device_id = supabase_rust::parse_response::<TrustedDeviceSchema>(query)
+            .await?
+            .first()
+            .and_then(|device| device.id)
+            .ok_or(Error {
+                http_status: 500,
+                kind: ErrorKind::Auth(AuthError {
+                    msg: Some("Trusted device wasn't saved".to_string()),
+                    ..Default::default()
+                }),
+            })?;
+
+        let expires = expires_at.unix_timestamp();
+        let signature = self.sign_device(user_id, device_id, expires);
+        Ok(format!("{device_id}.{expires}.{signature}"))
+    }
+
+    /// Id of the trusted device the cookie stands for, if it belongs to `user_id`,
+    /// hasn't expired and wasn't revoked
+    pub async fn verify_trusted_device(&self, user_id: &str, cookie: &str) -> Option<u64> {
+        let mut parts = cookie.splitn(3, '.');
+        let device_id: u64 = parts.next()?.parse().ok()?;
+        let expires: i64 = parts.next()?.parse().ok()?;
+        let signature = parts.next()?;
+
+        // Compared in constant time through the MAC
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.device_secret.as_bytes()).ok()?;
+        mac.update(format!("{user_id}.{device_id}.{expires}").as_bytes());
+        let signature = (0..signature.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(signature.get(i..i + 2)?, 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        mac.verify_slice(&signature).ok()?;
+
+        if expires < OffsetDateTime::now_utc().unix_timestamp() {
+            return None;
+        }
+
+        let update = serde_json::to_string(&TrustedDeviceSchema {
+            last_used_at: Some(OffsetDateTime::now_utc().format(&Iso8601::DEFAULT).unwrap()),
+            ..Default::default()
+        })
+        .unwrap();
+
+        // Revoked devices have no row left to update
+        let query = self
+            .client
+            .query()
+            .from("trusted_devices")
+            .update(update)
+            .eq("id", device_id.to_string())
+            .eq("user_id", user_id)
+            .auth(&self.service_key)
+            .execute()
+            .await;
+
+        supabase_rust::parse_response::<TrustedDeviceSchema>(query)
+            .await
+            .ok()
+            .filter(|devices| !devices.is_empty())
+            .map(|_| device_id)
+    }
+
+    pub async fn list_trusted_devices(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<TrustedDeviceSchema>, Error> {
+        let now = OffsetDateTime::now_utc().format(&Iso8601::DEFAULT).unwrap();
+        let query = self
+            .client
+            .query()
+            .from("trusted_devices")
+            .select("id,user_agent,ip_address,created_at,last_used_at,expires_at")
+            .eq("user_id", user_id)
+            .gt("expires_at", now)
+            .order("created_at.desc")
+            .auth(&self.service_key)
+            .execute()
+            .await;
+
+        supabase_rust::parse_response::<TrustedDeviceSchema>(query).await
+    }
+
+    /// Revokes one trusted device of `user_id`, or all of them
+    pub async fn revoke_trusted_devices(
+        &self,
+        user_id: &str,
+        device_id: Option<u64>,
+    ) -> Result<(), Error> {
+        let mut query = self
+            .client
+            .query()
+            .from("trusted_devices")
+            .delete()
+            .eq("user_id", user_id);
+        if let Some(device_id) = device_id {
+            query = query.eq("id", device_id.to_string());
+        }
+
+        let query = query.auth(&self.service_key).execute().await;
+        supabase_rust::parse_response::<TrustedDeviceSchema>(query)
+            .await
+            .map(|_| ())
+    }
+}
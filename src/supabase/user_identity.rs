@@ -4,10 +4,9 @@ use std::str::FromStr;
 use axum_login::tower_sessions::session;
 use leptos::serde_json;
 use time::ext::NumericalDuration;
-use time::format_description::well_known::Iso8601;
 use time::OffsetDateTime;
 
-#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
 pub struct IdentityData {
     pub auth_token: String,
     pub refresh_token: String,
@@ -17,6 +16,16 @@ pub struct IdentityData {
     pub email: String,
     pub has_mfa: bool,
     pub aal: String,
+    /// Set when the 2FA challenge was skipped for a trusted browser, the session stays
+    /// at `aal1` for sensitive actions
+    #[serde(default)]
+    pub trusted_device: Option<u64>,
+    /// When the user last proved who they are, by signing in or verifying again
+    #[serde(default)]
+    pub verified_at: Option<OffsetDateTime>,
+    /// `app_metadata.role` of the access token, kept in step with it on every refresh
+    #[serde(default)]
+    pub role: Option<String>,
 }
 
 impl IdentityData {
@@ -57,11 +66,35 @@ impl IdentityData {
         }
     }
 
-    pub fn parse_expiry_date(expiry_date: &Option<String>) -> OffsetDateTime {
-        expiry_date
-            .as_ref()
-            .and_then(|date_str| OffsetDateTime::parse(date_str, &Iso8601::DEFAULT).ok())
-            .unwrap_or_else(|| OffsetDateTime::now_utc() + time::Duration::days(1))
+    /// Reads `app_metadata.role` from a token `jwt_valid` already accepted. Only the service
+    /// role can write `app_metadata`, unlike `user_metadata`.
+    pub fn role_from_token(token: &str) -> Option<String> {
+        use base64::prelude::{Engine as _, BASE64_URL_SAFE_NO_PAD};
+
+        let payload = BASE64_URL_SAFE_NO_PAD
+            .decode(token.split('.').nth(1)?)
+            .ok()?;
+        let claims = serde_json::from_slice::<serde_json::Value>(&payload).ok()?;
+        claims
+            .get("app_metadata")?
+            .get("role")?
+            .as_str()
+            .map(str::to_string)
+    }
+
+    pub fn has_role(&self, role: &str) -> bool {
+        self.role.as_deref() == Some(role)
+    }
+
+    /// The user has a second factor the session hasn't verified yet
+    pub fn needs_second_factor(&self) -> bool {
+        self.has_mfa && self.aal != "aal2"
+    }
+
+    /// Raises the session to `aal2` in the app, for factors Supabase doesn't know about
+    pub fn verify_second_factor(&mut self) {
+        self.aal = "aal2".to_string();
+        self.verified_at = Some(OffsetDateTime::now_utc());
     }
 
     pub fn should_refetch_user(&self) -> bool {
@@ -69,18 +102,63 @@ impl IdentityData {
     }
 }
 
+/// Leaves the tokens out, sessions and users are debug printed in error logs
+impl std::fmt::Debug for IdentityData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IdentityData")
+            .field("auth_token", &"[redacted]")
+            .field("refresh_token", &"[redacted]")
+            .field("expiry_date", &self.expiry_date)
+            .field("last_accessed", &self.last_accessed)
+            .field("user_id", &self.user_id)
+            .field("email", &self.email)
+            .field("has_mfa", &self.has_mfa)
+            .field("aal", &self.aal)
+            .field("trusted_device", &self.trusted_device)
+            .field("verified_at", &self.verified_at)
+            .field("role", &self.role)
+            .finish()
+    }
+}
+
+/// Leaves the tokens out, what's displayed ends up in logs
 impl std::fmt::Display for IdentityData {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "IdentityData {{ auth_token: '{}', refresh_token: '{}', \
+            "IdentityData {{ auth_token: [redacted], refresh_token: [redacted], \
                 expiry_date: '{}', last_accessed: '{}', user_id: '{}', email: '{}' }}",
-            self.auth_token,
-            self.refresh_token,
-            self.expiry_date,
-            self.last_accessed,
-            self.user_id,
-            self.email
+            self.expiry_date, self.last_accessed, self.user_id, self.email
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_leaves_the_tokens_out() {
+        let identity = IdentityData {
+            auth_token: "access-secret".to_string(),
+            refresh_token: "refresh-secret".to_string(),
+            expiry_date: OffsetDateTime::now_utc(),
+            last_accessed: OffsetDateTime::now_utc(),
+            user_id: "user".to_string(),
+            email: "user@example.com".to_string(),
+            has_mfa: false,
+            aal: "aal1".to_string(),
+            trusted_device: None,
+            verified_at: None,
+            role: None,
+        };
+        for printed in [
+            format!("{identity:?}"),
+            format!("{identity:#?}"),
+            format!("{identity}"),
+        ] {
+            assert!(!printed.contains("secret"), "{printed}");
+            assert!(printed.contains("user@example.com"));
+        }
+    }
+}
@@ -0,0 +1,147 @@
+mod target;
+mod worker;
+
+use leptos::serde_json;
+use time::format_description::well_known::Iso8601;
+use time::OffsetDateTime;
+
+use crate::components::todo::TaskSchema;
+use crate::supabase::Supabase;
+
+pub use target::{check_url, resolve};
+pub use worker::run_worker;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TaskEvent {
+    Created,
+    Updated,
+    Completed,
+    Deleted,
+}
+
+impl TaskEvent {
+    pub const ALL: [TaskEvent; 4] = [
+        TaskEvent::Created,
+        TaskEvent::Updated,
+        TaskEvent::Completed,
+        TaskEvent::Deleted,
+    ];
+
+    /// Name stored in `webhooks.events`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskEvent::Created => "created",
+            TaskEvent::Updated => "updated",
+            TaskEvent::Completed => "completed",
+            TaskEvent::Deleted => "deleted",
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default)]
+pub struct WebhookSchema {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub events: Option<Vec<String>>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default)]
+pub struct DeliverySchema {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webhook_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attempts: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_attempt_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_status_code: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delivered_at: Option<String>,
+}
+
+/// Queues a delivery for every hook of `user_id` subscribed to `event`.
+/// The deliveries are sent by [`run_worker`], so this never blocks on the hooks themselves.
+///
+/// `auth_token` is the user's JWT, or the service key when acting on a personal access token.
+pub async fn emit(
+    supabase: Supabase,
+    user_id: String,
+    auth_token: String,
+    event: TaskEvent,
+    task: TaskSchema,
+) {
+    let query = supabase
+        .client
+        .query()
+        .from("webhooks")
+        .select("id")
+        .eq("owner_id", user_id.clone())
+        .cs("events", format!("{{{}}}", event.as_str()))
+        .auth(&auth_token)
+        .execute()
+        .await;
+
+    let hooks = match supabase_rust::parse_response::<WebhookSchema>(query).await {
+        Ok(hooks) => hooks,
+        Err(e) => {
+            tracing::error!("\nwebhooks::emit - {e:?}");
+            return;
+        }
+    };
+    if hooks.is_empty() {
+        return;
+    }
+
+    let now = OffsetDateTime::now_utc().format(&Iso8601::DEFAULT).unwrap();
+    let payload = serde_json::json!({
+        "event": format!("task.{}", event.as_str()),
+        "occurred_at": now,
+        "task": task,
+    });
+
+    let deliveries: Vec<DeliverySchema> = hooks
+        .into_iter()
+        .map(|hook| DeliverySchema {
+            webhook_id: hook.id,
+            owner_id: Some(user_id.clone()),
+            event: Some(event.as_str().to_string()),
+            payload: Some(payload.clone()),
+            next_attempt_at: Some(now.clone()),
+            ..Default::default()
+        })
+        .collect();
+
+    let query = supabase
+        .client
+        .query()
+        .from("webhook_deliveries")
+        .insert(serde_json::to_string(&deliveries).unwrap())
+        .auth(&auth_token)
+        .execute()
+        .await;
+
+    if let Err(e) = supabase_rust::parse_response::<DeliverySchema>(query).await {
+        tracing::error!("\nwebhooks::emit queue - {e:?}");
+    }
+}
@@ -0,0 +1,167 @@
+//! Webhook URLs are chosen by users and called from the server, so they may only point to
+//! public addresses. The check runs when a hook is added and again on the addresses the
+//! host resolves to at delivery, which are then the only ones the request may connect to.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use reqwest::Url;
+
+/// Parses `url` and rejects anything but http(s) to a public host
+pub fn check_url(url: &str) -> Result<Url, String> {
+    let url = Url::parse(url).map_err(|e| e.to_string())?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("Unsupported scheme {}", url.scheme()));
+    }
+    let host = host(&url).ok_or("Missing host")?.to_ascii_lowercase();
+    match host.parse::<IpAddr>() {
+        Ok(ip) if !is_public(ip) => Err(format!("{ip} isn't a public address")),
+        Ok(_) => Ok(url),
+        Err(_) if host == "localhost" || host.ends_with(".localhost") => {
+            Err(format!("{host} isn't a public host"))
+        }
+        Err(_) => Ok(url),
+    }
+}
+
+/// Host of `url` without the brackets of IPv6 addresses
+fn host(url: &Url) -> Option<&str> {
+    url.host_str()
+        .map(|host| host.trim_start_matches('[').trim_end_matches(']'))
+}
+
+/// Resolves the host of `url`, failing unless every address it has is public
+pub async fn resolve(url: &Url) -> Result<Vec<SocketAddr>, String> {
+    let host = host(url).ok_or("Missing host")?;
+    let port = url.port_or_known_default().unwrap_or(443);
+
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| format!("Can't resolve {host}: {e}"))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(format!("{host} has no address"));
+    }
+    if let Some(addr) = addrs.iter().find(|addr| !is_public(addr.ip())) {
+        return Err(format!(
+            "{host} resolves to {}, not a public address",
+            addr.ip()
+        ));
+    }
+    Ok(addrs)
+}
+
+/// Client for one delivery to `url`, connecting only to `addrs` and not following redirects
+pub fn pinned_client(url: &Url, addrs: &[SocketAddr]) -> reqwest::Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(10))
+        .redirect(reqwest::redirect::Policy::none());
+    if let Some(domain) = host(url).filter(|host| host.parse::<IpAddr>().is_err()) {
+        builder = builder.resolve_to_addrs(domain, addrs);
+    }
+    builder.build()
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_v4(ip),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        // 0.0.0.0/8, carrier-grade NAT 100.64.0.0/10 and benchmarking 198.18.0.0/15
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 198 && (18..20).contains(&b)))
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let embedded_v4 =
+        |high: u16, low: u16| Ipv4Addr::from((u32::from(high) << 16) | u32::from(low));
+    match ip.segments() {
+        // NAT64 64:ff9b::/96 and IPv4-compatible ::/96 reach the address in the last 32 bits,
+        // :: and ::1 land in 0.0.0.0/8
+        [0x64, 0xff9b, 0, 0, 0, 0, high, low] | [0, 0, 0, 0, 0, 0, high, low] => {
+            is_public_v4(embedded_v4(high, low))
+        }
+        // 6to4 2002::/16 has it right after the prefix
+        [0x2002, high, low, ..] => is_public_v4(embedded_v4(high, low)),
+        // Documentation 2001:db8::/32
+        [0x2001, 0xdb8, ..] => false,
+        [first, ..] => {
+            !(ip.is_loopback()
+                || ip.is_multicast()
+                // Unique local fc00::/7 and link-local fe80::/10
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_url_rejects_internal_addresses() {
+        for url in [
+            "http://127.0.0.1/hook",
+            "http://10.0.0.8/hook",
+            "http://192.168.1.1/hook",
+            "http://172.16.0.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://0.0.0.0:8080/",
+            "http://100.64.0.1/",
+            "http://[::1]/hook",
+            "http://[fd00::1]/hook",
+            "http://[fe80::1]/hook",
+            "http://[::ffff:127.0.0.1]/hook",
+            "http://[::127.0.0.1]/hook",
+            "http://[::]/hook",
+            "http://[64:ff9b::a9fe:a9fe]/latest/meta-data",
+            "http://[64:ff9b::10.0.0.8]/hook",
+            "http://[2002:7f00:1::]/hook",
+            "http://[2002:c0a8:101::1]/hook",
+            "http://[2001:db8::1]/hook",
+            "http://localhost:3000/hook",
+            "http://api.localhost/hook",
+        ] {
+            assert!(check_url(url).is_err(), "{url} was accepted");
+        }
+    }
+
+    #[test]
+    fn check_url_accepts_public_hosts() {
+        for url in [
+            "https://example.com/hook",
+            "http://93.184.216.34:8080/hook",
+            "http://[64:ff9b::5db8:d822]/hook",
+            "http://[2606:4700::1111]/hook",
+        ] {
+            assert!(check_url(url).is_ok(), "{url} was rejected");
+        }
+    }
+
+    #[test]
+    fn check_url_rejects_other_schemes() {
+        assert!(check_url("file:///etc/passwd").is_err());
+        assert!(check_url("gopher://example.com/").is_err());
+    }
+
+    #[tokio::test]
+    async fn resolve_rejects_hosts_pointing_inside() {
+        let url = Url::parse("http://localhost/hook").unwrap();
+        assert!(resolve(&url).await.is_err());
+    }
+}
@@ -49,3 +49,220 @@ create trigger on_auth_session_created
 after insert on auth.sessions for each row
 execute procedure public.handle_session_created ();
 
+
+create table
+  public.reminders (
+    id bigint generated by default as identity,
+    task_id bigint not null,
+    author_id uuid not null,
+    remind_at timestamp with time zone not null,
+    notify_at timestamp with time zone not null,
+    sent_at timestamp with time zone null,
+    constraint reminders_pkey primary key (id),
+    constraint reminders_task_id_key unique (task_id),
+    constraint reminders_task_id_fkey foreign key (task_id) references public.tasks (id) on delete cascade,
+    constraint reminders_author_id_fkey foreign key (author_id) references auth.users (id) on delete cascade
+  ) tablespace pg_default;
+
+create index reminders_pending_idx on public.reminders (notify_at) where sent_at is null;
+
+alter table public.reminders enable row level security;
+create policy "Individuals can create reminders." on public.reminders for
+    insert with check (
+        auth.uid() = author_id
+        and exists (select 1 from public.tasks t where t.id = task_id and t.author_id = auth.uid())
+    );
+create policy "Individuals can view their own reminders." on public.reminders for
+    select using ((select auth.uid()) = author_id);
+create policy "Individuals can update their own reminders." on public.reminders for
+    update using ((select auth.uid()) = author_id)
+    with check (
+        auth.uid() = author_id
+        and exists (select 1 from public.tasks t where t.id = task_id and t.author_id = auth.uid())
+    );
+create policy "Individuals can delete their own reminders." on public.reminders for
+    delete using ((select auth.uid()) = author_id);
+
+create table
+  public.user_settings (
+    user_id uuid not null,
+    reminder_lead_minutes integer not null default 15,
+    constraint user_settings_pkey primary key (user_id),
+    constraint user_settings_user_id_fkey foreign key (user_id) references auth.users (id) on delete cascade,
+    constraint user_settings_lead_minutes_check check (reminder_lead_minutes >= 0)
+  ) tablespace pg_default;
+
+alter table public.user_settings enable row level security;
+create policy "Individuals can create their own settings." on public.user_settings for
+    insert with check (auth.uid() = user_id);
+create policy "Individuals can view their own settings." on public.user_settings for
+    select using ((select auth.uid()) = user_id);
+create policy "Individuals can update their own settings." on public.user_settings for
+    update using ((select auth.uid()) = user_id);
+
+create table
+  public.webhooks (
+    id bigint generated by default as identity,
+    owner_id uuid not null,
+    url text not null,
+    secret text not null,
+    events text[] not null default '{}',
+    created_at timestamp with time zone not null default now(),
+    constraint webhooks_pkey primary key (id),
+    constraint webhooks_owner_id_fkey foreign key (owner_id) references auth.users (id) on delete cascade,
+    constraint webhooks_events_check check (events <@ array['created', 'updated', 'completed', 'deleted'])
+  ) tablespace pg_default;
+
+alter table public.webhooks enable row level security;
+create policy "Individuals can create webhooks." on public.webhooks for
+    insert with check (auth.uid() = owner_id);
+create policy "Individuals can view their own webhooks." on public.webhooks for
+    select using ((select auth.uid()) = owner_id);
+create policy "Individuals can delete their own webhooks." on public.webhooks for
+    delete using ((select auth.uid()) = owner_id);
+
+create table
+  public.webhook_deliveries (
+    id bigint generated by default as identity,
+    webhook_id bigint not null,
+    owner_id uuid not null,
+    event text not null,
+    payload jsonb not null,
+    status text not null default 'pending',
+    attempts integer not null default 0,
+    next_attempt_at timestamp with time zone not null default now(),
+    last_status_code integer null,
+    last_error text null,
+    created_at timestamp with time zone not null default now(),
+    delivered_at timestamp with time zone null,
+    constraint webhook_deliveries_pkey primary key (id),
+    constraint webhook_deliveries_webhook_id_fkey foreign key (webhook_id) references public.webhooks (id) on delete cascade,
+    constraint webhook_deliveries_status_check check (status in ('pending', 'delivered', 'failed'))
+  ) tablespace pg_default;
+
+create index webhook_deliveries_pending_idx on public.webhook_deliveries (next_attempt_at) where status = 'pending';
+
+alter table public.webhook_deliveries enable row level security;
+create policy "Individuals can queue deliveries for their webhooks." on public.webhook_deliveries for
+    insert with check (
+      auth.uid() = owner_id
+      and exists (
+        select 1 from public.webhooks w where w.id = webhook_id and w.owner_id = auth.uid()
+      )
+    );
+create policy "Individuals can view their own webhook deliveries." on public.webhook_deliveries for
+    select using ((select auth.uid()) = owner_id);
+
+create table
+  public.personal_access_tokens (
+    id bigint generated by default as identity,
+    owner_id uuid not null,
+    name text not null,
+    token_hash text not null,
+    scopes text[] not null default '{read}',
+    created_at timestamp with time zone not null default now(),
+    last_used_at timestamp with time zone null,
+    constraint personal_access_tokens_pkey primary key (id),
+    constraint personal_access_tokens_token_hash_key unique (token_hash),
+    constraint personal_access_tokens_owner_id_fkey foreign key (owner_id) references auth.users (id) on delete cascade,
+    constraint personal_access_tokens_scopes_check check (scopes <@ array['read', 'write'])
+  ) tablespace pg_default;
+
+alter table public.personal_access_tokens enable row level security;
+create policy "Individuals can create access tokens." on public.personal_access_tokens for
+    insert with check (auth.uid() = owner_id);
+create policy "Individuals can view their own access tokens." on public.personal_access_tokens for
+    select using ((select auth.uid()) = owner_id);
+create policy "Individuals can revoke their own access tokens." on public.personal_access_tokens for
+    delete using ((select auth.uid()) = owner_id);
+
+alter table public.tasks
+  add column due_at timestamp with time zone null,
+  add column tags text[] not null default '{}',
+  add column priority text null,
+  add constraint tasks_priority_check check (priority in ('low', 'medium', 'high'));
+create index tasks_due_at_idx on public.tasks (author_id, due_at) where due_at is not null;
+
+alter table public.sessions
+  add column user_id uuid null,
+  add column user_agent text null,
+  add column ip_address text null;
+update public.sessions s set user_id = a.user_id from auth.sessions a where a.id = s.sb_id;
+create index sessions_user_id_idx on public.sessions (user_id);
+
+create or replace function public.handle_session_created () returns trigger language plpgsql security definer
+set
+  search_path = '' as $$
+begin
+  insert into public.sessions (id, sb_id, user_id, created_at, expiry_date)
+  values (new.id, new.id, new.user_id, new.created_at, new.not_after);
+  return new;
+end;
+$$;
+
+create table
+  public.passkeys (
+    id bigint generated by default as identity,
+    user_id uuid not null,
+    name text not null,
+    passkey jsonb not null,
+    created_at timestamp with time zone not null default now(),
+    last_used_at timestamp with time zone null,
+    constraint passkeys_pkey primary key (id),
+    constraint passkeys_user_id_fkey foreign key (user_id) references auth.users (id) on delete cascade
+  ) tablespace pg_default;
+
+create index passkeys_user_id_idx on public.passkeys (user_id);
+
+-- Written by the server with the service key once a WebAuthn ceremony is verified
+alter table public.passkeys enable row level security;
+create policy "Individuals can view their own passkeys." on public.passkeys for
+    select using ((select auth.uid()) = user_id);
+
+create table
+  public.mfa_recovery_codes (
+    id bigint generated by default as identity,
+    user_id uuid not null,
+    code_hash text not null,
+    created_at timestamp with time zone not null default now(),
+    constraint mfa_recovery_codes_pkey primary key (id),
+    constraint mfa_recovery_codes_user_id_code_hash_key unique (user_id, code_hash),
+    constraint mfa_recovery_codes_user_id_fkey foreign key (user_id) references auth.users (id) on delete cascade
+  ) tablespace pg_default;
+
+-- Only the server reads and redeems codes, with the service key
+alter table public.mfa_recovery_codes enable row level security;
+
+create table
+  public.trusted_devices (
+    id bigint generated by default as identity,
+    user_id uuid not null,
+    user_agent text null,
+    ip_address text null,
+    created_at timestamp with time zone not null default now(),
+    last_used_at timestamp with time zone null,
+    expires_at timestamp with time zone not null,
+    constraint trusted_devices_pkey primary key (id),
+    constraint trusted_devices_user_id_fkey foreign key (user_id) references auth.users (id) on delete cascade
+  ) tablespace pg_default;
+
+create index trusted_devices_user_id_idx on public.trusted_devices (user_id);
+
+-- Managed by the server with the service key, the cookie signature is checked there
+alter table public.trusted_devices enable row level security;
+
+create table
+  public.admin_audit_log (
+    id bigint generated by default as identity,
+    admin_id uuid not null,
+    action text not null,
+    target_user_id uuid not null,
+    details jsonb not null default '{}',
+    created_at timestamp with time zone not null default now(),
+    constraint admin_audit_log_pkey primary key (id)
+  ) tablespace pg_default;
+
+create index admin_audit_log_target_user_id_idx on public.admin_audit_log (target_user_id, created_at desc);
+
+-- Kept after the accounts involved are deleted, written and read by the server only
+alter table public.admin_audit_log enable row level security;